@@ -0,0 +1,7 @@
+-- Add down migration script here
+-- Drop the table
+DROP TABLE IF EXISTS fx_rates;
+
+-- Drop the currency columns
+ALTER TABLE quotes DROP COLUMN IF EXISTS currency;
+ALTER TABLE trades_history DROP COLUMN IF EXISTS currency;
\ No newline at end of file
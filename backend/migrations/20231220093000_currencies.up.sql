@@ -0,0 +1,28 @@
+-- Add up migration script here
+-- Record the currency each trade and quote is priced in
+ALTER TABLE trades_history ADD COLUMN currency VARCHAR(3);
+UPDATE trades_history SET currency = CASE country
+    WHEN 'US' THEN 'USD'
+    WHEN 'CA' THEN 'CAD'
+    WHEN 'UK' THEN 'GBP'
+    ELSE 'AUD'
+END;
+ALTER TABLE trades_history ALTER COLUMN currency SET NOT NULL;
+
+ALTER TABLE quotes ADD COLUMN currency VARCHAR(3);
+UPDATE quotes SET currency = CASE
+    WHEN ticker LIKE '%.AX' THEN 'AUD'
+    WHEN ticker LIKE '%.TO' OR ticker LIKE '%.V' OR ticker LIKE '%.NE' THEN 'CAD'
+    WHEN ticker LIKE '%.L' THEN 'GBP'
+    ELSE 'USD'
+END;
+ALTER TABLE quotes ALTER COLUMN currency SET NOT NULL;
+
+-- Create a new table to store daily exchange rates, one unit of from_currency in to_currency
+CREATE TABLE IF NOT EXISTS fx_rates (
+    from_currency VARCHAR(3) NOT NULL,
+    to_currency VARCHAR(3) NOT NULL,
+    date DATE NOT NULL,
+    rate NUMERIC(18,8) NOT NULL,
+    PRIMARY KEY (from_currency, to_currency, date)
+);
\ No newline at end of file
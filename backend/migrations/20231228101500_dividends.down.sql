@@ -0,0 +1,6 @@
+-- Add down migration script here
+-- Drop the index
+DROP INDEX IF EXISTS dividends_ticker;
+
+-- Drop the table
+DROP TABLE IF EXISTS dividends;
\ No newline at end of file
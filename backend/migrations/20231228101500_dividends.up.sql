@@ -0,0 +1,15 @@
+-- Add up migration script here
+-- Create a new table to store dividends and distributions, amounts are per share
+CREATE TABLE IF NOT EXISTS dividends (
+    id SERIAL PRIMARY KEY,
+    ticker VARCHAR(8) NOT NULL,
+    ex_date DATE NOT NULL,
+    pay_date DATE NOT NULL,
+    amount_per_share NUMERIC(12,6) NOT NULL,
+    franking_credits NUMERIC(12,6) NOT NULL DEFAULT 0,
+    withholding_rate NUMERIC(5,4) NOT NULL DEFAULT 0,
+    currency VARCHAR(3) NOT NULL
+);
+
+-- Create an index on the ticker column for faster lookups
+CREATE INDEX dividends_ticker ON dividends (ticker);
\ No newline at end of file
@@ -0,0 +1,7 @@
+-- Add down migration script here
+ALTER TABLE dividends ADD COLUMN franking_credits NUMERIC(12,6) NOT NULL DEFAULT 0;
+
+UPDATE dividends SET franking_credits = amount_per_share * franking_percent / 100 * company_tax_rate / (1 - company_tax_rate);
+
+ALTER TABLE dividends DROP COLUMN company_tax_rate;
+ALTER TABLE dividends DROP COLUMN franking_percent;
\ No newline at end of file
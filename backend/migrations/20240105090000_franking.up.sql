@@ -0,0 +1,9 @@
+-- Add up migration script here
+-- Track franking as the percentage of the dividend that is franked and the company tax rate it was franked at
+ALTER TABLE dividends ADD COLUMN franking_percent NUMERIC(5,2) NOT NULL DEFAULT 0;
+ALTER TABLE dividends ADD COLUMN company_tax_rate NUMERIC(5,4) NOT NULL DEFAULT 0.30;
+
+-- Existing credits were stored per share at the 30% rate
+UPDATE dividends SET franking_percent = LEAST(100, franking_credits * 7 / 3 / amount_per_share * 100) WHERE amount_per_share > 0;
+
+ALTER TABLE dividends DROP COLUMN franking_credits;
\ No newline at end of file
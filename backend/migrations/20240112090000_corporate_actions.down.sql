@@ -0,0 +1,12 @@
+-- Add down migration script here
+-- Drop the snapshot tables
+DROP TABLE IF EXISTS corporate_action_stocks;
+DROP TABLE IF EXISTS corporate_action_dividends;
+DROP TABLE IF EXISTS corporate_action_trades;
+DROP TABLE IF EXISTS corporate_action_quotes;
+
+-- Drop the index
+DROP INDEX IF EXISTS corporate_actions_ticker;
+
+-- Drop the table
+DROP TABLE IF EXISTS corporate_actions;
\ No newline at end of file
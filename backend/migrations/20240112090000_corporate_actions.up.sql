@@ -0,0 +1,56 @@
+-- Add up migration script here
+-- Create a new table to store splits, consolidations, ticker changes and mergers.
+-- Holders of ratio_from shares of ticker end up with ratio_to shares of new_ticker (or ticker) plus cash_per_share per old share.
+CREATE TABLE IF NOT EXISTS corporate_actions (
+    id SERIAL PRIMARY KEY,
+    ticker VARCHAR(8) NOT NULL,
+    action_type VARCHAR(16) NOT NULL,
+    effective_date DATE NOT NULL,
+    ratio_from INT NOT NULL DEFAULT 1,
+    ratio_to INT NOT NULL DEFAULT 1,
+    new_ticker VARCHAR(8),
+    cash_per_share NUMERIC(12,6) NOT NULL DEFAULT 0,
+    applied_at TIMESTAMP
+);
+
+-- Create an index on the ticker column for faster lookups
+CREATE INDEX corporate_actions_ticker ON corporate_actions (ticker);
+
+-- Rows as they were before an action was applied, so undoing it restores them exactly
+CREATE TABLE IF NOT EXISTS corporate_action_quotes (
+    action_id INT NOT NULL REFERENCES corporate_actions (id) ON DELETE CASCADE,
+    ticker VARCHAR(8) NOT NULL,
+    date DATE NOT NULL,
+    open NUMERIC(10,2) NOT NULL,
+    high NUMERIC(10,2) NOT NULL,
+    low NUMERIC(10,2) NOT NULL,
+    close NUMERIC(10,2) NOT NULL,
+    volume BIGINT NOT NULL,
+    PRIMARY KEY (action_id, ticker, date)
+);
+
+CREATE TABLE IF NOT EXISTS corporate_action_trades (
+    action_id INT NOT NULL REFERENCES corporate_actions (id) ON DELETE CASCADE,
+    trade_id INT NOT NULL,
+    ticker VARCHAR(8) NOT NULL,
+    amount INT NOT NULL,
+    price NUMERIC(10,2) NOT NULL,
+    PRIMARY KEY (action_id, trade_id)
+);
+
+CREATE TABLE IF NOT EXISTS corporate_action_dividends (
+    action_id INT NOT NULL REFERENCES corporate_actions (id) ON DELETE CASCADE,
+    dividend_id INT NOT NULL,
+    ticker VARCHAR(8) NOT NULL,
+    amount_per_share NUMERIC(12,6) NOT NULL,
+    PRIMARY KEY (action_id, dividend_id)
+);
+
+CREATE TABLE IF NOT EXISTS corporate_action_stocks (
+    action_id INT NOT NULL REFERENCES corporate_actions (id) ON DELETE CASCADE,
+    stock_id INT NOT NULL,
+    ticker VARCHAR(8) NOT NULL,
+    amount_held INT NOT NULL,
+    last_updated DATE NOT NULL,
+    PRIMARY KEY (action_id, stock_id)
+);
\ No newline at end of file
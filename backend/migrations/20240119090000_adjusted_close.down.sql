@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE quotes DROP COLUMN adjusted_close;
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- Close adjusted for dividends paid after each date, kept up to date from the dividends table
+ALTER TABLE quotes ADD COLUMN adjusted_close NUMERIC(12,4);
+UPDATE quotes SET adjusted_close = close;
+ALTER TABLE quotes ALTER COLUMN adjusted_close SET NOT NULL;
\ No newline at end of file
@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- Create a new table to store the tickers the portfolio is compared against
+CREATE TABLE IF NOT EXISTS benchmarks (
+    id SERIAL PRIMARY KEY,
+    ticker VARCHAR(8) NOT NULL UNIQUE,
+    name VARCHAR(64)
+);
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- Add down migration script here
+-- Drop the table
+DROP TABLE IF EXISTS target_allocations;
\ No newline at end of file
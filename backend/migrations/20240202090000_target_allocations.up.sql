@@ -0,0 +1,9 @@
+-- Add up migration script here
+-- Create a new table to store target weights per portfolio, as fractions of its value
+CREATE TABLE IF NOT EXISTS target_allocations (
+    id SERIAL PRIMARY KEY,
+    portfolio VARCHAR(32) NOT NULL DEFAULT 'default',
+    ticker VARCHAR(8) NOT NULL,
+    weight NUMERIC(7,6) NOT NULL,
+    UNIQUE (portfolio, ticker)
+);
\ No newline at end of file
@@ -0,0 +1,14 @@
+-- Add up migration script here
+-- Create a new table to store Markov chains fitted to daily returns so simulations can be repeated.
+-- transitions is tickers x buckets x buckets and bucket_returns tickers x buckets, both flattened row major.
+CREATE TABLE IF NOT EXISTS markov_models (
+    id SERIAL PRIMARY KEY,
+    tickers VARCHAR(8)[] NOT NULL,
+    buckets INT NOT NULL,
+    start_date DATE NOT NULL,
+    end_date DATE NOT NULL,
+    transitions FLOAT8[] NOT NULL,
+    bucket_returns FLOAT8[] NOT NULL,
+    initial_states INT[] NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
\ No newline at end of file
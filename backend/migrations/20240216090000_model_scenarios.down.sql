@@ -0,0 +1,4 @@
+-- Add down migration script here
+-- Drop the tables
+DROP TABLE IF EXISTS model_results;
+DROP TABLE IF EXISTS model_scenarios;
\ No newline at end of file
@@ -0,0 +1,16 @@
+-- Add up migration script here
+-- Create new tables to store modelling scenarios, as JSON config, and the results of running them
+CREATE TABLE IF NOT EXISTS model_scenarios (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(64) NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    config JSONB NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS model_results (
+    id SERIAL PRIMARY KEY,
+    scenario_id INT NOT NULL REFERENCES model_scenarios(id) ON DELETE CASCADE,
+    result JSONB NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
\ No newline at end of file
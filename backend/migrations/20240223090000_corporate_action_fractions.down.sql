@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE corporate_actions DROP COLUMN cash_in_lieu;
+ALTER TABLE corporate_actions DROP COLUMN fractional_shares;
+ALTER TABLE corporate_action_trades ALTER COLUMN price TYPE NUMERIC(10,2);
+ALTER TABLE trades_history ALTER COLUMN price TYPE NUMERIC(10,2);
\ No newline at end of file
@@ -0,0 +1,8 @@
+-- Add up migration script here
+-- Rescaled trade prices keep six decimal places so a trade's cost survives a split
+ALTER TABLE trades_history ALTER COLUMN price TYPE NUMERIC(16,6);
+ALTER TABLE corporate_action_trades ALTER COLUMN price TYPE NUMERIC(16,6);
+
+-- Fraction of a new share left over when an action was applied, and the cash paid for it
+ALTER TABLE corporate_actions ADD COLUMN fractional_shares NUMERIC(12,6) NOT NULL DEFAULT 0;
+ALTER TABLE corporate_actions ADD COLUMN cash_in_lieu NUMERIC(16,6);
\ No newline at end of file
@@ -0,0 +1,15 @@
+pub mod lots;
+pub mod fx;
+pub mod income;
+pub mod prices;
+pub mod performance;
+pub mod returns;
+pub mod benchmark;
+pub mod risk;
+pub mod correlation;
+pub mod indicators;
+pub mod rebalance;
+pub mod markov;
+pub mod fire;
+pub mod pnl;
+pub mod tax;
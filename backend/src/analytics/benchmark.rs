@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::analytics::performance::last_mark;
+use crate::analytics::pnl::Mark;
+use crate::analytics::returns::{compound, covariance, mean, standard_deviation, variance, TRADING_DAYS_PER_YEAR};
+
+/// How the portfolio did against one benchmark over the same days. Returns are fractions and
+/// tracking error and the information ratio are annualised from daily figures.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BenchmarkComparison {
+    pub ticker: String,
+    pub name: Option<String>,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// Number of daily returns compared
+    pub observations: usize,
+    pub portfolio_return: Option<f64>,
+    pub benchmark_return: Option<f64>,
+    pub excess_return: Option<f64>,
+    pub tracking_error: Option<f64>,
+    pub beta: Option<f64>,
+    pub information_ratio: Option<f64>,
+}
+
+/// Compares the portfolio's growth index with a benchmark's total return closes. Both are sampled
+/// on the benchmark's trading days so weekends and holidays don't count as flat days, which means
+/// `portfolio` has to cover the benchmark's last close before `start`.
+pub fn compare(ticker: &str, name: Option<String>, portfolio: &[Mark], benchmark: &[Mark], start: NaiveDate, end: NaiveDate) -> BenchmarkComparison {
+    let portfolio: HashMap<NaiveDate, f64> = portfolio.iter().map(|mark| (mark.date, mark.price)).collect();
+    // The first return is measured from the benchmark's last close before the period
+    let opening = last_mark(benchmark, start.pred_opt().unwrap_or(start)).map(|mark| mark.date).unwrap_or(start);
+    let mut pairs: Vec<(f64, f64)> = Vec::new();
+    let mut previous: Option<(f64, f64)> = None;
+    for mark in benchmark.iter().filter(|mark| mark.date >= opening && mark.date <= end) {
+        let growth = match portfolio.get(&mark.date) {
+            Some(growth) => *growth,
+            None => continue,
+        };
+        if let Some((previous_growth, previous_close)) = previous {
+            if previous_growth > 0.0 && previous_close > 0.0 {
+                pairs.push((growth / previous_growth - 1.0, mark.price / previous_close - 1.0));
+            }
+        }
+        previous = Some((growth, mark.price));
+    }
+    let portfolio_returns: Vec<f64> = pairs.iter().map(|(portfolio, _)| *portfolio).collect();
+    let benchmark_returns: Vec<f64> = pairs.iter().map(|(_, benchmark)| *benchmark).collect();
+    let active_returns: Vec<f64> = pairs.iter().map(|(portfolio, benchmark)| portfolio - benchmark).collect();
+    let (portfolio_return, benchmark_return) = match pairs.is_empty() {
+        true => (None, None),
+        false => (Some(compound(&portfolio_returns)), Some(compound(&benchmark_returns))),
+    };
+    let tracking_error = standard_deviation(&active_returns).map(|deviation| deviation * TRADING_DAYS_PER_YEAR.sqrt());
+    let beta = match (covariance(&portfolio_returns, &benchmark_returns), variance(&benchmark_returns)) {
+        (Some(covariance), Some(variance)) if variance > f64::EPSILON => Some(covariance / variance),
+        _ => None,
+    };
+    let information_ratio = match (mean(&active_returns), tracking_error) {
+        (Some(mean), Some(tracking_error)) if tracking_error > f64::EPSILON => Some(mean * TRADING_DAYS_PER_YEAR / tracking_error),
+        _ => None,
+    };
+    BenchmarkComparison {
+        ticker: ticker.to_string(),
+        name,
+        start,
+        end,
+        observations: pairs.len(),
+        portfolio_return,
+        benchmark_return,
+        excess_return: portfolio_return.zip(benchmark_return).map(|(portfolio, benchmark)| portfolio - benchmark),
+        tracking_error,
+        beta,
+        information_ratio,
+    }
+}
\ No newline at end of file
@@ -0,0 +1,131 @@
+use std::collections::{BTreeSet, HashMap};
+use chrono::NaiveDate;
+use ndarray::{Array2, Axis};
+use serde::{Deserialize, Serialize};
+use crate::analytics::pnl::Mark;
+
+/// Most daily returns a correlation can be worked out over, about twenty years
+pub const MAX_WINDOW: usize = 5000;
+
+#[derive(Debug)]
+pub enum CorrelationError {
+    Invalid(String),
+}
+
+impl std::fmt::Display for CorrelationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CorrelationError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for CorrelationError {}
+
+/// A window needs two returns for a covariance and is capped at `MAX_WINDOW`
+pub fn validate_window(window: usize) -> Result<usize, CorrelationError> {
+    match (2..=MAX_WINDOW).contains(&window) {
+        true => Ok(window),
+        false => Err(CorrelationError::Invalid(format!("Window must be between 2 and {} days", MAX_WINDOW))),
+    }
+}
+
+/// Covariance and correlation of daily returns, rows and columns in the order of `tickers`.
+/// Covariance is per day; correlation is `None` where a ticker's price never moved.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CorrelationMatrix {
+    pub tickers: Vec<String>,
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+    pub observations: usize,
+    pub covariance: Vec<Vec<f64>>,
+    pub correlation: Vec<Vec<Option<f64>>>,
+}
+
+/// Returns of every ticker over the last `window` days on which all of them have a close, one
+/// row per day and one column per ticker
+pub fn aligned_returns(series: &[(String, Vec<Mark>)], window: usize) -> (Vec<NaiveDate>, Array2<f64>) {
+    let closes: Vec<HashMap<NaiveDate, f64>> = series.iter()
+        .map(|(_, marks)| marks.iter().map(|mark| (mark.date, mark.price)).collect())
+        .collect();
+    let mut dates: BTreeSet<NaiveDate> = series.first()
+        .map(|(_, marks)| marks.iter().map(|mark| mark.date).collect())
+        .unwrap_or_default();
+    dates.retain(|date| closes.iter().all(|closes| closes.get(date).map(|close| *close > 0.0).unwrap_or(false)));
+    let dates: Vec<NaiveDate> = dates.into_iter().collect();
+    let dates = &dates[dates.len().saturating_sub(window + 1)..];
+    let rows = dates.len().saturating_sub(1);
+    let mut returns = Array2::<f64>::zeros((rows, series.len()));
+    for (row, pair) in dates.windows(2).enumerate() {
+        for (column, closes) in closes.iter().enumerate() {
+            returns[[row, column]] = closes[&pair[1]] / closes[&pair[0]] - 1.0;
+        }
+    }
+    (dates.iter().skip(1).copied().collect(), returns)
+}
+
+pub fn calculate(series: &[(String, Vec<Mark>)], window: usize) -> CorrelationMatrix {
+    let (dates, returns) = aligned_returns(series, window);
+    let observations = returns.nrows();
+    let tickers: Vec<String> = series.iter().map(|(ticker, _)| ticker.clone()).collect();
+    let size = tickers.len();
+    let covariance = match (observations > 1, returns.mean_axis(Axis(0))) {
+        (true, Some(means)) => {
+            let centred = &returns - &means;
+            centred.t().dot(&centred) / (observations - 1) as f64
+        },
+        _ => Array2::<f64>::zeros((size, size)),
+    };
+    let deviations: Vec<f64> = (0..size).map(|index| covariance[[index, index]].sqrt()).collect();
+    let correlation = (0..size)
+        .map(|row| (0..size)
+            .map(|column| match deviations[row] * deviations[column] {
+                product if product > f64::EPSILON => Some((covariance[[row, column]] / product).clamp(-1.0, 1.0)),
+                _ => None,
+            })
+            .collect())
+        .collect();
+    CorrelationMatrix {
+        tickers,
+        start: dates.first().copied(),
+        end: dates.last().copied(),
+        observations,
+        covariance: covariance.outer_iter().map(|row| row.to_vec()).collect(),
+        correlation,
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytics::lots::tests::assert_close;
+
+    fn marks(prices: &[f64]) -> Vec<Mark> {
+        let start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        prices.iter().enumerate()
+            .map(|(day, price)| Mark {
+                date: start + chrono::Duration::days(day as i64),
+                price: *price,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn windows_outside_the_bounds_are_rejected() {
+        assert!(validate_window(1).is_err());
+        assert!(validate_window(MAX_WINDOW + 1).is_err());
+        assert_eq!(validate_window(252).unwrap(), 252);
+    }
+
+    #[test]
+    fn opposite_moves_are_perfectly_negatively_correlated() {
+        let series = vec![
+            ("ABC".to_string(), marks(&[100.0, 110.0, 99.0, 108.9])),
+            ("XYZ".to_string(), marks(&[100.0, 90.0, 99.0, 89.1])),
+        ];
+        let matrix = calculate(&series, 2);
+        assert_eq!(matrix.observations, 2);
+        assert_eq!(matrix.start, Some(series[0].1[2].date));
+        assert_close(matrix.correlation[0][1].unwrap(), -1.0);
+        assert_eq!(matrix.correlation[0][0], Some(1.0));
+    }
+}
@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use crate::analytics::risk::percentile;
+
+/// Percentiles reported for years to FIRE and portfolio value
+pub const PERCENTILES: [f64; 5] = [0.1, 0.25, 0.5, 0.75, 0.9];
+
+/// What happened on one simulated path
+#[derive(Debug, Clone)]
+pub struct PathOutcome {
+    /// `None` when the path never reached FIRE
+    pub years_to_fire: Option<f64>,
+    /// Portfolio value at the start and at the end of each simulated year, shorter than the
+    /// others when the path stopped once it reached FIRE
+    pub yearly_values: Vec<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PercentileValue {
+    pub percentile: f64,
+    pub value: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct YearSummary {
+    pub year: usize,
+    /// Share of paths that reached FIRE by the end of this year
+    pub probability: f64,
+    /// Paths still running in this year, which the value percentiles are taken over
+    #[serde(default)]
+    pub paths: usize,
+    pub values: Vec<PercentileValue>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FireSummary {
+    pub paths: usize,
+    /// Share of paths that reached FIRE at all
+    pub probability: f64,
+    /// Years to FIRE across every path. A percentile is `None` when fewer paths than that reach FIRE.
+    pub years_to_fire: Vec<PercentileValue>,
+    pub years: Vec<YearSummary>,
+}
+
+pub fn summarise(outcomes: &[PathOutcome]) -> FireSummary {
+    let paths = outcomes.len();
+    let share = |count: usize| match paths {
+        0 => 0.0,
+        paths => count as f64 / paths as f64,
+    };
+    let mut years_to_fire: Vec<f64> = outcomes.iter()
+        .map(|outcome| outcome.years_to_fire.unwrap_or(f64::INFINITY))
+        .collect();
+    years_to_fire.sort_by(|a, b| a.total_cmp(b));
+    let reached = years_to_fire.iter().filter(|years| years.is_finite()).count();
+    let years = outcomes.iter().map(|outcome| outcome.yearly_values.len()).max().unwrap_or(0);
+    FireSummary {
+        paths,
+        probability: share(reached),
+        // Nearest rank, so paths that never get there count as later than any that do
+        years_to_fire: PERCENTILES.iter()
+            .map(|quantile| PercentileValue {
+                percentile: *quantile,
+                value: match paths {
+                    0 => None,
+                    paths => Some(years_to_fire[((quantile * paths as f64).ceil() as usize).clamp(1, paths) - 1])
+                        .filter(|years| years.is_finite()),
+                },
+            })
+            .collect(),
+        years: (0..years)
+            .map(|year| {
+                let values: Vec<f64> = outcomes.iter().filter_map(|outcome| outcome.yearly_values.get(year).copied()).collect();
+                YearSummary {
+                    year,
+                    probability: share(years_to_fire.iter().filter(|years| **years <= year as f64).count()),
+                    paths: values.len(),
+                    values: PERCENTILES.iter()
+                        .map(|quantile| PercentileValue {
+                            percentile: *quantile,
+                            value: percentile(&values, *quantile),
+                        })
+                        .collect(),
+                }
+            })
+            .collect(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PercentileComparison {
+    pub percentile: f64,
+    pub left: Option<f64>,
+    pub right: Option<f64>,
+    /// Right less left
+    pub difference: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct YearComparison {
+    pub year: usize,
+    pub left_probability: f64,
+    pub right_probability: f64,
+    pub left_median: Option<f64>,
+    pub right_median: Option<f64>,
+}
+
+/// Two summaries side by side, differences are the right less the left
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FireComparison {
+    pub probability_difference: f64,
+    pub years_to_fire: Vec<PercentileComparison>,
+    /// Portfolio value at the end of the last year both simulated
+    pub final_values: Vec<PercentileComparison>,
+    pub years: Vec<YearComparison>,
+}
+
+fn compare_percentiles(left: &[PercentileValue], right: &[PercentileValue]) -> Vec<PercentileComparison> {
+    left.iter().zip(right)
+        .map(|(left, right)| PercentileComparison {
+            percentile: left.percentile,
+            left: left.value,
+            right: right.value,
+            difference: right.value.zip(left.value).map(|(right, left)| right - left),
+        })
+        .collect()
+}
+
+fn median(values: &[PercentileValue]) -> Option<f64> {
+    values.iter().find(|value| value.percentile == 0.5).and_then(|value| value.value)
+}
+
+pub fn compare(left: &FireSummary, right: &FireSummary) -> FireComparison {
+    let years: Vec<YearComparison> = left.years.iter().zip(&right.years)
+        .map(|(left, right)| YearComparison {
+            year: left.year,
+            left_probability: left.probability,
+            right_probability: right.probability,
+            left_median: median(&left.values),
+            right_median: median(&right.values),
+        })
+        .collect();
+    let final_values = match years.len() {
+        0 => Vec::new(),
+        len => compare_percentiles(&left.years[len - 1].values, &right.years[len - 1].values),
+    };
+    FireComparison {
+        probability_difference: right.probability - left.probability,
+        years_to_fire: compare_percentiles(&left.years_to_fire, &right.years_to_fire),
+        final_values,
+        years,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_that_stop_early_leave_later_years_to_the_rest() {
+        let outcomes = vec![
+            PathOutcome { years_to_fire: Some(0.5), yearly_values: vec![100.0, 200.0] },
+            PathOutcome { years_to_fire: Some(1.5), yearly_values: vec![100.0, 150.0, 300.0] },
+            PathOutcome { years_to_fire: None, yearly_values: vec![100.0, 110.0, 120.0, 130.0] },
+        ];
+        let summary = summarise(&outcomes);
+        assert_eq!(summary.years.len(), 4);
+        let paths: Vec<usize> = summary.years.iter().map(|year| year.paths).collect();
+        assert_eq!(paths, vec![3, 3, 2, 1]);
+        // Probabilities are always over every path
+        let probabilities: Vec<f64> = summary.years.iter().map(|year| year.probability).collect();
+        assert_eq!(probabilities, vec![0.0, 1.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0]);
+        assert_eq!(summary.years[2].values[2].value, Some(210.0));
+        assert_eq!(summary.years[3].values[2].value, Some(130.0));
+        assert_eq!(summary.years_to_fire[2].value, Some(1.5));
+        assert_eq!(summary.years_to_fire[4].value, None);
+    }
+}
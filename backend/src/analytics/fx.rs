@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+use bigdecimal::ToPrimitive;
+use chrono::NaiveDate;
+use crate::models::fx::{Currency, FxRateModel};
+
+/// Exchange rates loaded up front so conversions don't need a query per date
+#[derive(Debug, Clone, Default)]
+pub struct FxTable {
+    rates: HashMap<(Currency, Currency), Vec<(NaiveDate, f64)>>,
+}
+
+impl FxTable {
+    pub fn from_rates(rates: Vec<FxRateModel>) -> Self {
+        let mut table = Self::default();
+        for rate in rates {
+            table.rates
+                .entry((rate.from_currency, rate.to_currency))
+                .or_default()
+                .push((rate.date, rate.rate.to_f64().unwrap_or(0.0)));
+        }
+        for series in table.rates.values_mut() {
+            series.sort_by_key(|(date, _)| *date);
+        }
+        table
+    }
+    fn lookup(&self, from: Currency, to: Currency, date: NaiveDate) -> Option<f64> {
+        let series = self.rates.get(&(from, to))?;
+        let index = series.partition_point(|(rate_date, _)| *rate_date <= date);
+        match index {
+            0 => None,
+            _ => Some(series[index - 1].1).filter(|rate| *rate > 0.0),
+        }
+    }
+    /// Units of `to` for one unit of `from` on the latest date with a rate on or before `date`,
+    /// inverting the opposite pair when only that one is stored
+    pub fn rate(&self, from: Currency, to: Currency, date: NaiveDate) -> Option<f64> {
+        if from == to {
+            return Some(1.0);
+        }
+        self.lookup(from, to, date).or(self.lookup(to, from, date).map(|rate| 1.0 / rate))
+    }
+}
@@ -0,0 +1,131 @@
+use bigdecimal::ToPrimitive;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::analytics::fx::FxTable;
+use crate::analytics::tax::au::{franking_credit, COMPANY_TAX_RATE};
+use crate::models::dividends::DividendModel;
+use crate::models::fx::Currency;
+use crate::models::trades::{TradeModel, TradeType};
+
+/// What the portfolio received from one dividend, in the dividend's currency
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entitlement {
+    pub dividend_id: i32,
+    pub ticker: String,
+    pub ex_date: NaiveDate,
+    pub pay_date: NaiveDate,
+    pub currency: Currency,
+    pub shares: f64,
+    pub amount_per_share: f64,
+    pub gross: f64,
+    pub franking_percent: f64,
+    pub franked_amount: f64,
+    pub unfranked_amount: f64,
+    pub franking_credits: f64,
+    /// Gross dividend plus franking credits, the amount that is assessable in Australia
+    pub grossed_up: f64,
+    pub withholding_tax: f64,
+    pub net: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct IncomeTotals {
+    pub gross: f64,
+    pub franked_amount: f64,
+    pub unfranked_amount: f64,
+    pub franking_credits: f64,
+    pub grossed_up: f64,
+    pub withholding_tax: f64,
+    pub net: f64,
+}
+
+impl IncomeTotals {
+    pub fn add(&mut self, entitlement: &Entitlement, rate: f64) {
+        self.gross += entitlement.gross * rate;
+        self.franked_amount += entitlement.franked_amount * rate;
+        self.unfranked_amount += entitlement.unfranked_amount * rate;
+        self.franking_credits += entitlement.franking_credits * rate;
+        self.grossed_up += entitlement.grossed_up * rate;
+        self.withholding_tax += entitlement.withholding_tax * rate;
+        self.net += entitlement.net * rate;
+    }
+}
+
+/// Dividend income over a period with totals in `base`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IncomeSummary {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub base: Currency,
+    pub missing_fx: bool,
+    pub entitlements: Vec<Entitlement>,
+    pub totals: IncomeTotals,
+}
+
+/// Shares of `ticker` held at the close of the day before `ex_date`, which is what a dividend is paid on
+pub fn shares_held_before(trades: &[TradeModel], ticker: &str, ex_date: NaiveDate) -> f64 {
+    trades.iter()
+        .filter(|trade| trade.ticker == ticker && trade.date < ex_date)
+        .fold(0.0, |total, trade| match trade.trade_type {
+            TradeType::Buy => total + trade.amount as f64,
+            TradeType::Sell => total - trade.amount as f64,
+        })
+        .max(0.0)
+}
+
+/// Entitlements to every dividend the trade history held shares for on the ex-date
+pub fn entitlements(dividends: &[DividendModel], trades: &[TradeModel]) -> Vec<Entitlement> {
+    let mut entitlements = Vec::new();
+    for dividend in dividends {
+        let shares = shares_held_before(trades, &dividend.ticker, dividend.ex_date);
+        if shares <= 0.0 {
+            continue;
+        }
+        let amount_per_share = dividend.amount_per_share.to_f64().unwrap_or(0.0);
+        let gross = shares * amount_per_share;
+        let withholding_tax = gross * dividend.withholding_rate.to_f64().unwrap_or(0.0);
+        let franking_percent = dividend.franking_percent.to_f64().unwrap_or(0.0).clamp(0.0, 100.0);
+        let franked_amount = gross * franking_percent / 100.0;
+        let franking_credits = franking_credit(franked_amount, dividend.company_tax_rate.to_f64().unwrap_or(COMPANY_TAX_RATE));
+        entitlements.push(Entitlement {
+            dividend_id: dividend.id,
+            ticker: dividend.ticker.clone(),
+            ex_date: dividend.ex_date,
+            pay_date: dividend.pay_date,
+            currency: dividend.currency,
+            shares,
+            amount_per_share,
+            gross,
+            franking_percent,
+            franked_amount,
+            unfranked_amount: gross - franked_amount,
+            franking_credits,
+            grossed_up: gross + franking_credits,
+            withholding_tax,
+            net: gross - withholding_tax,
+        });
+    }
+    entitlements.sort_by_key(|entitlement| (entitlement.pay_date, entitlement.dividend_id));
+    entitlements
+}
+
+/// Totals an income period, converting each entitlement into `base` at the rate on its pay date
+pub fn summarise(entitlements: Vec<Entitlement>, fx: &FxTable, base: Currency, start: NaiveDate, end: NaiveDate) -> IncomeSummary {
+    let mut totals = IncomeTotals::default();
+    let mut missing_fx = false;
+    for entitlement in &entitlements {
+        let rate = fx.rate(entitlement.currency, base, entitlement.pay_date).unwrap_or_else(|| {
+            missing_fx = true;
+            1.0
+        });
+        totals.add(entitlement, rate);
+    }
+    IncomeSummary {
+        start,
+        end,
+        base,
+        missing_fx,
+        entitlements,
+        totals,
+    }
+}
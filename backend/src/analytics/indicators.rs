@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+use bigdecimal::ToPrimitive;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::models::quotes::QuoteModel;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IndicatorKind {
+    Sma,
+    Ema,
+    Rsi,
+    Macd,
+    Bollinger,
+    Atr,
+    HighLow,
+}
+
+impl IndicatorKind {
+    pub fn default_window(&self) -> usize {
+        match self {
+            IndicatorKind::Rsi | IndicatorKind::Atr => 14,
+            IndicatorKind::Macd => 9,
+            _ => 20,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum IndicatorError {
+    Invalid(String),
+}
+
+impl std::fmt::Display for IndicatorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndicatorError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for IndicatorError {}
+
+/// Settings for `calculate`. `window` is the signal period for MACD, `deviations` only applies
+/// to Bollinger Bands.
+#[derive(Debug, Clone, Copy)]
+pub struct IndicatorSettings {
+    pub kind: IndicatorKind,
+    pub window: usize,
+    pub fast: usize,
+    pub slow: usize,
+    pub deviations: f64,
+}
+
+/// Indicator values for one day, keyed by line name, e.g. `upper`, `middle` and `lower`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IndicatorPoint {
+    pub date: NaiveDate,
+    #[serde(flatten)]
+    pub values: BTreeMap<String, f64>,
+}
+
+/// Simple moving average, `None` until `window` values have been seen
+pub fn sma(values: &[f64], window: usize) -> Vec<Option<f64>> {
+    let mut sum = 0.0;
+    values.iter().enumerate()
+        .map(|(index, value)| {
+            sum += value;
+            if index >= window {
+                sum -= values[index - window];
+            }
+            match index + 1 >= window {
+                true => Some(sum / window as f64),
+                false => None,
+            }
+        })
+        .collect()
+}
+
+/// Exponential moving average seeded with the simple average of the first `window` values
+pub fn ema(values: &[f64], window: usize) -> Vec<Option<f64>> {
+    let alpha = 2.0 / (window as f64 + 1.0);
+    smooth(values, window, alpha)
+}
+
+/// Wilder's smoothing, which is an exponential average with `1 / window` weight on the latest value
+fn wilder(values: &[f64], window: usize) -> Vec<Option<f64>> {
+    smooth(values, window, 1.0 / window as f64)
+}
+
+fn smooth(values: &[f64], window: usize, alpha: f64) -> Vec<Option<f64>> {
+    let mut average: Option<f64> = None;
+    values.iter().enumerate()
+        .map(|(index, value)| {
+            average = match average {
+                Some(previous) => Some(previous + alpha * (value - previous)),
+                None if index + 1 == window => Some(values[..window].iter().fold(0.0, |sum, value| sum + value) / window as f64),
+                None => None,
+            };
+            average
+        })
+        .collect()
+}
+
+/// Relative strength index from Wilder smoothed gains and losses, 0 to 100
+pub fn rsi(closes: &[f64], window: usize) -> Vec<Option<f64>> {
+    let changes: Vec<f64> = closes.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let gains: Vec<f64> = changes.iter().map(|change| change.max(0.0)).collect();
+    let losses: Vec<f64> = changes.iter().map(|change| (-change).max(0.0)).collect();
+    let strength = wilder(&gains, window).into_iter()
+        .zip(wilder(&losses, window))
+        .map(|(gain, loss)| match (gain, loss) {
+            (Some(gain), Some(loss)) if loss > 0.0 => Some(100.0 - 100.0 / (1.0 + gain / loss)),
+            (Some(gain), Some(_)) if gain > 0.0 => Some(100.0),
+            (Some(_), Some(_)) => Some(50.0),
+            _ => None,
+        });
+    // The first close has no change so it never has a value
+    std::iter::once(None).chain(strength).take(closes.len()).collect()
+}
+
+/// MACD line (fast EMA less slow EMA), its signal line and the histogram between them
+pub fn macd(closes: &[f64], fast: usize, slow: usize, signal: usize) -> (Vec<Option<f64>>, Vec<Option<f64>>, Vec<Option<f64>>) {
+    let line: Vec<Option<f64>> = ema(closes, fast).into_iter()
+        .zip(ema(closes, slow))
+        .map(|(fast, slow)| Some(fast? - slow?))
+        .collect();
+    let offset = line.iter().take_while(|value| value.is_none()).count();
+    let defined: Vec<f64> = line.iter().flatten().copied().collect();
+    let signal_line: Vec<Option<f64>> = std::iter::repeat(None).take(offset)
+        .chain(ema(&defined, signal))
+        .collect();
+    let histogram = line.iter().zip(&signal_line)
+        .map(|(line, signal)| Some((*line)? - (*signal)?))
+        .collect();
+    (line, signal_line, histogram)
+}
+
+/// Simple moving average with bands `deviations` population standard deviations either side
+pub fn bollinger(closes: &[f64], window: usize, deviations: f64) -> (Vec<Option<f64>>, Vec<Option<f64>>, Vec<Option<f64>>) {
+    let middle = sma(closes, window);
+    let (upper, lower) = middle.iter().enumerate()
+        .map(|(index, average)| match average {
+            Some(average) => {
+                let variance = closes[index + 1 - window..=index].iter()
+                    .fold(0.0, |sum, close| sum + (close - average).powi(2)) / window as f64;
+                let width = deviations * variance.sqrt();
+                (Some(average + width), Some(average - width))
+            },
+            None => (None, None),
+        })
+        .unzip();
+    (upper, middle, lower)
+}
+
+/// Average true range with Wilder smoothing. The first day's true range is just its high less low.
+pub fn atr(highs: &[f64], lows: &[f64], closes: &[f64], window: usize) -> Vec<Option<f64>> {
+    let true_ranges: Vec<f64> = (0..closes.len())
+        .map(|index| {
+            let range = highs[index] - lows[index];
+            match index {
+                0 => range,
+                _ => range
+                    .max((highs[index] - closes[index - 1]).abs())
+                    .max((lows[index] - closes[index - 1]).abs()),
+            }
+        })
+        .collect();
+    wilder(&true_ranges, window)
+}
+
+/// Highest high and lowest low over the last `window` days
+pub fn rolling_high_low(highs: &[f64], lows: &[f64], window: usize) -> (Vec<Option<f64>>, Vec<Option<f64>>) {
+    (0..highs.len())
+        .map(|index| match index + 1 >= window {
+            true => {
+                let range = index + 1 - window..=index;
+                (
+                    Some(highs[range.clone()].iter().fold(f64::MIN, |high, value| high.max(*value))),
+                    Some(lows[range].iter().fold(f64::MAX, |low, value| low.min(*value))),
+                )
+            },
+            false => (None, None),
+        })
+        .unzip()
+}
+
+/// Indicator lines for `quotes`, which must be sorted oldest first. Days before every line has
+/// enough history are left out.
+pub fn calculate(quotes: &[QuoteModel], settings: IndicatorSettings) -> Result<Vec<IndicatorPoint>, IndicatorError> {
+    if settings.window == 0 || settings.fast == 0 || settings.slow == 0 {
+        return Err(IndicatorError::Invalid("Windows must be at least one day".to_string()));
+    }
+    if settings.kind == IndicatorKind::Macd && settings.fast >= settings.slow {
+        return Err(IndicatorError::Invalid("The fast window must be shorter than the slow window".to_string()));
+    }
+    let closes: Vec<f64> = quotes.iter().map(|quote| quote.close.to_f64().unwrap_or(0.0)).collect();
+    let highs: Vec<f64> = quotes.iter().map(|quote| quote.high.to_f64().unwrap_or(0.0)).collect();
+    let lows: Vec<f64> = quotes.iter().map(|quote| quote.low.to_f64().unwrap_or(0.0)).collect();
+    let window = settings.window;
+    let lines: Vec<(&str, Vec<Option<f64>>)> = match settings.kind {
+        IndicatorKind::Sma => vec![("sma", sma(&closes, window))],
+        IndicatorKind::Ema => vec![("ema", ema(&closes, window))],
+        IndicatorKind::Rsi => vec![("rsi", rsi(&closes, window))],
+        IndicatorKind::Macd => {
+            let (line, signal, histogram) = macd(&closes, settings.fast, settings.slow, window);
+            vec![("macd", line), ("signal", signal), ("histogram", histogram)]
+        },
+        IndicatorKind::Bollinger => {
+            let (upper, middle, lower) = bollinger(&closes, window, settings.deviations);
+            vec![("upper", upper), ("middle", middle), ("lower", lower)]
+        },
+        IndicatorKind::Atr => vec![("atr", atr(&highs, &lows, &closes, window))],
+        IndicatorKind::HighLow => {
+            let (high, low) = rolling_high_low(&highs, &lows, window);
+            vec![("high", high), ("low", low)]
+        },
+    };
+    Ok(quotes.iter().enumerate()
+        .filter_map(|(index, quote)| {
+            let values = lines.iter()
+                .map(|(name, values)| Some((name.to_string(), values[index]?)))
+                .collect::<Option<BTreeMap<String, f64>>>()?;
+            Some(IndicatorPoint {
+                date: quote.date,
+                values,
+            })
+        })
+        .collect())
+}
\ No newline at end of file
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use bigdecimal::ToPrimitive;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use strum_macros::{EnumString, Display};
+use crate::models::fx::Currency;
+use crate::models::trades::{TradeModel, TradeType};
+
+/// How a sell is matched against the open lots of a ticker
+#[derive(Serialize, Deserialize, Debug, EnumString, Display, Clone, Copy, PartialEq, Default)]
+pub enum CostBasisMethod {
+    #[default]
+    Fifo,
+    Lifo,
+    HighestCost,
+    AverageCost,
+}
+
+/// Shares acquired by a single buy that have not yet been sold
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Lot {
+    pub trade_id: i32,
+    pub ticker: String,
+    pub acquired: NaiveDate,
+    pub quantity: f64,
+    pub unit_cost: f64,
+    pub currency: Currency,
+}
+
+impl Lot {
+    pub fn cost_basis(&self) -> f64 {
+        self.quantity * self.unit_cost
+    }
+}
+
+/// The part of a sell that was matched against one lot
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LotMatch {
+    pub lot_trade_id: i32,
+    pub acquired: NaiveDate,
+    pub quantity: f64,
+    pub unit_cost: f64,
+    pub cost_basis: f64,
+    pub proceeds: f64,
+    pub gain: f64,
+}
+
+/// A sell together with the lots it closed and the gain it realised
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Disposal {
+    pub trade_id: i32,
+    pub ticker: String,
+    pub currency: Currency,
+    pub sold: NaiveDate,
+    pub quantity: f64,
+    pub unit_price: f64,
+    pub proceeds: f64,
+    pub cost_basis: f64,
+    pub gain: f64,
+    /// Shares sold that had no open lot to match, e.g. a trade history that starts mid-position.
+    /// Their proceeds are left out of `proceeds` since they have no cost to set against.
+    pub unmatched_quantity: f64,
+    pub unmatched_proceeds: f64,
+    pub matches: Vec<LotMatch>,
+}
+
+/// Replays trades per ticker, keeping the open lots and every disposal
+pub struct LotEngine {
+    pub method: CostBasisMethod,
+    open: HashMap<String, Vec<Lot>>,
+    disposals: Vec<Disposal>,
+}
+
+impl LotEngine {
+    pub fn new(method: CostBasisMethod) -> Self {
+        Self {
+            method,
+            open: HashMap::new(),
+            disposals: Vec::new(),
+        }
+    }
+    /// Builds the engine from trades in any order; trades are applied by date then id
+    pub fn replay(method: CostBasisMethod, trades: &[TradeModel]) -> Self {
+        let mut engine = Self::new(method);
+        for trade in sorted_trades(trades) {
+            engine.apply(trade);
+        }
+        engine
+    }
+    pub fn apply(&mut self, trade: &TradeModel) {
+        let quantity = trade.amount as f64;
+        let unit_price = trade.price.to_f64().unwrap_or(0.0);
+        match trade.trade_type {
+            TradeType::Buy => self.buy(trade.id, &trade.ticker, trade.currency, trade.date, quantity, unit_price),
+            TradeType::Sell => {
+                let disposal = self.sell(trade.id, &trade.ticker, trade.currency, trade.date, quantity, unit_price);
+                self.disposals.push(disposal);
+            }
+        }
+    }
+    pub fn buy(&mut self, trade_id: i32, ticker: &str, currency: Currency, date: NaiveDate, quantity: f64, unit_cost: f64) {
+        let lots = self.open.entry(ticker.to_string()).or_default();
+        lots.push(Lot {
+            trade_id,
+            ticker: ticker.to_string(),
+            acquired: date,
+            quantity,
+            unit_cost,
+            currency,
+        });
+        if self.method == CostBasisMethod::AverageCost {
+            // Keep every lot at the pooled cost so sells consume them in order at the average
+            let (quantity, cost) = totals(lots);
+            if quantity > 0.0 {
+                for lot in lots.iter_mut() {
+                    lot.unit_cost = cost / quantity;
+                }
+            }
+        }
+    }
+    pub fn sell(&mut self, trade_id: i32, ticker: &str, currency: Currency, date: NaiveDate, quantity: f64, unit_price: f64) -> Disposal {
+        let method = self.method;
+        let lots = self.open.entry(ticker.to_string()).or_default();
+        let mut remaining = quantity;
+        let mut matches = Vec::new();
+        while remaining > f64::EPSILON {
+            let index = match next_lot(lots, method) {
+                Some(index) => index,
+                None => break,
+            };
+            let lot = &mut lots[index];
+            let matched = remaining.min(lot.quantity);
+            let cost_basis = matched * lot.unit_cost;
+            let proceeds = matched * unit_price;
+            matches.push(LotMatch {
+                lot_trade_id: lot.trade_id,
+                acquired: lot.acquired,
+                quantity: matched,
+                unit_cost: lot.unit_cost,
+                cost_basis,
+                proceeds,
+                gain: proceeds - cost_basis,
+            });
+            lot.quantity -= matched;
+            remaining -= matched;
+            if lot.quantity <= f64::EPSILON {
+                lots.remove(index);
+            }
+        }
+        let cost_basis = matches.iter().fold(0.0, |total, m| total + m.cost_basis);
+        let proceeds = matches.iter().fold(0.0, |total, m| total + m.proceeds);
+        let unmatched_quantity = remaining.max(0.0);
+        Disposal {
+            trade_id,
+            ticker: ticker.to_string(),
+            currency,
+            sold: date,
+            quantity,
+            unit_price,
+            proceeds,
+            cost_basis,
+            gain: matches.iter().fold(0.0, |total, m| total + m.gain),
+            unmatched_quantity,
+            unmatched_proceeds: unmatched_quantity * unit_price,
+            matches,
+        }
+    }
+    pub fn tickers(&self) -> Vec<String> {
+        let mut tickers: Vec<String> = self.open.iter()
+            .filter(|(_, lots)| !lots.is_empty())
+            .map(|(ticker, _)| ticker.clone())
+            .collect();
+        tickers.sort();
+        tickers
+    }
+    pub fn open_lots(&self, ticker: &str) -> &[Lot] {
+        self.open.get(ticker).map(|lots| lots.as_slice()).unwrap_or(&[])
+    }
+    pub fn quantity(&self, ticker: &str) -> f64 {
+        totals(self.open_lots(ticker)).0
+    }
+    pub fn cost_basis(&self, ticker: &str) -> f64 {
+        totals(self.open_lots(ticker)).1
+    }
+    pub fn average_cost(&self, ticker: &str) -> Option<f64> {
+        let (quantity, cost) = totals(self.open_lots(ticker));
+        match quantity > 0.0 {
+            true => Some(cost / quantity),
+            false => None,
+        }
+    }
+    pub fn disposals(&self) -> &[Disposal] {
+        &self.disposals
+    }
+}
+
+pub fn sorted_trades(trades: &[TradeModel]) -> Vec<&TradeModel> {
+    let mut sorted: Vec<&TradeModel> = trades.iter().collect();
+    sorted.sort_by_key(|trade| (trade.date, trade.id));
+    sorted
+}
+
+fn totals(lots: &[Lot]) -> (f64, f64) {
+    lots.iter().fold((0.0, 0.0), |(quantity, cost), lot| (quantity + lot.quantity, cost + lot.cost_basis()))
+}
+
+fn next_lot(lots: &[Lot], method: CostBasisMethod) -> Option<usize> {
+    if lots.is_empty() {
+        return None;
+    }
+    match method {
+        CostBasisMethod::Fifo | CostBasisMethod::AverageCost => Some(0),
+        CostBasisMethod::Lifo => Some(lots.len() - 1),
+        CostBasisMethod::HighestCost => lots.iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.unit_cost.total_cmp(&b.unit_cost))
+            .map(|(index, _)| index),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use bigdecimal::FromPrimitive;
+    use sqlx::types::BigDecimal;
+    use crate::models::trades::Country;
+
+    /// A trade in `ABC` for the engine tests, `date` is `YYYY-MM-DD`
+    pub(crate) fn trade(id: i32, country: Country, date: &str, trade_type: TradeType, amount: i32, price: f64) -> TradeModel {
+        TradeModel {
+            id,
+            ticker: "ABC".to_string(),
+            amount,
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            country,
+            price: BigDecimal::from_f64(price).unwrap(),
+            trade_type,
+            currency: Currency::default(),
+        }
+    }
+
+    pub(crate) fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} is not {}", actual, expected);
+    }
+
+    /// Three buys at 10, 20 and 15 then a sell of 12 at 30, which only partly uses a lot
+    fn trades() -> Vec<TradeModel> {
+        vec![
+            trade(1, Country::AU, "2020-01-01", TradeType::Buy, 10, 10.0),
+            trade(2, Country::AU, "2020-02-01", TradeType::Buy, 10, 20.0),
+            trade(3, Country::AU, "2020-03-01", TradeType::Buy, 5, 15.0),
+            trade(4, Country::AU, "2020-04-01", TradeType::Sell, 12, 30.0),
+        ]
+    }
+
+    fn open(engine: &LotEngine) -> Vec<(i32, f64, f64)> {
+        engine.open_lots("ABC").iter().map(|lot| (lot.trade_id, lot.quantity, lot.unit_cost)).collect()
+    }
+
+    #[test]
+    fn fifo_sells_oldest_lots_first() {
+        let engine = LotEngine::replay(CostBasisMethod::Fifo, &trades());
+        let disposal = &engine.disposals()[0];
+        let matched: Vec<(i32, f64)> = disposal.matches.iter().map(|m| (m.lot_trade_id, m.quantity)).collect();
+        assert_eq!(matched, vec![(1, 10.0), (2, 2.0)]);
+        assert_eq!(disposal.proceeds, 360.0);
+        assert_eq!(disposal.cost_basis, 140.0);
+        assert_eq!(disposal.gain, 220.0);
+        assert_eq!(open(&engine), vec![(2, 8.0, 20.0), (3, 5.0, 15.0)]);
+        assert_eq!(engine.cost_basis("ABC"), 235.0);
+    }
+
+    #[test]
+    fn lifo_sells_newest_lots_first() {
+        let engine = LotEngine::replay(CostBasisMethod::Lifo, &trades());
+        let disposal = &engine.disposals()[0];
+        let matched: Vec<(i32, f64)> = disposal.matches.iter().map(|m| (m.lot_trade_id, m.quantity)).collect();
+        assert_eq!(matched, vec![(3, 5.0), (2, 7.0)]);
+        assert_eq!(disposal.cost_basis, 215.0);
+        assert_eq!(disposal.gain, 145.0);
+        assert_eq!(open(&engine), vec![(1, 10.0, 10.0), (2, 3.0, 20.0)]);
+    }
+
+    #[test]
+    fn highest_cost_sells_dearest_lots_first() {
+        let engine = LotEngine::replay(CostBasisMethod::HighestCost, &trades());
+        let disposal = &engine.disposals()[0];
+        let matched: Vec<(i32, f64)> = disposal.matches.iter().map(|m| (m.lot_trade_id, m.quantity)).collect();
+        assert_eq!(matched, vec![(2, 10.0), (3, 2.0)]);
+        assert_eq!(disposal.cost_basis, 230.0);
+        assert_eq!(disposal.gain, 130.0);
+        assert_eq!(open(&engine), vec![(1, 10.0, 10.0), (3, 3.0, 15.0)]);
+    }
+
+    #[test]
+    fn average_cost_pools_every_buy() {
+        let mut trades = trades();
+        trades.push(trade(5, Country::AU, "2020-05-01", TradeType::Buy, 7, 25.0));
+        let engine = LotEngine::replay(CostBasisMethod::AverageCost, &trades);
+        let disposal = &engine.disposals()[0];
+        // 375 over 25 shares is 15 a share
+        assert_eq!(disposal.cost_basis, 180.0);
+        assert_eq!(disposal.gain, 180.0);
+        // 13 left at 15 plus 7 at 25 is 370 over 20 shares
+        assert_eq!(engine.quantity("ABC"), 20.0);
+        assert_eq!(engine.average_cost("ABC"), Some(18.5));
+    }
+
+    #[test]
+    fn replay_orders_by_date_then_id() {
+        let mut trades = trades();
+        trades.reverse();
+        let engine = LotEngine::replay(CostBasisMethod::Fifo, &trades);
+        assert_eq!(engine.disposals()[0].gain, 220.0);
+        assert_eq!(engine.disposals()[0].unmatched_quantity, 0.0);
+    }
+
+    #[test]
+    fn selling_more_than_held_leaves_the_rest_unmatched() {
+        let mut trades = trades();
+        trades.push(trade(5, Country::AU, "2020-05-01", TradeType::Sell, 20, 30.0));
+        let engine = LotEngine::replay(CostBasisMethod::Fifo, &trades);
+        let disposal = &engine.disposals()[1];
+        assert_eq!(disposal.unmatched_quantity, 7.0);
+        assert_eq!(disposal.unmatched_proceeds, 210.0);
+        assert_eq!(disposal.proceeds, 390.0);
+        assert_eq!(disposal.cost_basis, 235.0);
+        assert_eq!(disposal.gain, disposal.proceeds - disposal.cost_basis);
+        assert!(engine.tickers().is_empty());
+        assert_eq!(engine.average_cost("ABC"), None);
+    }
+}
@@ -0,0 +1,201 @@
+use chrono::NaiveDate;
+use ndarray::{Array2, Array3};
+use crate::analytics::pnl::Mark;
+use crate::analytics::returns::simple_returns;
+use crate::models::markov::MarkovFitModel;
+
+/// Return buckets used when a request doesn't choose
+pub const DEFAULT_BUCKETS: usize = 5;
+
+/// Most return buckets a fit can have, which keeps each ticker's matrix to 10,000 entries
+pub const MAX_BUCKETS: usize = 100;
+
+#[derive(Debug)]
+pub enum MarkovError {
+    Invalid(String),
+}
+
+impl std::fmt::Display for MarkovError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MarkovError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for MarkovError {}
+
+/// One Markov chain per ticker over buckets of daily returns. Bucket edges are quantiles of the
+/// ticker's returns so every bucket is seen about as often, and each bucket steps the price by
+/// the mean return that fell into it.
+#[derive(Debug, Clone)]
+pub struct MarkovFit {
+    pub tickers: Vec<String>,
+    pub buckets: usize,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// Probability of moving from the second index's bucket to the third's, per ticker
+    pub transitions: Array3<f64>,
+    pub bucket_returns: Array2<f64>,
+    /// Bucket of each ticker's last return, where simulations start
+    pub initial_states: Vec<usize>,
+}
+
+/// Upper edges of all but the last bucket
+fn bucket_edges(returns: &[f64], buckets: usize) -> Vec<f64> {
+    let mut sorted = returns.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    (1..buckets).map(|bucket| sorted[bucket * sorted.len() / buckets]).collect()
+}
+
+pub fn validate_buckets(buckets: usize) -> Result<(), MarkovError> {
+    match (2..=MAX_BUCKETS).contains(&buckets) {
+        true => Ok(()),
+        false => Err(MarkovError::Invalid(format!("Buckets must be between 2 and {}", MAX_BUCKETS))),
+    }
+}
+
+/// Fits every ticker in `series`. Each needs at least two daily returns per bucket, checked before
+/// anything is allocated.
+pub fn fit(series: &[(String, Vec<Mark>)], buckets: usize, start: NaiveDate, end: NaiveDate) -> Result<MarkovFit, MarkovError> {
+    validate_buckets(buckets)?;
+    if series.is_empty() {
+        return Err(MarkovError::Invalid("No tickers to fit".to_string()));
+    }
+    let series_returns: Vec<Vec<f64>> = series.iter().map(|(_, marks)| simple_returns(marks)).collect();
+    for ((ticker, _), returns) in series.iter().zip(&series_returns) {
+        if returns.len() < buckets * 2 {
+            return Err(MarkovError::Invalid(format!("{} has {} daily returns, {} are needed for {} buckets", ticker, returns.len(), buckets * 2, buckets)));
+        }
+    }
+    let mut transitions = Array3::<f64>::zeros((series.len(), buckets, buckets));
+    let mut bucket_returns = Array2::<f64>::zeros((series.len(), buckets));
+    let mut initial_states = Vec::new();
+    for (index, returns) in series_returns.iter().enumerate() {
+        let edges = bucket_edges(returns, buckets);
+        let states: Vec<usize> = returns.iter().map(|value| edges.partition_point(|edge| edge <= value)).collect();
+        let mut totals = vec![0.0; buckets];
+        let mut counts = vec![0.0; buckets];
+        for (value, state) in returns.iter().zip(&states) {
+            totals[*state] += value;
+            counts[*state] += 1.0;
+        }
+        for bucket in 0..buckets {
+            if counts[bucket] > 0.0 {
+                bucket_returns[[index, bucket]] = totals[bucket] / counts[bucket];
+            }
+        }
+        for pair in states.windows(2) {
+            transitions[[index, pair[0], pair[1]]] += 1.0;
+        }
+        for from in 0..buckets {
+            let row_total = (0..buckets).fold(0.0, |total, to| total + transitions[[index, from, to]]);
+            for to in 0..buckets {
+                transitions[[index, from, to]] = match row_total > 0.0 {
+                    true => transitions[[index, from, to]] / row_total,
+                    // Never left this bucket, so fall back on how often each bucket was seen
+                    false => counts[to] / states.len() as f64,
+                };
+            }
+        }
+        initial_states.push(*states.last().unwrap_or(&0));
+    }
+    Ok(MarkovFit {
+        tickers: series.iter().map(|(ticker, _)| ticker.clone()).collect(),
+        buckets,
+        start,
+        end,
+        transitions,
+        bucket_returns,
+        initial_states,
+    })
+}
+
+impl MarkovFit {
+    pub fn from_model(model: MarkovFitModel) -> Result<Self, MarkovError> {
+        let tickers = model.tickers.len();
+        let invalid = || MarkovError::Invalid(format!("Markov model {} does not match its shape", model.id));
+        if model.buckets < 1 || model.initial_states.len() != tickers {
+            return Err(invalid());
+        }
+        let buckets = model.buckets as usize;
+        Ok(Self {
+            transitions: Array3::from_shape_vec((tickers, buckets, buckets), model.transitions).map_err(|_| invalid())?,
+            bucket_returns: Array2::from_shape_vec((tickers, buckets), model.bucket_returns).map_err(|_| invalid())?,
+            initial_states: model.initial_states.iter().map(|state| (*state).clamp(0, model.buckets - 1) as usize).collect(),
+            tickers: model.tickers,
+            buckets,
+            start: model.start_date,
+            end: model.end_date,
+        })
+    }
+    pub fn to_model(&self) -> MarkovFitModel {
+        MarkovFitModel {
+            id: -1,
+            tickers: self.tickers.clone(),
+            buckets: self.buckets as i32,
+            start_date: self.start,
+            end_date: self.end,
+            transitions: self.transitions.iter().copied().collect(),
+            bucket_returns: self.bucket_returns.iter().copied().collect(),
+            initial_states: self.initial_states.iter().map(|state| *state as i32).collect(),
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytics::lots::tests::assert_close;
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    /// Closes that alternate between rising 10% and falling 10%
+    fn alternating(days: usize) -> Vec<Mark> {
+        let mut price = 100.0;
+        (0..days)
+            .map(|day| {
+                if day > 0 {
+                    price *= match day % 2 {
+                        1 => 1.1,
+                        _ => 0.9,
+                    };
+                }
+                Mark {
+                    date: date("2024-01-01") + chrono::Days::new(day as u64),
+                    price,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn alternating_returns_always_switch_bucket() {
+        let series = vec![("A".to_string(), alternating(11))];
+        let fit = fit(&series, 2, date("2024-01-01"), date("2024-01-11")).unwrap();
+        assert_close(fit.bucket_returns[[0, 0]], -0.1);
+        assert_close(fit.bucket_returns[[0, 1]], 0.1);
+        assert_close(fit.transitions[[0, 0, 1]], 1.0);
+        assert_close(fit.transitions[[0, 1, 0]], 1.0);
+        // The tenth return was a fall
+        assert_eq!(fit.initial_states, vec![0]);
+    }
+
+    #[test]
+    fn buckets_are_bounded() {
+        let series = vec![("A".to_string(), alternating(11))];
+        assert!(fit(&series, 1, date("2024-01-01"), date("2024-01-11")).is_err());
+        assert!(fit(&series, MAX_BUCKETS + 1, date("2024-01-01"), date("2024-01-11")).is_err());
+        assert!(fit(&series, usize::MAX, date("2024-01-01"), date("2024-01-11")).is_err());
+    }
+
+    #[test]
+    fn every_ticker_needs_two_returns_per_bucket() {
+        let series = vec![("A".to_string(), alternating(101)), ("B".to_string(), alternating(11))];
+        assert!(fit(&series, 5, date("2024-01-01"), date("2024-04-10")).is_ok());
+        assert!(fit(&series, 6, date("2024-01-01"), date("2024-04-10")).is_err());
+    }
+}
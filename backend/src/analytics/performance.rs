@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+use bigdecimal::ToPrimitive;
+use chrono::{Datelike, Days, Duration, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use crate::analytics::fx::FxTable;
+use crate::analytics::income::Entitlement;
+use crate::analytics::lots::{sorted_trades, CostBasisMethod, LotEngine};
+use crate::analytics::pnl::Mark;
+use crate::models::fx::Currency;
+use crate::models::trades::{TradeModel, TradeType};
+
+const DAYS_PER_YEAR: f64 = 365.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    #[serde(rename = "1M")]
+    OneMonth,
+    #[serde(rename = "3M")]
+    ThreeMonths,
+    #[serde(rename = "YTD")]
+    YearToDate,
+    #[serde(rename = "1Y")]
+    OneYear,
+    #[serde(rename = "inception")]
+    Inception,
+}
+
+impl Period {
+    pub const ALL: [Period; 5] = [Period::OneMonth, Period::ThreeMonths, Period::YearToDate, Period::OneYear, Period::Inception];
+
+    /// First day of the period ending on `end`, never earlier than `inception`
+    pub fn start(&self, end: NaiveDate, inception: NaiveDate) -> NaiveDate {
+        let start = match self {
+            Period::OneMonth => end.checked_sub_months(Months::new(1)).map(|date| date + Duration::days(1)),
+            Period::ThreeMonths => end.checked_sub_months(Months::new(3)).map(|date| date + Duration::days(1)),
+            Period::YearToDate => NaiveDate::from_ymd_opt(end.year(), 1, 1),
+            Period::OneYear => end.checked_sub_months(Months::new(12)).map(|date| date + Duration::days(1)),
+            Period::Inception => Some(inception),
+        };
+        start.unwrap_or(inception).max(inception)
+    }
+}
+
+/// Portfolio at the close of one day, in the base currency. Cash flow is money put in by buys
+/// less money taken out by sells, income is dividends paid out that day.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ValuePoint {
+    pub date: NaiveDate,
+    pub value: f64,
+    pub cost_basis: f64,
+    pub cash_flow: f64,
+    pub income: f64,
+    /// Per ticker values, only filled in when a breakdown is asked for
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub holdings: Vec<TickerValue>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TickerValue {
+    pub ticker: String,
+    pub quantity: f64,
+    pub value: f64,
+    pub cost_basis: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Interval {
+    #[default]
+    Daily,
+    /// Weeks ending on Sunday
+    Weekly,
+    Monthly,
+}
+
+/// Keeps the last point of each week or month, with the cash flows and income of every day in it
+pub fn downsample(points: Vec<ValuePoint>, interval: Interval) -> Vec<ValuePoint> {
+    let bucket = |date: NaiveDate| -> (i32, u32) {
+        match interval {
+            Interval::Daily => (date.year(), date.ordinal()),
+            Interval::Weekly => (date.iso_week().year(), date.iso_week().week()),
+            Interval::Monthly => (date.year(), date.month()),
+        }
+    };
+    let mut sampled: Vec<ValuePoint> = Vec::new();
+    for point in points {
+        match sampled.last_mut() {
+            Some(last) if bucket(last.date) == bucket(point.date) => {
+                let cash_flow = last.cash_flow + point.cash_flow;
+                let income = last.income + point.income;
+                *last = ValuePoint {
+                    cash_flow,
+                    income,
+                    ..point
+                };
+            },
+            _ => sampled.push(point),
+        }
+    }
+    sampled
+}
+
+/// Everything needed to value the portfolio on any day
+pub struct PortfolioHistory {
+    pub trades: Vec<TradeModel>,
+    /// Closes for each ticker, oldest first
+    pub prices: HashMap<String, Vec<Mark>>,
+    pub dividends: Vec<Entitlement>,
+    pub fx: FxTable,
+    pub base: Currency,
+}
+
+impl PortfolioHistory {
+    /// Date of the first trade
+    pub fn inception(&self) -> Option<NaiveDate> {
+        self.trades.iter().map(|trade| trade.date).min()
+    }
+
+    /// Replays the trades day by day from inception, marking every open position at its last
+    /// close on or before the day. Positions without a close yet are carried at cost. Returns
+    /// one point per calendar day from `start` to `end` and whether any conversion was made at par.
+    /// `start` is clamped to the day before the first trade, since every day before it is empty.
+    pub fn daily_values(&self, start: NaiveDate, end: NaiveDate, breakdown: bool) -> (Vec<ValuePoint>, bool) {
+        let mut missing_fx = false;
+        let mut convert = |currency: Currency, date: NaiveDate| -> f64 {
+            self.fx.rate(currency, self.base, date).unwrap_or_else(|| {
+                missing_fx = true;
+                1.0
+            })
+        };
+        let trades = sorted_trades(&self.trades);
+        let opening = self.inception().and_then(|inception| inception.checked_sub_days(Days::new(1))).unwrap_or(end);
+        let start = start.max(opening);
+        let first = self.inception().unwrap_or(start).min(start);
+        let mut engine = LotEngine::new(CostBasisMethod::Fifo);
+        let mut next_trade = 0;
+        let mut points = Vec::new();
+        let mut date = first;
+        while date <= end {
+            let mut point = ValuePoint {
+                date,
+                ..Default::default()
+            };
+            while next_trade < trades.len() && trades[next_trade].date == date {
+                let trade = trades[next_trade];
+                let amount = trade.amount as f64 * trade.price.to_f64().unwrap_or(0.0) * convert(trade.currency, date);
+                point.cash_flow += match trade.trade_type {
+                    TradeType::Buy => amount,
+                    TradeType::Sell => -amount,
+                };
+                engine.apply(trade);
+                next_trade += 1;
+            }
+            if date >= start {
+                for ticker in engine.tickers() {
+                    let mark = self.prices.get(&ticker).and_then(|marks| last_mark(marks, date));
+                    let mut holding = TickerValue {
+                        quantity: engine.quantity(&ticker),
+                        ticker,
+                        ..Default::default()
+                    };
+                    for lot in engine.open_lots(&holding.ticker) {
+                        let price = mark.map(|mark| mark.price).unwrap_or(lot.unit_cost);
+                        holding.value += lot.quantity * price * convert(lot.currency, date);
+                        holding.cost_basis += lot.cost_basis() * convert(lot.currency, lot.acquired);
+                    }
+                    point.value += holding.value;
+                    point.cost_basis += holding.cost_basis;
+                    if breakdown && holding.quantity > 0.0 {
+                        point.holdings.push(holding);
+                    }
+                }
+                for entitlement in self.dividends.iter().filter(|entitlement| entitlement.pay_date == date) {
+                    point.income += entitlement.net * convert(entitlement.currency, date);
+                }
+                points.push(point);
+            }
+            date += Duration::days(1);
+        }
+        (points, missing_fx)
+    }
+
+    pub fn performance(&self, start: NaiveDate, end: NaiveDate) -> PerformanceReport {
+        // The close the day before the period starts is the opening value
+        let (points, missing_fx) = self.daily_values(start.checked_sub_days(Days::new(1)).unwrap_or(start), end, false);
+        let start_value = points.first().map(|point| point.value).unwrap_or(0.0);
+        let end_value = points.last().map(|point| point.value).unwrap_or(0.0);
+        let flows = points.iter().skip(1);
+        let net_cash_flow = flows.clone().fold(0.0, |total, point| total + point.cash_flow);
+        let income = flows.fold(0.0, |total, point| total + point.income);
+        let days = (end - start).num_days() + 1;
+        let twr = time_weighted_return(&points);
+        let mwr_annualised = money_weighted_return(&points);
+        let mwr = mwr_annualised.map(|rate| (1.0 + rate).powf(days as f64 / DAYS_PER_YEAR) - 1.0);
+        PerformanceReport {
+            period: None,
+            start,
+            end,
+            base: self.base,
+            missing_fx,
+            start_value,
+            end_value,
+            net_cash_flow,
+            income,
+            twr,
+            twr_annualised: twr.and_then(|twr| annualise(twr, days)),
+            mwr,
+            mwr_annualised: mwr_annualised.filter(|_| days as f64 >= DAYS_PER_YEAR),
+        }
+    }
+}
+
+/// Last close on or before `date` from closes sorted oldest first
+pub fn last_mark(marks: &[Mark], date: NaiveDate) -> Option<Mark> {
+    match marks.partition_point(|mark| mark.date <= date) {
+        0 => None,
+        index => Some(marks[index - 1]),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PerformanceReport {
+    pub period: Option<Period>,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub base: Currency,
+    pub missing_fx: bool,
+    /// Value at the close of the day before `start`
+    pub start_value: f64,
+    pub end_value: f64,
+    pub net_cash_flow: f64,
+    pub income: f64,
+    /// Returns over the whole period as fractions, e.g. 0.05 for 5%
+    pub twr: Option<f64>,
+    pub mwr: Option<f64>,
+    /// Only given for periods of at least a year
+    pub twr_annualised: Option<f64>,
+    pub mwr_annualised: Option<f64>,
+}
+
+fn annualise(period_return: f64, days: i64) -> Option<f64> {
+    match days as f64 >= DAYS_PER_YEAR && period_return > -1.0 {
+        true => Some((1.0 + period_return).powf(DAYS_PER_YEAR / days as f64) - 1.0),
+        false => None,
+    }
+}
+
+/// Chain links daily returns, treating each day's cash flow as arriving at the start of the day
+/// and dividends as leaving at the close. The first point is the opening value. Days with nothing
+/// invested are skipped, and `None` means nothing was invested at all.
+pub fn time_weighted_return(points: &[ValuePoint]) -> Option<f64> {
+    let mut growth = 1.0;
+    let mut linked = false;
+    for window in points.windows(2) {
+        let invested = window[0].value + window[1].cash_flow;
+        if invested <= f64::EPSILON {
+            continue;
+        }
+        growth *= (window[1].value + window[1].income) / invested;
+        linked = true;
+    }
+    match linked {
+        true => Some(growth - 1.0),
+        false => None,
+    }
+}
+
+/// Time weighted growth of one unit invested at the first point, as closes so it can be compared
+/// with a ticker. Days with nothing invested leave the index unchanged.
+pub fn growth_index(points: &[ValuePoint]) -> Vec<Mark> {
+    let mut growth = 1.0;
+    let mut index = Vec::new();
+    for (position, point) in points.iter().enumerate() {
+        if position > 0 {
+            let invested = points[position - 1].value + point.cash_flow;
+            if invested > f64::EPSILON {
+                growth *= (point.value + point.income) / invested;
+            }
+        }
+        index.push(Mark {
+            date: point.date,
+            price: growth,
+        });
+    }
+    index
+}
+
+/// Annual internal rate of return on the investor's cash flows: the opening value and buys
+/// going in, sells and dividends coming out, and the closing value coming out at the end
+pub fn money_weighted_return(points: &[ValuePoint]) -> Option<f64> {
+    let (first, last) = (points.first()?, points.last()?);
+    let mut flows = vec![(first.date, -first.value)];
+    for point in points.iter().skip(1) {
+        flows.push((point.date, point.income - point.cash_flow));
+    }
+    flows.push((last.date, last.value));
+    xirr(&flows)
+}
+
+fn net_present_value(flows: &[(NaiveDate, f64)], rate: f64) -> f64 {
+    let first = flows[0].0;
+    flows.iter().fold(0.0, |total, (date, amount)| {
+        total + amount / (1.0 + rate).powf((*date - first).num_days() as f64 / DAYS_PER_YEAR)
+    })
+}
+
+/// Annual rate that discounts the dated `flows` to zero. Newton's method first, falling back to
+/// bisection when it doesn't converge. `None` when the flows don't change sign.
+pub fn xirr(flows: &[(NaiveDate, f64)]) -> Option<f64> {
+    let flows: Vec<(NaiveDate, f64)> = flows.iter().copied().filter(|(_, amount)| amount.abs() > f64::EPSILON).collect();
+    if !flows.iter().any(|(_, amount)| *amount > 0.0) || !flows.iter().any(|(_, amount)| *amount < 0.0) {
+        return None;
+    }
+    let mut rate = 0.1;
+    for _ in 0..100 {
+        let value = net_present_value(&flows, rate);
+        let step = 1e-6;
+        let derivative = (net_present_value(&flows, rate + step) - value) / step;
+        if derivative.abs() < f64::EPSILON {
+            break;
+        }
+        let next = rate - value / derivative;
+        if !next.is_finite() || next <= -1.0 {
+            break;
+        }
+        if (next - rate).abs() < 1e-10 {
+            return Some(next);
+        }
+        rate = next;
+    }
+    let (mut low, mut high) = (-0.9999, 100.0);
+    let mut low_value = net_present_value(&flows, low);
+    if low_value.signum() == net_present_value(&flows, high).signum() {
+        return None;
+    }
+    for _ in 0..200 {
+        let middle = (low + high) / 2.0;
+        let value = net_present_value(&flows, middle);
+        if value.signum() == low_value.signum() {
+            low = middle;
+            low_value = value;
+        } else {
+            high = middle;
+        }
+    }
+    Some((low + high) / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytics::lots::tests::{assert_close, trade};
+    use crate::models::trades::Country;
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    fn point(on: &str, value: f64, cash_flow: f64) -> ValuePoint {
+        ValuePoint {
+            date: date(on),
+            value,
+            cash_flow,
+            ..Default::default()
+        }
+    }
+
+    fn history(trades: Vec<TradeModel>) -> PortfolioHistory {
+        PortfolioHistory {
+            trades,
+            prices: HashMap::new(),
+            dividends: Vec::new(),
+            fx: FxTable::default(),
+            base: Currency::AUD,
+        }
+    }
+
+    #[test]
+    fn xirr_finds_the_annual_rate() {
+        let flows = [(date("2023-01-01"), -1000.0), (date("2024-01-01"), 1100.0)];
+        assert_close(xirr(&flows).unwrap(), 0.1);
+        let flows = [(date("2023-01-01"), -1000.0), (date("2023-07-02"), -1000.0), (date("2024-01-01"), 2000.0)];
+        assert!(xirr(&flows).unwrap().abs() < 1e-9);
+        assert_eq!(xirr(&[(date("2023-01-01"), -1000.0)]), None);
+    }
+
+    #[test]
+    fn twr_ignores_the_size_of_cash_flows() {
+        // Up 10%, then 1000 more goes in and it all falls 10%
+        let points = [point("2023-01-01", 100.0, 0.0), point("2023-01-02", 110.0, 0.0), point("2023-01-03", 999.0, 1000.0)];
+        assert_close(time_weighted_return(&points).unwrap(), 1.1 * 0.9 - 1.0);
+        assert_eq!(time_weighted_return(&[point("2023-01-01", 0.0, 0.0), point("2023-01-02", 0.0, 0.0)]), None);
+    }
+
+    #[test]
+    fn downsampling_keeps_the_last_point_and_every_cash_flow() {
+        let points = vec![
+            point("2023-01-30", 100.0, 100.0),
+            point("2023-01-31", 120.0, 10.0),
+            point("2023-02-01", 130.0, 5.0),
+        ];
+        let monthly = downsample(points.clone(), Interval::Monthly);
+        assert_eq!(monthly.len(), 2);
+        assert_eq!((monthly[0].value, monthly[0].cash_flow), (120.0, 110.0));
+        assert_eq!((monthly[1].value, monthly[1].cash_flow), (130.0, 5.0));
+        // Monday 30 January to Wednesday 1 February is one ISO week
+        let weekly = downsample(points.clone(), Interval::Weekly);
+        assert_eq!(weekly.len(), 1);
+        assert_eq!((weekly[0].value, weekly[0].cash_flow), (130.0, 115.0));
+        assert_eq!(downsample(points, Interval::Daily).len(), 3);
+    }
+
+    #[test]
+    fn daily_values_start_the_day_before_the_first_trade() {
+        let history = history(vec![trade(1, Country::AU, "2023-01-02", TradeType::Buy, 10, 10.0)]);
+        let (points, missing_fx) = history.daily_values(NaiveDate::MIN, date("2023-01-03"), false);
+        assert!(!missing_fx);
+        let values: Vec<(NaiveDate, f64, f64)> = points.iter().map(|point| (point.date, point.value, point.cash_flow)).collect();
+        assert_eq!(values, vec![
+            (date("2023-01-01"), 0.0, 0.0),
+            (date("2023-01-02"), 100.0, 100.0),
+            (date("2023-01-03"), 100.0, 0.0),
+        ]);
+        let report = history.performance(NaiveDate::MIN, date("2023-01-03"));
+        assert_eq!(report.twr, Some(0.0));
+        assert_eq!(report.net_cash_flow, 100.0);
+    }
+}
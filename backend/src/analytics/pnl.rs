@@ -0,0 +1,356 @@
+use std::collections::{BTreeSet, HashMap};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::analytics::fx::FxTable;
+use crate::analytics::income::Entitlement;
+use crate::analytics::lots::{CostBasisMethod, LotEngine};
+use crate::models::fx::Currency;
+
+/// Closing price used to mark a position, with the date of the quote it came from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Mark {
+    pub date: NaiveDate,
+    pub price: f64,
+}
+
+/// A lot, or part of one, that was closed by a sell. Amounts are in the report's base currency.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClosedLot {
+    pub lot_trade_id: i32,
+    pub sell_trade_id: i32,
+    pub acquired: NaiveDate,
+    pub sold: NaiveDate,
+    pub quantity: f64,
+    pub cost_basis: f64,
+    pub proceeds: f64,
+    pub gain: f64,
+    pub gain_percent: Option<f64>,
+    pub price_gain: f64,
+    pub fx_gain: f64,
+}
+
+/// Totals in the report's base currency. Price gain is the move in the local price converted at
+/// the closing rate, FX gain is the move in the rate applied to the local cost.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PnlTotals {
+    pub cost_basis: f64,
+    pub market_value: f64,
+    pub unrealised_gain: f64,
+    pub unrealised_percent: Option<f64>,
+    pub unrealised_price_gain: f64,
+    pub unrealised_fx_gain: f64,
+    pub realised_cost_basis: f64,
+    pub realised_proceeds: f64,
+    pub realised_gain: f64,
+    pub realised_percent: Option<f64>,
+    pub realised_price_gain: f64,
+    pub realised_fx_gain: f64,
+    pub total_gain: f64,
+    pub total_percent: Option<f64>,
+    /// Net dividends paid on or before the report date
+    pub dividend_income: f64,
+    /// Total gain plus dividend income
+    pub total_return: f64,
+    pub total_return_percent: Option<f64>,
+}
+
+impl PnlTotals {
+    fn add(&mut self, other: &PnlTotals) {
+        self.cost_basis += other.cost_basis;
+        self.market_value += other.market_value;
+        self.unrealised_price_gain += other.unrealised_price_gain;
+        self.unrealised_fx_gain += other.unrealised_fx_gain;
+        self.realised_cost_basis += other.realised_cost_basis;
+        self.realised_proceeds += other.realised_proceeds;
+        self.realised_price_gain += other.realised_price_gain;
+        self.realised_fx_gain += other.realised_fx_gain;
+        self.dividend_income += other.dividend_income;
+        self.recalculate();
+    }
+    fn recalculate(&mut self) {
+        self.unrealised_gain = self.market_value - self.cost_basis;
+        self.realised_gain = self.realised_proceeds - self.realised_cost_basis;
+        self.total_gain = self.unrealised_gain + self.realised_gain;
+        self.unrealised_percent = percent(self.unrealised_gain, self.cost_basis);
+        self.realised_percent = percent(self.realised_gain, self.realised_cost_basis);
+        self.total_percent = percent(self.total_gain, self.cost_basis + self.realised_cost_basis);
+        self.total_return = self.total_gain + self.dividend_income;
+        self.total_return_percent = percent(self.total_return, self.cost_basis + self.realised_cost_basis);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TickerPnl {
+    pub ticker: String,
+    pub currency: Currency,
+    pub quantity: f64,
+    /// Average cost in the ticker's own currency
+    pub average_cost: Option<f64>,
+    pub mark: Option<Mark>,
+    /// Currency the mark is quoted in, which can differ from the currency it was traded in
+    pub mark_currency: Option<Currency>,
+    #[serde(flatten)]
+    pub totals: PnlTotals,
+    pub closed_lots: Vec<ClosedLot>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PortfolioPnl {
+    pub as_of: NaiveDate,
+    pub method: CostBasisMethod,
+    pub base: Currency,
+    /// Set when a conversion had no stored rate and was made at par
+    pub missing_fx: bool,
+    pub holdings: Vec<TickerPnl>,
+    pub totals: PnlTotals,
+}
+
+/// Gain as a percentage of what was paid, `None` when nothing was paid
+pub fn percent(gain: f64, cost: f64) -> Option<f64> {
+    match cost.abs() > f64::EPSILON {
+        true => Some(gain / cost * 100.0),
+        false => None,
+    }
+}
+
+/// Realised P&L comes from the engine's disposals, unrealised P&L marks its open lots at `marks`,
+/// each in the currency it is quoted in, and everything is converted into `base` at the rate on
+/// the day each amount was paid or valued. Positions with no mark are carried at cost so they
+/// only show an FX gain. Dividends count towards the total return once they are paid.
+pub fn calculate(engine: &LotEngine, marks: &HashMap<String, (Mark, Currency)>, dividends: &[Entitlement], fx: &FxTable, base: Currency, as_of: NaiveDate) -> PortfolioPnl {
+    let dividends: Vec<&Entitlement> = dividends.iter().filter(|entitlement| entitlement.pay_date <= as_of).collect();
+    let mut tickers: BTreeSet<String> = engine.tickers().into_iter().collect();
+    tickers.extend(engine.disposals().iter().map(|disposal| disposal.ticker.clone()));
+    tickers.extend(dividends.iter().map(|entitlement| entitlement.ticker.clone()));
+
+    let mut missing_fx = false;
+    let mut convert = |currency: Currency, date: NaiveDate| -> f64 {
+        fx.rate(currency, base, date).unwrap_or_else(|| {
+            missing_fx = true;
+            1.0
+        })
+    };
+    let mut holdings = Vec::new();
+    let mut portfolio = PnlTotals::default();
+    for ticker in tickers {
+        let quoted = marks.get(&ticker).copied();
+        let mark = quoted.map(|(mark, _)| mark);
+        let valued_on = mark.map(|mark| mark.date).unwrap_or(as_of);
+        let mut totals = PnlTotals::default();
+        let mut currency = None;
+        for lot in engine.open_lots(&ticker) {
+            currency = Some(lot.currency);
+            let market_value = match quoted {
+                Some((mark, quoted_in)) => lot.quantity * mark.price * convert(quoted_in, mark.date),
+                None => lot.cost_basis() * convert(lot.currency, as_of),
+            };
+            let opening_rate = convert(lot.currency, lot.acquired);
+            let closing_rate = convert(lot.currency, valued_on);
+            totals.cost_basis += lot.cost_basis() * opening_rate;
+            totals.market_value += market_value;
+            totals.unrealised_price_gain += market_value - lot.cost_basis() * closing_rate;
+            totals.unrealised_fx_gain += lot.cost_basis() * (closing_rate - opening_rate);
+        }
+        let mut closed_lots = Vec::new();
+        for disposal in engine.disposals().iter().filter(|disposal| disposal.ticker == ticker) {
+            currency = Some(disposal.currency);
+            let closing_rate = convert(disposal.currency, disposal.sold);
+            for matched in &disposal.matches {
+                let opening_rate = convert(disposal.currency, matched.acquired);
+                let cost_basis = matched.cost_basis * opening_rate;
+                let proceeds = matched.proceeds * closing_rate;
+                closed_lots.push(ClosedLot {
+                    lot_trade_id: matched.lot_trade_id,
+                    sell_trade_id: disposal.trade_id,
+                    acquired: matched.acquired,
+                    sold: disposal.sold,
+                    quantity: matched.quantity,
+                    cost_basis,
+                    proceeds,
+                    gain: proceeds - cost_basis,
+                    gain_percent: percent(proceeds - cost_basis, cost_basis),
+                    price_gain: matched.gain * closing_rate,
+                    fx_gain: matched.cost_basis * (closing_rate - opening_rate),
+                });
+            }
+        }
+        for lot in &closed_lots {
+            totals.realised_cost_basis += lot.cost_basis;
+            totals.realised_proceeds += lot.proceeds;
+            totals.realised_price_gain += lot.price_gain;
+            totals.realised_fx_gain += lot.fx_gain;
+        }
+        for entitlement in dividends.iter().filter(|entitlement| entitlement.ticker == ticker) {
+            currency = currency.or(Some(entitlement.currency));
+            totals.dividend_income += entitlement.net * convert(entitlement.currency, entitlement.pay_date);
+        }
+        totals.recalculate();
+        portfolio.add(&totals);
+        holdings.push(TickerPnl {
+            currency: currency.unwrap_or(base),
+            quantity: engine.quantity(&ticker),
+            average_cost: engine.average_cost(&ticker),
+            ticker,
+            mark,
+            mark_currency: quoted.map(|(_, currency)| currency),
+            totals,
+            closed_lots,
+        });
+    }
+    PortfolioPnl {
+        as_of,
+        method: engine.method,
+        base,
+        missing_fx,
+        holdings,
+        totals: portfolio,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::FromPrimitive;
+    use sqlx::types::BigDecimal;
+    use crate::analytics::lots::tests::{assert_close, trade};
+    use crate::models::fx::FxRateModel;
+    use crate::models::trades::{Country, TradeModel, TradeType};
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    fn marks(on: &str, price: f64, currency: Currency) -> HashMap<String, (Mark, Currency)> {
+        HashMap::from([("ABC".to_string(), (Mark { date: date(on), price }, currency))])
+    }
+
+    fn usd(mut trade: TradeModel) -> TradeModel {
+        trade.currency = Currency::USD;
+        trade
+    }
+
+    /// USD to AUD at 1.5 from the start of 2023 and 2 from March
+    fn rates() -> FxTable {
+        FxTable::from_rates([("2023-01-01", 1.5), ("2023-03-01", 2.0)].iter()
+            .map(|(on, rate)| FxRateModel {
+                from_currency: Currency::USD,
+                to_currency: Currency::AUD,
+                date: date(on),
+                rate: BigDecimal::from_f64(*rate).unwrap(),
+            })
+            .collect())
+    }
+
+    fn entitlement(pay_date: &str, net: f64) -> Entitlement {
+        Entitlement {
+            dividend_id: 1,
+            ticker: "ABC".to_string(),
+            ex_date: date(pay_date),
+            pay_date: date(pay_date),
+            currency: Currency::USD,
+            shares: 10.0,
+            amount_per_share: net / 10.0,
+            gross: net,
+            franking_percent: 0.0,
+            franked_amount: 0.0,
+            unfranked_amount: net,
+            franking_credits: 0.0,
+            grossed_up: net,
+            withholding_tax: 0.0,
+            net,
+        }
+    }
+
+    #[test]
+    fn realised_and_unrealised_in_one_currency() {
+        let engine = LotEngine::replay(CostBasisMethod::Fifo, &[
+            trade(1, Country::AU, "2023-01-01", TradeType::Buy, 10, 10.0),
+            trade(2, Country::AU, "2023-02-01", TradeType::Buy, 10, 20.0),
+            trade(3, Country::AU, "2023-03-01", TradeType::Sell, 5, 30.0),
+        ]);
+        let report = calculate(&engine, &marks("2023-04-01", 25.0, Currency::AUD), &[], &FxTable::default(), Currency::AUD, date("2023-04-01"));
+        let totals = &report.totals;
+        assert_eq!(totals.realised_cost_basis, 50.0);
+        assert_eq!(totals.realised_proceeds, 150.0);
+        assert_eq!(totals.realised_gain, 100.0);
+        // 5 left at 10 and 10 at 20, marked at 25
+        assert_eq!(totals.cost_basis, 250.0);
+        assert_eq!(totals.market_value, 375.0);
+        assert_eq!(totals.unrealised_gain, 125.0);
+        assert_eq!(totals.unrealised_fx_gain, 0.0);
+        assert_eq!(totals.total_percent, Some(75.0));
+        assert!(!report.missing_fx);
+        assert_eq!(report.holdings[0].closed_lots.len(), 1);
+    }
+
+    #[test]
+    fn unrealised_gain_splits_into_price_and_fx() {
+        let engine = LotEngine::replay(CostBasisMethod::Fifo, &[
+            usd(trade(1, Country::US, "2023-01-01", TradeType::Buy, 10, 10.0)),
+        ]);
+        let report = calculate(&engine, &marks("2023-04-01", 12.0, Currency::USD), &[], &rates(), Currency::AUD, date("2023-04-01"));
+        let totals = &report.totals;
+        // Bought for 100 USD at 1.5, worth 120 USD at 2
+        assert_close(totals.cost_basis, 150.0);
+        assert_close(totals.market_value, 240.0);
+        assert_close(totals.unrealised_price_gain, 40.0);
+        assert_close(totals.unrealised_fx_gain, 50.0);
+        assert_close(totals.unrealised_gain, totals.unrealised_price_gain + totals.unrealised_fx_gain);
+    }
+
+    #[test]
+    fn realised_gain_splits_into_price_and_fx() {
+        let engine = LotEngine::replay(CostBasisMethod::Fifo, &[
+            usd(trade(1, Country::US, "2023-01-01", TradeType::Buy, 10, 10.0)),
+            usd(trade(2, Country::US, "2023-03-01", TradeType::Sell, 10, 12.0)),
+        ]);
+        let report = calculate(&engine, &HashMap::new(), &[], &rates(), Currency::AUD, date("2023-04-01"));
+        let lot = &report.holdings[0].closed_lots[0];
+        assert_close(lot.cost_basis, 150.0);
+        assert_close(lot.proceeds, 240.0);
+        assert_close(lot.price_gain, 40.0);
+        assert_close(lot.fx_gain, 50.0);
+        assert_close(report.totals.realised_gain, 90.0);
+    }
+
+    #[test]
+    fn positions_without_a_mark_or_rate_are_carried_at_cost() {
+        let engine = LotEngine::replay(CostBasisMethod::Fifo, &[
+            usd(trade(1, Country::US, "2023-01-01", TradeType::Buy, 10, 10.0)),
+        ]);
+        let report = calculate(&engine, &HashMap::new(), &[], &FxTable::default(), Currency::AUD, date("2023-04-01"));
+        assert!(report.missing_fx);
+        assert!(report.holdings[0].mark.is_none());
+        assert_eq!(report.totals.market_value, 100.0);
+        assert_eq!(report.totals.unrealised_gain, 0.0);
+    }
+
+    #[test]
+    fn dividends_count_once_paid() {
+        let engine = LotEngine::replay(CostBasisMethod::Fifo, &[
+            usd(trade(1, Country::US, "2023-01-01", TradeType::Buy, 10, 10.0)),
+        ]);
+        let dividends = [entitlement("2023-03-15", 30.0), entitlement("2023-05-01", 30.0)];
+        let report = calculate(&engine, &marks("2023-04-01", 12.0, Currency::USD), &dividends, &rates(), Currency::AUD, date("2023-04-01"));
+        let totals = &report.totals;
+        assert_close(totals.dividend_income, 60.0);
+        assert_close(totals.total_return, totals.total_gain + 60.0);
+        assert_eq!(totals.total_return_percent, percent(totals.total_return, totals.cost_basis));
+    }
+
+    #[test]
+    fn marks_are_converted_from_the_quote_currency() {
+        // Bought in AUD but quoted in USD
+        let engine = LotEngine::replay(CostBasisMethod::Fifo, &[
+            trade(1, Country::AU, "2023-01-01", TradeType::Buy, 10, 15.0),
+        ]);
+        let report = calculate(&engine, &marks("2023-04-01", 12.0, Currency::USD), &[], &rates(), Currency::AUD, date("2023-04-01"));
+        let totals = &report.totals;
+        assert_close(totals.cost_basis, 150.0);
+        assert_close(totals.market_value, 240.0);
+        assert_close(totals.unrealised_price_gain, 90.0);
+        assert_close(totals.unrealised_fx_gain, 0.0);
+        assert_eq!(report.holdings[0].currency, Currency::AUD);
+        assert_eq!(report.holdings[0].mark_currency, Some(Currency::USD));
+    }
+}
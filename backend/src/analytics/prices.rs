@@ -0,0 +1,93 @@
+use bigdecimal::{FromPrimitive, ToPrimitive};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
+use crate::models::corporate_actions::CorporateActionModel;
+use crate::models::dividends::DividendModel;
+use crate::models::quotes::QuoteModel;
+
+/// How a range of quotes is adjusted for corporate events
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceAdjustment {
+    /// Prices as they traded on the day, before any split that has been applied since
+    Raw,
+    /// Prices in today's shares, which is how quotes are stored once splits are applied
+    #[default]
+    SplitAdjusted,
+    /// Split adjusted and with dividends reinvested, scaled so the latest close is unchanged
+    TotalReturn,
+}
+
+/// Shares today per share held on `date` from the applied splits and consolidations in `actions`
+pub fn split_factor(actions: &[CorporateActionModel], ticker: &str, date: NaiveDate) -> f64 {
+    actions.iter()
+        .filter(|action| action.applied_at.is_some() && action.ticker == ticker && action.resulting_ticker() == ticker)
+        .filter(|action| action.effective_date > date)
+        .fold(1.0, |factor, action| factor * action.share_factor())
+}
+
+/// Dividend adjusted closes for `quotes`, which must be sorted oldest first. Each dividend scales
+/// every close before its ex-date by one minus the dividend over the last close before the ex-date.
+pub fn dividend_adjusted_closes(quotes: &[QuoteModel], dividends: &[DividendModel]) -> Vec<f64> {
+    let closes: Vec<f64> = quotes.iter().map(|quote| quote.close.to_f64().unwrap_or(0.0)).collect();
+    let mut factors = vec![1.0; quotes.len()];
+    for dividend in dividends {
+        // Index of the first quote on or after the ex-date, everything before it gets adjusted
+        let ex_index = quotes.partition_point(|quote| quote.date < dividend.ex_date);
+        if ex_index == 0 {
+            continue;
+        }
+        let previous_close = closes[ex_index - 1];
+        let amount = dividend.amount_per_share.to_f64().unwrap_or(0.0);
+        if previous_close <= amount || previous_close <= 0.0 {
+            continue;
+        }
+        let factor = 1.0 - amount / previous_close;
+        for adjustment in factors.iter_mut().take(ex_index) {
+            *adjustment *= factor;
+        }
+    }
+    closes.iter().zip(factors).map(|(close, factor)| close * factor).collect()
+}
+
+fn scale(value: &BigDecimal, factor: f64) -> BigDecimal {
+    BigDecimal::from_f64(value.to_f64().unwrap_or(0.0) * factor).unwrap_or(BigDecimal::from_f64(0.0).unwrap())
+}
+
+/// Converts stored quotes into the requested adjustment. `actions` only matter for raw prices.
+pub fn adjust(quotes: Vec<QuoteModel>, adjustment: PriceAdjustment, actions: &[CorporateActionModel]) -> Vec<QuoteModel> {
+    match adjustment {
+        PriceAdjustment::SplitAdjusted => quotes,
+        PriceAdjustment::Raw => quotes.into_iter()
+            .map(|quote| {
+                let factor = split_factor(actions, &quote.ticker, quote.date);
+                QuoteModel {
+                    open: scale(&quote.open, factor),
+                    high: scale(&quote.high, factor),
+                    low: scale(&quote.low, factor),
+                    close: scale(&quote.close, factor),
+                    adjusted_close: scale(&quote.adjusted_close, factor),
+                    volume: (quote.volume as f64 / factor).round() as i64,
+                    ..quote
+                }
+            })
+            .collect(),
+        PriceAdjustment::TotalReturn => quotes.into_iter()
+            .map(|quote| {
+                let close = quote.close.to_f64().unwrap_or(0.0);
+                let factor = match close > 0.0 {
+                    true => quote.adjusted_close.to_f64().unwrap_or(close) / close,
+                    false => 1.0,
+                };
+                QuoteModel {
+                    open: scale(&quote.open, factor),
+                    high: scale(&quote.high, factor),
+                    low: scale(&quote.low, factor),
+                    close: quote.adjusted_close.clone(),
+                    ..quote
+                }
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+
+/// Weights can be off by this much in total before the targets are rejected
+const WEIGHT_TOLERANCE: f64 = 1e-6;
+
+/// Largest amount of cash a plan can invest, well inside what whole share counts can hold
+pub const MAX_CASH: f64 = 1e12;
+
+/// Most single shares the top up after rounding can add
+const MAX_TOP_UPS: usize = 100_000;
+
+#[derive(Debug)]
+pub enum RebalanceError {
+    Invalid(String),
+}
+
+impl std::fmt::Display for RebalanceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RebalanceError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for RebalanceError {}
+
+/// Targets must be unique by ticker, each between zero and one, and add up to at most one.
+/// Whatever is left over is the target for cash.
+pub fn validate_targets(targets: &[(String, f64)]) -> Result<(), RebalanceError> {
+    let mut seen = HashSet::new();
+    for (ticker, weight) in targets {
+        if !seen.insert(ticker) {
+            return Err(RebalanceError::Invalid(format!("{} has more than one target", ticker)));
+        }
+        if !(0.0..=1.0).contains(weight) {
+            return Err(RebalanceError::Invalid(format!("Target for {} must be between 0 and 1", ticker)));
+        }
+    }
+    let total = targets.iter().fold(0.0, |total, (_, weight)| total + weight);
+    if total > 1.0 + WEIGHT_TOLERANCE {
+        return Err(RebalanceError::Invalid(format!("Targets add up to {}, more than 1", total)));
+    }
+    Ok(())
+}
+
+/// A held or targeted ticker with its price in the base currency, `None` when it has no quotes
+#[derive(Debug, Clone)]
+pub struct Position {
+    pub ticker: String,
+    pub quantity: i64,
+    pub price: Option<f64>,
+    pub target: f64,
+}
+
+impl Position {
+    fn value(&self) -> f64 {
+        self.quantity as f64 * self.price.unwrap_or(0.0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Drift {
+    pub ticker: String,
+    pub quantity: i64,
+    pub price: Option<f64>,
+    pub value: f64,
+    pub weight: f64,
+    pub target: f64,
+    /// Current weight less the target, positive when overweight
+    pub drift: f64,
+    /// Value to buy, or sell when negative, to get back to the target
+    pub drift_value: f64,
+}
+
+/// Current weights against targets, as shares of the holdings plus cash. Tickers without a price
+/// are left out of the total and listed in `unpriced`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DriftReport {
+    pub total: f64,
+    pub cash: f64,
+    pub cash_weight: f64,
+    pub cash_target: f64,
+    /// Largest absolute drift of any ticker
+    pub max_drift: f64,
+    pub positions: Vec<Drift>,
+    pub unpriced: Vec<String>,
+}
+
+/// Cash to invest or held alongside the holdings must be between zero and `MAX_CASH`
+pub fn validate_cash(cash: f64) -> Result<f64, RebalanceError> {
+    match (0.0..=MAX_CASH).contains(&cash) {
+        true => Ok(cash),
+        false => Err(RebalanceError::Invalid(format!("Cash must be between 0 and {}", MAX_CASH))),
+    }
+}
+
+pub fn drift(positions: &[Position], cash: f64) -> DriftReport {
+    let total = positions.iter().fold(cash, |total, position| total + position.value());
+    let priced = positions.iter().filter(|position| position.price.is_some());
+    let drifts: Vec<Drift> = priced
+        .map(|position| {
+            let value = position.value();
+            let weight = match total > 0.0 {
+                true => value / total,
+                false => 0.0,
+            };
+            Drift {
+                ticker: position.ticker.clone(),
+                quantity: position.quantity,
+                price: position.price,
+                value,
+                weight,
+                target: position.target,
+                drift: weight - position.target,
+                drift_value: position.target * total - value,
+            }
+        })
+        .collect();
+    DriftReport {
+        total,
+        cash,
+        cash_weight: match total > 0.0 {
+            true => cash / total,
+            false => 0.0,
+        },
+        cash_target: 1.0 - positions.iter().fold(0.0, |total, position| total + position.target),
+        max_drift: drifts.iter().fold(0.0, |max: f64, drift| max.max(drift.drift.abs())),
+        positions: drifts,
+        unpriced: unpriced(positions),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Order {
+    pub ticker: String,
+    pub side: Side,
+    pub quantity: i64,
+    pub price: f64,
+    pub amount: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RebalancePlan {
+    pub cash: f64,
+    pub buy_only: bool,
+    pub orders: Vec<Order>,
+    /// Cash left after every order, never negative
+    pub cash_remaining: f64,
+    /// Drift once the orders have been filled at the given prices
+    pub after: DriftReport,
+    pub unpriced: Vec<String>,
+}
+
+fn unpriced(positions: &[Position]) -> Vec<String> {
+    positions.iter()
+        .filter(|position| position.price.is_none())
+        .map(|position| position.ticker.clone())
+        .collect()
+}
+
+/// Whole share orders that bring `positions` as close to their targets as `cash` allows. Without
+/// `buy_only` overweight holdings are sold down to their target; with it nothing is sold and the
+/// cash is split across underweight tickers in proportion to how far below target they are.
+pub fn rebalance(positions: &[Position], cash: f64, buy_only: bool) -> RebalancePlan {
+    let holdings = positions.iter().fold(0.0, |total, position| total + position.value());
+    let total = holdings + cash;
+    let shortfalls: Vec<f64> = positions.iter()
+        .map(|position| position.target * total - position.value())
+        .collect();
+    let buy_needed = shortfalls.iter().fold(0.0, |needed, shortfall| needed + shortfall.max(0.0));
+    let mut quantities: Vec<i64> = positions.iter().map(|position| position.quantity).collect();
+    for (index, position) in positions.iter().enumerate() {
+        let price = match position.price {
+            Some(price) if price > 0.0 => price,
+            _ => continue,
+        };
+        quantities[index] = match buy_only {
+            // Scale every buy down when the cash does not cover all of them
+            true => match buy_needed > cash && buy_needed > 0.0 {
+                true => position.quantity.saturating_add((shortfalls[index].max(0.0) * cash / buy_needed / price).floor() as i64),
+                false => position.quantity.saturating_add((shortfalls[index].max(0.0) / price).floor() as i64),
+            },
+            false => (position.target * total / price).floor() as i64,
+        };
+    }
+    let spent = |quantities: &[i64]| positions.iter().zip(quantities)
+        .fold(0.0, |spent, (position, quantity)| spent + (quantity - position.quantity) as f64 * position.price.unwrap_or(0.0));
+    // Rounding down leaves cash over, top up whichever ticker is furthest below target with it while
+    // another share gets closer to the target than staying put
+    for _ in 0..MAX_TOP_UPS {
+        let remaining = cash - spent(&quantities);
+        let next = positions.iter().enumerate()
+            .filter_map(|(index, position)| {
+                let price = position.price.filter(|price| *price > 0.0 && *price <= remaining + 1e-9)?;
+                let shortfall = position.target * total - quantities[index] as f64 * price;
+                match shortfall >= price / 2.0 {
+                    true => Some((index, shortfall)),
+                    false => None,
+                }
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match next.and_then(|(index, _)| Some((index, quantities[index].checked_add(1)?))) {
+            Some((index, quantity)) => quantities[index] = quantity,
+            None => break,
+        }
+    }
+    let orders = positions.iter().zip(&quantities)
+        .filter_map(|(position, quantity)| {
+            let change = quantity - position.quantity;
+            let price = position.price?;
+            let side = match change {
+                0 => return None,
+                change if change > 0 => Side::Buy,
+                _ => Side::Sell,
+            };
+            Some(Order {
+                ticker: position.ticker.clone(),
+                side,
+                quantity: change.abs(),
+                price,
+                amount: change.abs() as f64 * price,
+            })
+        })
+        .collect();
+    let after: Vec<Position> = positions.iter().zip(&quantities)
+        .map(|(position, quantity)| Position {
+            quantity: *quantity,
+            ..position.clone()
+        })
+        .collect();
+    let cash_remaining = (cash - spent(&quantities)).max(0.0);
+    RebalancePlan {
+        cash,
+        buy_only,
+        orders,
+        cash_remaining,
+        after: drift(&after, cash_remaining),
+        unpriced: unpriced(positions),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytics::lots::tests::assert_close;
+
+    fn position(ticker: &str, quantity: i64, price: f64, target: f64) -> Position {
+        Position {
+            ticker: ticker.to_string(),
+            quantity,
+            price: Some(price),
+            target,
+        }
+    }
+
+    fn orders(plan: &RebalancePlan) -> Vec<(String, Side, i64)> {
+        plan.orders.iter().map(|order| (order.ticker.clone(), order.side, order.quantity)).collect()
+    }
+
+    #[test]
+    fn drift_counts_cash_in_the_total() {
+        let positions = [position("A", 50, 10.0, 0.5), position("B", 30, 10.0, 0.3)];
+        let report = drift(&positions, 200.0);
+        assert_eq!(report.total, 1000.0);
+        assert_close(report.cash_weight, 0.2);
+        assert_close(report.cash_target, 0.2);
+        assert_close(report.max_drift, 0.0);
+        assert_close(report.positions[0].drift_value, 0.0);
+    }
+
+    #[test]
+    fn unpriced_positions_are_left_out() {
+        let mut positions = vec![position("A", 10, 10.0, 0.5), position("B", 10, 10.0, 0.5)];
+        positions[1].price = None;
+        let report = drift(&positions, 0.0);
+        assert_eq!(report.total, 100.0);
+        assert_eq!(report.positions.len(), 1);
+        assert_eq!(report.unpriced, vec!["B".to_string()]);
+    }
+
+    #[test]
+    fn investing_cash_lands_on_the_targets() {
+        let positions = [position("A", 0, 10.0, 0.5), position("B", 0, 10.0, 0.3)];
+        let plan = rebalance(&positions, 1000.0, true);
+        assert_eq!(orders(&plan), vec![("A".to_string(), Side::Buy, 50), ("B".to_string(), Side::Buy, 30)]);
+        assert_close(plan.cash_remaining, 200.0);
+        assert_close(plan.after.max_drift, 0.0);
+        assert_close(plan.after.positions[0].drift_value, 0.0);
+    }
+
+    #[test]
+    fn buy_only_splits_short_cash_across_the_shortfalls() {
+        // Of 1200 with the cash, A is overweight and B and C are 380 and 180 short, so the 200
+        // goes 13 to 6, with the share left over topping up B
+        let positions = [position("A", 60, 10.0, 0.2), position("B", 10, 10.0, 0.4), position("C", 30, 10.0, 0.4)];
+        let plan = rebalance(&positions, 200.0, true);
+        assert!(plan.orders.iter().all(|order| order.side == Side::Buy));
+        assert_eq!(orders(&plan), vec![("B".to_string(), Side::Buy, 14), ("C".to_string(), Side::Buy, 6)]);
+        assert_close(plan.cash_remaining, 0.0);
+    }
+
+    #[test]
+    fn selling_brings_overweight_holdings_back_to_target() {
+        let positions = [position("A", 80, 10.0, 0.5), position("B", 20, 10.0, 0.5)];
+        let plan = rebalance(&positions, 0.0, false);
+        assert_eq!(orders(&plan), vec![("A".to_string(), Side::Sell, 30), ("B".to_string(), Side::Buy, 30)]);
+        assert_close(plan.cash_remaining, 0.0);
+        assert_close(plan.after.max_drift, 0.0);
+    }
+
+    #[test]
+    fn cash_must_be_bounded() {
+        assert!(validate_cash(-1.0).is_err());
+        assert!(validate_cash(f64::NAN).is_err());
+        assert!(validate_cash(1e30).is_err());
+        assert_eq!(validate_cash(MAX_CASH).unwrap(), MAX_CASH);
+    }
+}
@@ -0,0 +1,44 @@
+use crate::analytics::pnl::Mark;
+
+/// Used to annualise daily statistics
+pub const TRADING_DAYS_PER_YEAR: f64 = 252.0;
+
+pub fn mean(values: &[f64]) -> Option<f64> {
+    match values.len() {
+        0 => None,
+        len => Some(values.iter().fold(0.0, |total, value| total + value) / len as f64),
+    }
+}
+
+/// Sample covariance, `None` with fewer than two pairs
+pub fn covariance(a: &[f64], b: &[f64]) -> Option<f64> {
+    let len = a.len().min(b.len());
+    if len < 2 {
+        return None;
+    }
+    let (mean_a, mean_b) = (mean(&a[..len])?, mean(&b[..len])?);
+    let total = a.iter().zip(b).fold(0.0, |total, (a, b)| total + (a - mean_a) * (b - mean_b));
+    Some(total / (len - 1) as f64)
+}
+
+/// Sample variance, `None` with fewer than two values
+pub fn variance(values: &[f64]) -> Option<f64> {
+    covariance(values, values)
+}
+
+pub fn standard_deviation(values: &[f64]) -> Option<f64> {
+    variance(values).map(f64::sqrt)
+}
+
+/// Growth of one unit compounded over `returns`, less one
+pub fn compound(returns: &[f64]) -> f64 {
+    returns.iter().fold(1.0, |growth, value| growth * (1.0 + value)) - 1.0
+}
+
+/// Simple returns between consecutive closes sorted oldest first, skipping non-positive closes
+pub fn simple_returns(marks: &[Mark]) -> Vec<f64> {
+    marks.windows(2)
+        .filter(|window| window[0].price > 0.0)
+        .map(|window| window[1].price / window[0].price - 1.0)
+        .collect()
+}
\ No newline at end of file
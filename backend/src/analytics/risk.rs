@@ -0,0 +1,208 @@
+use std::collections::{BTreeSet, HashMap};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::analytics::pnl::Mark;
+use crate::analytics::returns::{compound, mean, simple_returns, standard_deviation, TRADING_DAYS_PER_YEAR};
+
+/// Largest fall from a peak, as a negative fraction, with when it started, bottomed and recovered
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Drawdown {
+    pub depth: f64,
+    pub peak: NaiveDate,
+    pub trough: NaiveDate,
+    /// First close back at the peak, `None` while still under water
+    pub recovery: Option<NaiveDate>,
+}
+
+/// Risk statistics from daily closes. Volatility and the ratios are annualised, value at risk is
+/// the one day loss as a positive fraction that is only exceeded with `1 - confidence` probability.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RiskMetrics {
+    pub observations: usize,
+    pub annualised_return: Option<f64>,
+    pub volatility: Option<f64>,
+    pub risk_free_rate: f64,
+    pub sharpe_ratio: Option<f64>,
+    pub sortino_ratio: Option<f64>,
+    pub max_drawdown: Option<Drawdown>,
+    pub confidence: f64,
+    pub historical_var: Option<f64>,
+    pub parametric_var: Option<f64>,
+}
+
+pub fn max_drawdown(marks: &[Mark]) -> Option<Drawdown> {
+    let mut peak = *marks.first()?;
+    let mut worst: Option<Drawdown> = None;
+    let mut worst_peak_price = 0.0;
+    for mark in marks {
+        if mark.price >= peak.price {
+            peak = *mark;
+            continue;
+        }
+        if peak.price <= 0.0 {
+            continue;
+        }
+        let depth = mark.price / peak.price - 1.0;
+        if worst.as_ref().map(|worst| depth < worst.depth).unwrap_or(true) {
+            worst = Some(Drawdown {
+                depth,
+                peak: peak.date,
+                trough: mark.date,
+                recovery: None,
+            });
+            worst_peak_price = peak.price;
+        }
+    }
+    let mut drawdown = worst?;
+    drawdown.recovery = marks.iter()
+        .find(|mark| mark.date > drawdown.trough && mark.price >= worst_peak_price)
+        .map(|mark| mark.date);
+    Some(drawdown)
+}
+
+/// Keeps the marks on days at least one of `prices` has a close, so weekends and market holidays
+/// aren't counted as days when nothing moved
+pub fn on_quote_dates(marks: Vec<Mark>, prices: &HashMap<String, Vec<Mark>>) -> Vec<Mark> {
+    let dates: BTreeSet<NaiveDate> = prices.values().flatten().map(|mark| mark.date).collect();
+    marks.into_iter().filter(|mark| dates.contains(&mark.date)).collect()
+}
+
+/// Value at `quantile` of `values`, interpolating between the closest ranks
+pub fn percentile(values: &[f64], quantile: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let rank = quantile.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64))
+}
+
+/// Inverse of the standard normal distribution, using Acklam's rational approximation
+pub fn inverse_normal(probability: f64) -> f64 {
+    const A: [f64; 6] = [-3.969683028665376e1, 2.209460984245205e2, -2.759285104469687e2, 1.383577518672690e2, -3.066479806614716e1, 2.506628277459239];
+    const B: [f64; 5] = [-5.447609879822406e1, 1.615858368580409e2, -1.556989798598866e2, 6.680131188771972e1, -1.328068155288572e1];
+    const C: [f64; 6] = [-7.784894002430293e-3, -3.223964580411365e-1, -2.400758277161838, -2.549732539343734, 4.374664141464968, 2.938163982698783];
+    const D: [f64; 4] = [7.784695709041462e-3, 3.224671290700398e-1, 2.445134137142996, 3.754408661907416];
+    let low = 0.02425;
+    let p = probability.clamp(f64::MIN_POSITIVE, 1.0 - f64::EPSILON);
+    if p < low {
+        let q = (-2.0 * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5]) / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    } else if p <= 1.0 - low {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    } else {
+        -inverse_normal(1.0 - p)
+    }
+}
+
+/// Works out every metric from closes sorted oldest first. `risk_free_rate` is an annual rate.
+pub fn calculate(marks: &[Mark], risk_free_rate: f64, confidence: f64) -> RiskMetrics {
+    let returns = simple_returns(marks);
+    let daily_risk_free = (1.0 + risk_free_rate).powf(1.0 / TRADING_DAYS_PER_YEAR) - 1.0;
+    let excess: Vec<f64> = returns.iter().map(|value| value - daily_risk_free).collect();
+    let deviation = standard_deviation(&returns);
+    let volatility = deviation.map(|deviation| deviation * TRADING_DAYS_PER_YEAR.sqrt());
+    let annualised_return = match returns.is_empty() {
+        true => None,
+        false => Some((1.0 + compound(&returns)).powf(TRADING_DAYS_PER_YEAR / returns.len() as f64) - 1.0),
+    };
+    let mean_excess = mean(&excess);
+    let sharpe_ratio = match (mean_excess, volatility) {
+        (Some(mean_excess), Some(volatility)) if volatility > f64::EPSILON => Some(mean_excess * TRADING_DAYS_PER_YEAR / volatility),
+        _ => None,
+    };
+    // Downside deviation only counts returns below the risk free rate
+    let downside = mean(&excess.iter().map(|value| value.min(0.0).powi(2)).collect::<Vec<f64>>())
+        .map(|squares| squares.sqrt() * TRADING_DAYS_PER_YEAR.sqrt());
+    let sortino_ratio = match (mean_excess, downside) {
+        (Some(mean_excess), Some(downside)) if downside > f64::EPSILON => Some(mean_excess * TRADING_DAYS_PER_YEAR / downside),
+        _ => None,
+    };
+    let parametric_var = match (mean(&returns), deviation) {
+        (Some(mean), Some(deviation)) => Some(-(mean + inverse_normal(1.0 - confidence) * deviation)),
+        _ => None,
+    };
+    RiskMetrics {
+        observations: returns.len(),
+        annualised_return,
+        volatility,
+        risk_free_rate,
+        sharpe_ratio,
+        sortino_ratio,
+        max_drawdown: max_drawdown(marks),
+        confidence,
+        historical_var: percentile(&returns, 1.0 - confidence).map(|value| -value),
+        parametric_var,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytics::lots::tests::assert_close;
+
+    fn marks(prices: &[f64]) -> Vec<Mark> {
+        let start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        prices.iter().enumerate()
+            .map(|(day, price)| Mark {
+                date: start + chrono::Duration::days(day as i64),
+                price: *price,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drawdown_runs_from_the_peak_to_the_recovery() {
+        let closes = marks(&[100.0, 120.0, 90.0, 60.0, 100.0, 125.0, 110.0]);
+        let drawdown = max_drawdown(&closes).unwrap();
+        assert_close(drawdown.depth, -0.5);
+        assert_eq!(drawdown.peak, closes[1].date);
+        assert_eq!(drawdown.trough, closes[3].date);
+        assert_eq!(drawdown.recovery, Some(closes[5].date));
+        assert!(max_drawdown(&marks(&[1.0, 2.0, 3.0])).is_none());
+    }
+
+    #[test]
+    fn percentile_interpolates_between_ranks() {
+        let values = [4.0, 1.0, 3.0, 2.0, 5.0];
+        assert_eq!(percentile(&values, 0.0), Some(1.0));
+        assert_eq!(percentile(&values, 0.5), Some(3.0));
+        assert_close(percentile(&values, 0.05).unwrap(), 1.2);
+        assert_eq!(percentile(&[], 0.5), None);
+    }
+
+    #[test]
+    fn inverse_normal_matches_known_quantiles() {
+        assert!(inverse_normal(0.5).abs() < 1e-9);
+        assert!((inverse_normal(0.05) + 1.644854).abs() < 1e-6);
+        assert!((inverse_normal(0.99) - 2.326348).abs() < 1e-6);
+    }
+
+    #[test]
+    fn metrics_from_alternating_returns() {
+        // Up 10% then down 10%, twice over
+        let metrics = calculate(&marks(&[100.0, 110.0, 99.0, 108.9, 98.01]), 0.0, 0.95);
+        assert_eq!(metrics.observations, 4);
+        assert_close(metrics.volatility.unwrap(), (0.04_f64 / 3.0).sqrt() * TRADING_DAYS_PER_YEAR.sqrt());
+        assert_close(metrics.parametric_var.unwrap(), -inverse_normal(0.05) * (0.04_f64 / 3.0).sqrt());
+        assert_close(metrics.historical_var.unwrap(), 0.1);
+        assert_close(metrics.max_drawdown.unwrap().depth, 98.01 / 110.0 - 1.0);
+        let flat = calculate(&marks(&[100.0, 100.0, 100.0]), 0.0, 0.95);
+        assert_eq!(flat.sharpe_ratio, None);
+    }
+
+    #[test]
+    fn only_days_with_quotes_are_kept() {
+        let index = marks(&[1.0, 1.1, 1.2, 1.3]);
+        let prices = HashMap::from([
+            ("ABC".to_string(), vec![index[0], index[2]]),
+            ("XYZ".to_string(), vec![index[2], index[3]]),
+        ]);
+        let dates: Vec<NaiveDate> = on_quote_dates(index.clone(), &prices).iter().map(|mark| mark.date).collect();
+        assert_eq!(dates, vec![index[0].date, index[2].date, index[3].date]);
+    }
+}
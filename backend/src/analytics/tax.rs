@@ -0,0 +1,105 @@
+pub mod au;
+pub mod uk;
+pub mod ca;
+pub mod us;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::models::trades::{Country, TradeModel};
+
+#[derive(Debug)]
+pub enum TaxError {
+    Invalid(String),
+}
+
+impl std::fmt::Display for TaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaxError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for TaxError {}
+
+/// Checks every tax year ending in `year`, which can start the year before, has dates that can
+/// be represented
+pub fn validate_year(year: i32) -> Result<i32, TaxError> {
+    let start = year.checked_sub(1).and_then(|previous| NaiveDate::from_ymd_opt(previous, 1, 1));
+    let end = NaiveDate::from_ymd_opt(year, 12, 31);
+    match start.and(end) {
+        Some(_) => Ok(year),
+        None => Err(TaxError::Invalid(format!("{} is not a valid tax year", year))),
+    }
+}
+
+/// The inclusive date range a tax report covers
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaxYear {
+    pub label: String,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl TaxYear {
+    /// Australian financial year ending 30 June of `year`, e.g. 2024 is 1 July 2023 to 30 June 2024
+    pub fn australian(year: i32) -> Self {
+        Self {
+            label: format!("{}-{:02}", year - 1, year % 100),
+            start: NaiveDate::from_ymd_opt(year - 1, 7, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(year, 6, 30).unwrap(),
+        }
+    }
+    /// UK tax year ending 5 April of `year`, e.g. 2024 is 6 April 2023 to 5 April 2024
+    pub fn uk(year: i32) -> Self {
+        Self {
+            label: format!("{}-{:02}", year - 1, year % 100),
+            start: NaiveDate::from_ymd_opt(year - 1, 4, 6).unwrap(),
+            end: NaiveDate::from_ymd_opt(year, 4, 5).unwrap(),
+        }
+    }
+    pub fn calendar(year: i32) -> Self {
+        Self {
+            label: year.to_string(),
+            start: NaiveDate::from_ymd_opt(year, 1, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(year, 12, 31).unwrap(),
+        }
+    }
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        date >= self.start && date <= self.end
+    }
+}
+
+pub fn trades_for_country(trades: &[TradeModel], country: Country) -> Vec<TradeModel> {
+    trades.iter().filter(|trade| trade.country == country).cloned().collect()
+}
+
+/// Joins fields into one CSV line, quoting any field that needs it
+pub fn csv_line(fields: &[String]) -> String {
+    let fields: Vec<String> = fields.iter()
+        .map(|field| match field.contains(',') || field.contains('"') || field.contains('\n') {
+            true => format!("\"{}\"", field.replace('"', "\"\"")),
+            false => field.clone(),
+        })
+        .collect();
+    format!("{}\n", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Datelike;
+
+    #[test]
+    fn years_outside_the_calendar_are_rejected() {
+        assert_eq!(validate_year(2024).unwrap(), 2024);
+        assert!(validate_year(i32::MAX).is_err());
+        assert!(validate_year(i32::MIN).is_err());
+        assert!(validate_year(NaiveDate::MAX.year() + 1).is_err());
+        // The year before has to exist too, since most tax years start in it
+        assert!(validate_year(NaiveDate::MIN.year()).is_err());
+        let year = validate_year(NaiveDate::MAX.year()).unwrap();
+        assert_eq!(uk::calculate(&[], year).tax_year.end.year(), year);
+        assert_eq!(au::calculate(&[], year, Default::default(), 0.0, &Default::default()).unwrap().financial_year.end.year(), year);
+    }
+}
@@ -0,0 +1,346 @@
+use chrono::{Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use crate::analytics::fx::FxTable;
+use crate::analytics::income::{self, Entitlement, IncomeTotals};
+use crate::analytics::lots::{CostBasisMethod, LotEngine};
+use crate::analytics::tax::{csv_line, trades_for_country, TaxError, TaxYear};
+use crate::models::dividends::DividendModel;
+use crate::models::fx::Currency;
+use crate::models::trades::{Country, TradeModel};
+
+/// Share of a discountable gain that is taxed for individuals
+pub const CGT_DISCOUNT: f64 = 0.5;
+/// Corporate tax rate dividends are franked at unless the company is a base rate entity
+pub const COMPANY_TAX_RATE: f64 = 0.3;
+
+/// One parcel disposed of, i.e. a sell matched against a single acquisition. Amounts are in AUD,
+/// converted at the rate on the day they were paid or received.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CgtEvent {
+    pub ticker: String,
+    pub sell_trade_id: i32,
+    pub lot_trade_id: i32,
+    pub acquired: NaiveDate,
+    pub sold: NaiveDate,
+    pub quantity: f64,
+    pub cost_base: f64,
+    pub capital_proceeds: f64,
+    pub gain: f64,
+    pub discount_eligible: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CgtReport {
+    pub financial_year: TaxYear,
+    pub method: CostBasisMethod,
+    pub events: Vec<CgtEvent>,
+    /// Gains before losses and the discount, split by whether the parcel was held over 12 months
+    pub discountable_gains: f64,
+    pub non_discountable_gains: f64,
+    pub current_year_losses: f64,
+    pub losses_carried_in: f64,
+    pub losses_applied: f64,
+    pub discount_applied: f64,
+    pub net_capital_gain: f64,
+    pub losses_carried_forward: f64,
+    /// Set when a conversion to AUD had no stored rate and was made at par
+    pub missing_fx: bool,
+}
+
+/// The discount needs the asset held for at least 12 months, not counting the days it was
+/// acquired and disposed of
+pub fn discount_eligible(acquired: NaiveDate, sold: NaiveDate) -> bool {
+    match acquired.checked_add_months(Months::new(12)) {
+        Some(anniversary) => sold > anniversary,
+        None => false,
+    }
+}
+
+/// Builds the CGT report for the financial year ending 30 June `year` from the AU trades.
+/// Every AU trade is replayed so parcels bought in earlier years are matched correctly. The ATO
+/// only accepts identifiable parcels for shares, so average cost is rejected.
+pub fn calculate(trades: &[TradeModel], year: i32, method: CostBasisMethod, losses_carried_in: f64, fx: &FxTable) -> Result<CgtReport, TaxError> {
+    if method == CostBasisMethod::AverageCost {
+        return Err(TaxError::Invalid("Average cost can't be used for Australian CGT on shares".to_string()));
+    }
+    let financial_year = TaxYear::australian(year);
+    let engine = LotEngine::replay(method, &trades_for_country(trades, Country::AU));
+    let mut missing_fx = false;
+    let mut convert = |currency: Currency, date: NaiveDate| -> f64 {
+        fx.rate(currency, Currency::AUD, date).unwrap_or_else(|| {
+            missing_fx = true;
+            1.0
+        })
+    };
+    let mut events = Vec::new();
+    for disposal in engine.disposals().iter().filter(|disposal| financial_year.contains(disposal.sold)) {
+        let sale_rate = convert(disposal.currency, disposal.sold);
+        for matched in &disposal.matches {
+            let cost_base = matched.cost_basis * convert(disposal.currency, matched.acquired);
+            let capital_proceeds = matched.proceeds * sale_rate;
+            events.push(CgtEvent {
+                ticker: disposal.ticker.clone(),
+                sell_trade_id: disposal.trade_id,
+                lot_trade_id: matched.lot_trade_id,
+                acquired: matched.acquired,
+                sold: disposal.sold,
+                quantity: matched.quantity,
+                cost_base,
+                capital_proceeds,
+                gain: capital_proceeds - cost_base,
+                discount_eligible: discount_eligible(matched.acquired, disposal.sold),
+            });
+        }
+    }
+
+    let gains = |discountable: bool| -> f64 {
+        events.iter()
+            .filter(|event| event.gain > 0.0 && event.discount_eligible == discountable)
+            .fold(0.0, |total, event| total + event.gain)
+    };
+    let discountable_gains = gains(true);
+    let non_discountable_gains = gains(false);
+    let current_year_losses = events.iter()
+        .filter(|event| event.gain < 0.0)
+        .fold(0.0, |total, event| total - event.gain);
+
+    // Losses come off non-discountable gains first so as much as possible keeps the discount
+    let available_losses = current_year_losses + losses_carried_in.max(0.0);
+    let against_non_discountable = available_losses.min(non_discountable_gains);
+    let against_discountable = (available_losses - against_non_discountable).min(discountable_gains);
+    let remaining_discountable = discountable_gains - against_discountable;
+    let discount_applied = remaining_discountable * CGT_DISCOUNT;
+    let losses_applied = against_non_discountable + against_discountable;
+
+    Ok(CgtReport {
+        financial_year,
+        method,
+        events,
+        discountable_gains,
+        non_discountable_gains,
+        current_year_losses,
+        losses_carried_in,
+        losses_applied,
+        discount_applied,
+        net_capital_gain: non_discountable_gains - against_non_discountable + remaining_discountable - discount_applied,
+        losses_carried_forward: available_losses - losses_applied,
+        missing_fx,
+    })
+}
+
+impl CgtReport {
+    /// One row per CGT event followed by the summary figures, for handing to an accountant
+    pub fn to_csv(&self) -> String {
+        let mut csv = csv_line(&[
+            "ticker", "acquired", "sold", "quantity", "cost_base", "capital_proceeds", "gain", "discount_eligible",
+        ].map(String::from));
+        for event in &self.events {
+            csv += &csv_line(&[
+                event.ticker.clone(),
+                event.acquired.to_string(),
+                event.sold.to_string(),
+                event.quantity.to_string(),
+                format!("{:.2}", event.cost_base),
+                format!("{:.2}", event.capital_proceeds),
+                format!("{:.2}", event.gain),
+                event.discount_eligible.to_string(),
+            ]);
+        }
+        csv += "\n";
+        csv += &csv_line(&["summary".to_string(), self.financial_year.label.clone()]);
+        for (name, value) in [
+            ("discountable_gains", self.discountable_gains),
+            ("non_discountable_gains", self.non_discountable_gains),
+            ("current_year_losses", self.current_year_losses),
+            ("losses_carried_in", self.losses_carried_in),
+            ("losses_applied", self.losses_applied),
+            ("discount_applied", self.discount_applied),
+            ("net_capital_gain", self.net_capital_gain),
+            ("losses_carried_forward", self.losses_carried_forward),
+        ] {
+            csv += &csv_line(&[name.to_string(), format!("{:.2}", value)]);
+        }
+        csv
+    }
+}
+
+/// Tax the company already paid on a franked amount, which the shareholder gets as a credit
+pub fn franking_credit(franked_amount: f64, company_tax_rate: f64) -> f64 {
+    match company_tax_rate > 0.0 && company_tax_rate < 1.0 {
+        true => franked_amount * company_tax_rate / (1.0 - company_tax_rate),
+        false => 0.0,
+    }
+}
+
+/// Dividends on AU holdings paid during a financial year, which is when they are assessable
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DividendIncomeReport {
+    pub financial_year: TaxYear,
+    pub distributions: Vec<Entitlement>,
+    pub totals: IncomeTotals,
+}
+
+/// Builds the dividend income summary for the financial year ending 30 June `year`, with
+/// entitlements worked out from the AU trades
+pub fn income(dividends: &[DividendModel], trades: &[TradeModel], year: i32) -> DividendIncomeReport {
+    let financial_year = TaxYear::australian(year);
+    let distributions: Vec<Entitlement> = income::entitlements(dividends, &trades_for_country(trades, Country::AU))
+        .into_iter()
+        .filter(|entitlement| financial_year.contains(entitlement.pay_date))
+        .collect();
+    let mut totals = IncomeTotals::default();
+    for distribution in &distributions {
+        totals.add(distribution, 1.0);
+    }
+    DividendIncomeReport {
+        financial_year,
+        distributions,
+        totals,
+    }
+}
+
+impl DividendIncomeReport {
+    pub fn to_csv(&self) -> String {
+        let mut csv = csv_line(&[
+            "ticker", "ex_date", "pay_date", "shares", "amount_per_share", "franking_percent", "unfranked_amount",
+            "franked_amount", "franking_credits", "grossed_up", "withholding_tax", "net",
+        ].map(String::from));
+        for distribution in &self.distributions {
+            csv += &csv_line(&[
+                distribution.ticker.clone(),
+                distribution.ex_date.to_string(),
+                distribution.pay_date.to_string(),
+                distribution.shares.to_string(),
+                distribution.amount_per_share.to_string(),
+                format!("{:.2}", distribution.franking_percent),
+                format!("{:.2}", distribution.unfranked_amount),
+                format!("{:.2}", distribution.franked_amount),
+                format!("{:.2}", distribution.franking_credits),
+                format!("{:.2}", distribution.grossed_up),
+                format!("{:.2}", distribution.withholding_tax),
+                format!("{:.2}", distribution.net),
+            ]);
+        }
+        csv += "\n";
+        csv += &csv_line(&["summary".to_string(), self.financial_year.label.clone()]);
+        for (name, value) in [
+            ("unfranked_amount", self.totals.unfranked_amount),
+            ("franked_amount", self.totals.franked_amount),
+            ("franking_credits", self.totals.franking_credits),
+            ("grossed_up", self.totals.grossed_up),
+            ("withholding_tax", self.totals.withholding_tax),
+            ("net", self.totals.net),
+        ] {
+            csv += &csv_line(&[name.to_string(), format!("{:.2}", value)]);
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::FromPrimitive;
+    use sqlx::types::BigDecimal;
+    use crate::analytics::lots::tests::{assert_close, trade};
+    use crate::models::fx::FxRateModel;
+    use crate::models::trades::TradeType;
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn discount_needs_more_than_twelve_months() {
+        assert!(!discount_eligible(date("2020-01-01"), date("2021-01-01")));
+        assert!(discount_eligible(date("2020-01-01"), date("2021-01-02")));
+        // A leap day anniversary falls on 28 February
+        assert!(!discount_eligible(date("2020-02-29"), date("2021-02-28")));
+        assert!(discount_eligible(date("2020-02-29"), date("2021-03-01")));
+    }
+
+    #[test]
+    fn partial_parcel_splits_discountable_and_not() {
+        let trades = vec![
+            trade(1, Country::AU, "2020-01-01", TradeType::Buy, 10, 10.0),
+            trade(2, Country::AU, "2023-01-01", TradeType::Buy, 10, 30.0),
+            trade(3, Country::AU, "2023-08-01", TradeType::Sell, 15, 20.0),
+        ];
+        let report = calculate(&trades, 2024, CostBasisMethod::Fifo, 0.0, &FxTable::default()).unwrap();
+        let events: Vec<(i32, f64, f64, bool)> = report.events.iter()
+            .map(|event| (event.lot_trade_id, event.quantity, event.gain, event.discount_eligible))
+            .collect();
+        assert_eq!(events, vec![(1, 10.0, 100.0, true), (2, 5.0, -50.0, false)]);
+        assert_eq!(report.discountable_gains, 100.0);
+        assert_eq!(report.current_year_losses, 50.0);
+        // The loss comes off before the discount halves what is left
+        assert_eq!(report.discount_applied, 25.0);
+        assert_eq!(report.net_capital_gain, 25.0);
+        assert_eq!(report.losses_carried_forward, 0.0);
+    }
+
+    #[test]
+    fn losses_come_off_non_discountable_gains_first() {
+        let trades = vec![
+            trade(1, Country::AU, "2020-01-01", TradeType::Buy, 10, 10.0),
+            trade(2, Country::AU, "2023-01-01", TradeType::Buy, 10, 10.0),
+            trade(3, Country::AU, "2023-08-01", TradeType::Sell, 20, 20.0),
+        ];
+        let report = calculate(&trades, 2024, CostBasisMethod::Fifo, 120.0, &FxTable::default()).unwrap();
+        assert_eq!(report.non_discountable_gains, 100.0);
+        assert_eq!(report.discountable_gains, 100.0);
+        assert_eq!(report.losses_applied, 120.0);
+        assert_eq!(report.discount_applied, 40.0);
+        assert_eq!(report.net_capital_gain, 40.0);
+
+        let report = calculate(&trades, 2024, CostBasisMethod::Fifo, 250.0, &FxTable::default()).unwrap();
+        assert_eq!(report.net_capital_gain, 0.0);
+        assert_eq!(report.losses_carried_forward, 50.0);
+    }
+
+    #[test]
+    fn only_sales_in_the_financial_year_are_reported() {
+        let trades = vec![
+            trade(1, Country::AU, "2020-01-01", TradeType::Buy, 10, 10.0),
+            trade(2, Country::AU, "2023-06-30", TradeType::Sell, 4, 20.0),
+            trade(3, Country::AU, "2023-07-01", TradeType::Sell, 6, 20.0),
+            trade(4, Country::US, "2023-07-01", TradeType::Sell, 6, 20.0),
+        ];
+        let report = calculate(&trades, 2024, CostBasisMethod::Fifo, 0.0, &FxTable::default()).unwrap();
+        let sells: Vec<i32> = report.events.iter().map(|event| event.sell_trade_id).collect();
+        assert_eq!(sells, vec![3]);
+        assert_eq!(report.discountable_gains, 60.0);
+    }
+
+    #[test]
+    fn average_cost_is_rejected() {
+        let trades = vec![trade(1, Country::AU, "2020-01-01", TradeType::Buy, 10, 10.0)];
+        assert!(calculate(&trades, 2024, CostBasisMethod::AverageCost, 0.0, &FxTable::default()).is_err());
+    }
+
+    #[test]
+    fn foreign_currency_parcels_are_converted_on_each_date() {
+        let mut trades = vec![
+            trade(1, Country::AU, "2022-01-01", TradeType::Buy, 10, 10.0),
+            trade(2, Country::AU, "2023-08-01", TradeType::Sell, 10, 10.0),
+        ];
+        for trade in &mut trades {
+            trade.currency = Currency::USD;
+        }
+        let fx = FxTable::from_rates([("2022-01-01", 1.25), ("2023-08-01", 1.5)].iter()
+            .map(|(on, rate)| FxRateModel {
+                from_currency: Currency::USD,
+                to_currency: Currency::AUD,
+                date: date(on),
+                rate: BigDecimal::from_f64(*rate).unwrap(),
+            })
+            .collect());
+        let report = calculate(&trades, 2024, CostBasisMethod::Fifo, 0.0, &fx).unwrap();
+        // No gain in USD, but the dollar rose from 1.25 to 1.5
+        assert_close(report.events[0].cost_base, 125.0);
+        assert_close(report.events[0].capital_proceeds, 150.0);
+        assert_close(report.discountable_gains, 25.0);
+        assert!(!report.missing_fx);
+        assert!(calculate(&trades, 2024, CostBasisMethod::Fifo, 0.0, &FxTable::default()).unwrap().missing_fx);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,227 @@
+use std::collections::{BTreeSet, HashMap};
+use bigdecimal::ToPrimitive;
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use crate::analytics::lots::sorted_trades;
+use crate::analytics::tax::{trades_for_country, TaxYear};
+use crate::models::trades::{Country, TradeModel, TradeType};
+
+/// A loss is superficial when identical shares are bought within this many days either side
+pub const SUPERFICIAL_LOSS_DAYS: i64 = 30;
+/// Share of a net capital gain that is taxable
+pub const INCLUSION_RATE: f64 = 0.5;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Disposition {
+    pub trade_id: i32,
+    pub ticker: String,
+    pub date: NaiveDate,
+    pub quantity: f64,
+    pub proceeds: f64,
+    pub adjusted_cost_base: f64,
+    pub gain: f64,
+    /// Part of the loss denied as superficial and added to the replacement shares' ACB
+    pub superficial_loss: f64,
+    pub allowable_gain: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AcbPosition {
+    pub ticker: String,
+    pub quantity: f64,
+    pub total_acb: f64,
+    pub acb_per_share: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AcbReport {
+    pub tax_year: TaxYear,
+    pub dispositions: Vec<Disposition>,
+    /// ACB of each security held at the end of the tax year
+    pub positions: Vec<AcbPosition>,
+    pub total_gains: f64,
+    pub total_losses: f64,
+    pub denied_losses: f64,
+    pub net_gain: f64,
+    pub taxable_capital_gain: f64,
+}
+
+/// Share of a loss that is superficial: the smallest of shares sold, shares bought in the
+/// 61-day window around the sale and shares still held at the end of the window, over shares sold
+fn superficial_fraction(trades: &[&TradeModel], sold: f64, date: NaiveDate) -> f64 {
+    let window_start = date - Duration::days(SUPERFICIAL_LOSS_DAYS);
+    let window_end = date + Duration::days(SUPERFICIAL_LOSS_DAYS);
+    let bought_in_window = trades.iter()
+        .filter(|trade| trade.trade_type == TradeType::Buy && trade.date >= window_start && trade.date <= window_end)
+        .fold(0.0, |total, trade| total + trade.amount as f64);
+    let held_at_end = trades.iter()
+        .filter(|trade| trade.date <= window_end)
+        .fold(0.0, |total, trade| match trade.trade_type {
+            TradeType::Buy => total + trade.amount as f64,
+            TradeType::Sell => total - trade.amount as f64,
+        });
+    match sold > 0.0 {
+        true => sold.min(bought_in_window).min(held_at_end).max(0.0) / sold,
+        false => 0.0,
+    }
+}
+
+/// Runs one security's trades through its ACB pool, returning every disposition and the
+/// position at the end of `until`
+fn track_ticker(ticker: &str, trades: &[&TradeModel], until: NaiveDate) -> (Vec<Disposition>, AcbPosition) {
+    let mut position = AcbPosition {
+        ticker: ticker.to_string(),
+        ..Default::default()
+    };
+    let mut dispositions = Vec::new();
+    for trade in trades.iter().filter(|trade| trade.date <= until) {
+        let quantity = trade.amount as f64;
+        let price = trade.price.to_f64().unwrap_or(0.0);
+        match trade.trade_type {
+            TradeType::Buy => {
+                position.quantity += quantity;
+                position.total_acb += quantity * price;
+            },
+            TradeType::Sell => {
+                let sold = quantity.min(position.quantity);
+                let adjusted_cost_base = match position.quantity > 0.0 {
+                    true => position.total_acb * sold / position.quantity,
+                    false => 0.0,
+                };
+                position.quantity -= sold;
+                position.total_acb -= adjusted_cost_base;
+                let proceeds = quantity * price;
+                let gain = proceeds - adjusted_cost_base;
+                let superficial_loss = match gain < 0.0 {
+                    true => -gain * superficial_fraction(trades, quantity, trade.date),
+                    false => 0.0,
+                };
+                // The denied loss moves into the cost of the shares that replaced those sold
+                position.total_acb += superficial_loss;
+                dispositions.push(Disposition {
+                    trade_id: trade.id,
+                    ticker: ticker.to_string(),
+                    date: trade.date,
+                    quantity,
+                    proceeds,
+                    adjusted_cost_base,
+                    gain,
+                    superficial_loss,
+                    allowable_gain: gain + superficial_loss,
+                });
+            }
+        }
+    }
+    position.acb_per_share = match position.quantity > 0.0 {
+        true => Some(position.total_acb / position.quantity),
+        false => None,
+    };
+    (dispositions, position)
+}
+
+/// Builds the ACB report for calendar `year` from the CA trades
+pub fn calculate(trades: &[TradeModel], year: i32) -> AcbReport {
+    let tax_year = TaxYear::calendar(year);
+    let ca_trades = trades_for_country(trades, Country::CA);
+    let mut by_ticker: HashMap<String, Vec<&TradeModel>> = HashMap::new();
+    for trade in sorted_trades(&ca_trades) {
+        by_ticker.entry(trade.ticker.clone()).or_default().push(trade);
+    }
+    let tickers: BTreeSet<&String> = by_ticker.keys().collect();
+    let mut dispositions = Vec::new();
+    let mut positions = Vec::new();
+    for ticker in tickers {
+        let (ticker_dispositions, position) = track_ticker(ticker, &by_ticker[ticker], tax_year.end);
+        dispositions.extend(ticker_dispositions.into_iter().filter(|disposition| tax_year.contains(disposition.date)));
+        if position.quantity > 0.0 {
+            positions.push(position);
+        }
+    }
+    dispositions.sort_by_key(|disposition| (disposition.date, disposition.trade_id));
+    let total_gains = dispositions.iter()
+        .filter(|d| d.allowable_gain > 0.0)
+        .fold(0.0, |total, d| total + d.allowable_gain);
+    let total_losses = dispositions.iter()
+        .filter(|d| d.allowable_gain < 0.0)
+        .fold(0.0, |total, d| total - d.allowable_gain);
+    let net_gain = total_gains - total_losses;
+    AcbReport {
+        tax_year,
+        denied_losses: dispositions.iter().fold(0.0, |total, d| total + d.superficial_loss),
+        dispositions,
+        positions,
+        total_gains,
+        total_losses,
+        net_gain,
+        taxable_capital_gain: net_gain.max(0.0) * INCLUSION_RATE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytics::lots::tests::{assert_close, trade};
+
+    #[test]
+    fn gains_use_the_average_cost_and_inclusion_rate() {
+        let trades = vec![
+            trade(1, Country::CA, "2023-01-01", TradeType::Buy, 10, 10.0),
+            trade(2, Country::CA, "2023-02-01", TradeType::Buy, 10, 20.0),
+            trade(3, Country::CA, "2023-03-01", TradeType::Sell, 5, 30.0),
+        ];
+        let report = calculate(&trades, 2023);
+        assert_eq!(report.dispositions[0].adjusted_cost_base, 75.0);
+        assert_eq!(report.dispositions[0].gain, 75.0);
+        assert_eq!(report.taxable_capital_gain, 37.5);
+        assert_eq!(report.positions[0].quantity, 15.0);
+        assert_eq!(report.positions[0].acb_per_share, Some(15.0));
+    }
+
+    #[test]
+    fn buying_back_within_thirty_days_after_denies_the_loss() {
+        let trades = vec![
+            trade(1, Country::CA, "2023-01-01", TradeType::Buy, 100, 10.0),
+            trade(2, Country::CA, "2023-06-01", TradeType::Sell, 100, 8.0),
+            trade(3, Country::CA, "2023-07-01", TradeType::Buy, 50, 9.0),
+        ];
+        let report = calculate(&trades, 2023);
+        let disposition = &report.dispositions[0];
+        // Only the 50 shares bought back are replacements, so half the loss is denied
+        assert_eq!(disposition.gain, -200.0);
+        assert_eq!(disposition.superficial_loss, 100.0);
+        assert_eq!(disposition.allowable_gain, -100.0);
+        assert_eq!(report.denied_losses, 100.0);
+        assert_eq!(report.positions[0].total_acb, 550.0);
+    }
+
+    #[test]
+    fn buying_back_on_day_thirty_one_keeps_the_loss() {
+        let trades = vec![
+            trade(1, Country::CA, "2023-01-01", TradeType::Buy, 100, 10.0),
+            trade(2, Country::CA, "2023-06-01", TradeType::Sell, 100, 8.0),
+            trade(3, Country::CA, "2023-07-02", TradeType::Buy, 50, 9.0),
+        ];
+        let report = calculate(&trades, 2023);
+        assert_eq!(report.dispositions[0].superficial_loss, 0.0);
+        assert_eq!(report.total_losses, 200.0);
+        assert_eq!(report.positions[0].total_acb, 450.0);
+    }
+
+    #[test]
+    fn buying_within_thirty_days_before_denies_the_loss() {
+        let trades = vec![
+            trade(1, Country::CA, "2023-01-01", TradeType::Buy, 100, 10.0),
+            trade(2, Country::CA, "2023-05-02", TradeType::Buy, 100, 8.0),
+            trade(3, Country::CA, "2023-06-01", TradeType::Sell, 100, 7.0),
+        ];
+        let report = calculate(&trades, 2023);
+        assert_close(report.dispositions[0].gain, -200.0);
+        assert_close(report.dispositions[0].superficial_loss, 200.0);
+        assert_close(report.net_gain, 0.0);
+        assert_close(report.positions[0].total_acb, 1100.0);
+
+        let mut trades = trades;
+        trades[1] = trade(2, Country::CA, "2023-05-01", TradeType::Buy, 100, 8.0);
+        assert_eq!(calculate(&trades, 2023).dispositions[0].superficial_loss, 0.0);
+    }
+}
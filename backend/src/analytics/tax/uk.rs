@@ -0,0 +1,287 @@
+use std::collections::{BTreeMap, BTreeSet};
+use bigdecimal::ToPrimitive;
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use strum_macros::{EnumString, Display};
+use crate::analytics::tax::{trades_for_country, TaxYear};
+use crate::models::trades::{Country, TradeModel, TradeType};
+
+/// Acquisitions within this many days after a disposal are matched to it
+pub const BED_AND_BREAKFAST_DAYS: i64 = 30;
+
+#[derive(Serialize, Deserialize, Debug, EnumString, Display, Clone, Copy, PartialEq)]
+pub enum MatchingRule {
+    SameDay,
+    BedAndBreakfast,
+    Section104,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShareMatch {
+    pub rule: MatchingRule,
+    /// Date of the matched acquisition, `None` for the Section 104 pool
+    pub acquired: Option<NaiveDate>,
+    pub quantity: f64,
+    pub allowable_cost: f64,
+}
+
+/// All sells of one ticker on one day, which HMRC treats as a single disposal
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UkDisposal {
+    pub ticker: String,
+    pub date: NaiveDate,
+    pub quantity: f64,
+    pub proceeds: f64,
+    pub allowable_cost: f64,
+    pub gain: f64,
+    /// Shares sold beyond everything held, left without a cost
+    pub unmatched_quantity: f64,
+    pub matches: Vec<ShareMatch>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Section104Pool {
+    pub ticker: String,
+    pub quantity: f64,
+    pub cost: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UkGainsReport {
+    pub tax_year: TaxYear,
+    pub disposals: Vec<UkDisposal>,
+    pub total_proceeds: f64,
+    pub total_gains: f64,
+    pub total_losses: f64,
+    pub net_gain: f64,
+    /// Pools as they stand at the end of the tax year
+    pub pools: Vec<Section104Pool>,
+}
+
+/// Quantity and cost of one side of a day's trading, with how much is still to be matched
+#[derive(Debug, Clone, Default)]
+struct DayTotal {
+    quantity: f64,
+    value: f64,
+    remaining: f64,
+}
+
+impl DayTotal {
+    fn unit_value(&self) -> f64 {
+        match self.quantity > 0.0 {
+            true => self.value / self.quantity,
+            false => 0.0,
+        }
+    }
+}
+
+fn match_acquisition(disposal: &mut DayTotal, acquisition: &mut DayTotal) -> f64 {
+    let matched = disposal.remaining.min(acquisition.remaining);
+    disposal.remaining -= matched;
+    acquisition.remaining -= matched;
+    matched
+}
+
+/// Applies the same-day, bed-and-breakfast and Section 104 rules to one ticker's trades up to
+/// `until`, returning every disposal and the pool as it stood at the end of `pool_date`
+fn match_ticker(ticker: &str, trades: &[&TradeModel], until: NaiveDate, pool_date: NaiveDate) -> (Vec<UkDisposal>, Section104Pool) {
+    let mut acquisitions: BTreeMap<NaiveDate, DayTotal> = BTreeMap::new();
+    let mut disposals: BTreeMap<NaiveDate, DayTotal> = BTreeMap::new();
+    for trade in trades.iter().filter(|trade| trade.date <= until) {
+        let side = match trade.trade_type {
+            TradeType::Buy => acquisitions.entry(trade.date).or_default(),
+            TradeType::Sell => disposals.entry(trade.date).or_default(),
+        };
+        side.quantity += trade.amount as f64;
+        side.remaining += trade.amount as f64;
+        side.value += trade.amount as f64 * trade.price.to_f64().unwrap_or(0.0);
+    }
+    let mut matches: BTreeMap<NaiveDate, Vec<ShareMatch>> = BTreeMap::new();
+
+    // Same-day acquisitions take priority over any bed-and-breakfast match
+    for (date, disposal) in disposals.iter_mut() {
+        if let Some(acquisition) = acquisitions.get_mut(date) {
+            let matched = match_acquisition(disposal, acquisition);
+            if matched > 0.0 {
+                matches.entry(*date).or_default().push(ShareMatch {
+                    rule: MatchingRule::SameDay,
+                    acquired: Some(*date),
+                    quantity: matched,
+                    allowable_cost: matched * acquisition.unit_value(),
+                });
+            }
+        }
+    }
+    for (date, disposal) in disposals.iter_mut() {
+        let window_end = *date + Duration::days(BED_AND_BREAKFAST_DAYS);
+        for (acquired, acquisition) in acquisitions.range_mut(date.succ_opt().unwrap_or(*date)..=window_end) {
+            if disposal.remaining <= 0.0 {
+                break;
+            }
+            let matched = match_acquisition(disposal, acquisition);
+            if matched > 0.0 {
+                matches.entry(*date).or_default().push(ShareMatch {
+                    rule: MatchingRule::BedAndBreakfast,
+                    acquired: Some(*acquired),
+                    quantity: matched,
+                    allowable_cost: matched * acquisition.unit_value(),
+                });
+            }
+        }
+    }
+
+    // Whatever is left flows through the pool in date order, acquisitions before disposals
+    let mut pool = Section104Pool {
+        ticker: ticker.to_string(),
+        ..Default::default()
+    };
+    let mut snapshot = None;
+    let mut results = Vec::new();
+    let dates: BTreeSet<NaiveDate> = acquisitions.keys().chain(disposals.keys()).copied().collect();
+    for date in dates {
+        if date > pool_date && snapshot.is_none() {
+            snapshot = Some(pool.clone());
+        }
+        if let Some(acquisition) = acquisitions.get(&date) {
+            pool.quantity += acquisition.remaining;
+            pool.cost += acquisition.remaining * acquisition.unit_value();
+        }
+        if let Some(disposal) = disposals.get(&date) {
+            let mut day_matches = matches.remove(&date).unwrap_or_default();
+            let from_pool = disposal.remaining.min(pool.quantity);
+            if from_pool > 0.0 {
+                let cost = pool.cost * from_pool / pool.quantity;
+                pool.quantity -= from_pool;
+                pool.cost -= cost;
+                day_matches.push(ShareMatch {
+                    rule: MatchingRule::Section104,
+                    acquired: None,
+                    quantity: from_pool,
+                    allowable_cost: cost,
+                });
+            }
+            let allowable_cost = day_matches.iter().fold(0.0, |total, m| total + m.allowable_cost);
+            results.push(UkDisposal {
+                ticker: ticker.to_string(),
+                date,
+                quantity: disposal.quantity,
+                proceeds: disposal.value,
+                allowable_cost,
+                gain: disposal.value - allowable_cost,
+                unmatched_quantity: disposal.remaining - from_pool,
+                matches: day_matches,
+            });
+        }
+    }
+    (results, snapshot.unwrap_or(pool))
+}
+
+/// Builds the gains report for the UK tax year ending 5 April `year` from the UK trades.
+/// Trades up to 30 days after the year end are included so bed-and-breakfast matches for
+/// late disposals are picked up.
+pub fn calculate(trades: &[TradeModel], year: i32) -> UkGainsReport {
+    let tax_year = TaxYear::uk(year);
+    let uk_trades = trades_for_country(trades, Country::UK);
+    let tickers: BTreeSet<String> = uk_trades.iter().map(|trade| trade.ticker.clone()).collect();
+    let mut disposals = Vec::new();
+    let mut pools = Vec::new();
+    for ticker in tickers {
+        let ticker_trades: Vec<&TradeModel> = uk_trades.iter().filter(|trade| trade.ticker == ticker).collect();
+        let until = tax_year.end + Duration::days(BED_AND_BREAKFAST_DAYS);
+        let (ticker_disposals, pool) = match_ticker(&ticker, &ticker_trades, until, tax_year.end);
+        disposals.extend(ticker_disposals.into_iter().filter(|disposal| tax_year.contains(disposal.date)));
+        if pool.quantity > 0.0 {
+            pools.push(pool);
+        }
+    }
+    disposals.sort_by_key(|disposal| disposal.date);
+    let total_gains = disposals.iter().filter(|d| d.gain > 0.0).fold(0.0, |total, d| total + d.gain);
+    let total_losses = disposals.iter().filter(|d| d.gain < 0.0).fold(0.0, |total, d| total - d.gain);
+    UkGainsReport {
+        tax_year,
+        total_proceeds: disposals.iter().fold(0.0, |total, d| total + d.proceeds),
+        disposals,
+        total_gains,
+        total_losses,
+        net_gain: total_gains - total_losses,
+        pools,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytics::lots::tests::trade;
+
+    fn rules(disposal: &UkDisposal) -> Vec<(MatchingRule, f64, f64)> {
+        disposal.matches.iter().map(|m| (m.rule, m.quantity, m.allowable_cost)).collect()
+    }
+
+    #[test]
+    fn same_day_buys_match_before_the_pool() {
+        let trades = vec![
+            trade(1, Country::UK, "2023-01-01", TradeType::Buy, 100, 10.0),
+            trade(2, Country::UK, "2023-06-01", TradeType::Sell, 30, 20.0),
+            trade(3, Country::UK, "2023-06-01", TradeType::Buy, 30, 15.0),
+            trade(4, Country::UK, "2023-06-01", TradeType::Sell, 20, 20.0),
+        ];
+        let report = calculate(&trades, 2024);
+        // Both sells on the day are one disposal
+        assert_eq!(report.disposals.len(), 1);
+        let disposal = &report.disposals[0];
+        assert_eq!(disposal.quantity, 50.0);
+        assert_eq!(rules(disposal), vec![(MatchingRule::SameDay, 30.0, 450.0), (MatchingRule::Section104, 20.0, 200.0)]);
+        assert_eq!(disposal.gain, 350.0);
+        assert_eq!(report.pools[0].quantity, 80.0);
+        assert_eq!(report.pools[0].cost, 800.0);
+    }
+
+    #[test]
+    fn bed_and_breakfast_covers_thirty_days_after() {
+        let trades = vec![
+            trade(1, Country::UK, "2023-01-01", TradeType::Buy, 100, 10.0),
+            trade(2, Country::UK, "2023-06-01", TradeType::Sell, 40, 8.0),
+            // Day 30 is inside the window and day 31 is not
+            trade(3, Country::UK, "2023-07-01", TradeType::Buy, 10, 9.0),
+            trade(4, Country::UK, "2023-07-02", TradeType::Buy, 10, 12.0),
+        ];
+        let report = calculate(&trades, 2024);
+        let disposal = &report.disposals[0];
+        assert_eq!(rules(disposal), vec![(MatchingRule::BedAndBreakfast, 10.0, 90.0), (MatchingRule::Section104, 30.0, 300.0)]);
+        assert_eq!(disposal.matches[0].acquired, Some(NaiveDate::from_ymd_opt(2023, 7, 1).unwrap()));
+        assert_eq!(disposal.gain, -70.0);
+        assert_eq!(report.total_losses, 70.0);
+        // The matched buy never reaches the pool, the one after the window does
+        assert_eq!(report.pools[0].quantity, 80.0);
+        assert_eq!(report.pools[0].cost, 820.0);
+    }
+
+    #[test]
+    fn buys_after_the_year_end_still_match_late_disposals() {
+        let trades = vec![
+            trade(1, Country::UK, "2023-01-01", TradeType::Buy, 100, 10.0),
+            trade(2, Country::UK, "2024-04-01", TradeType::Sell, 10, 15.0),
+            trade(3, Country::UK, "2024-04-20", TradeType::Buy, 10, 13.0),
+        ];
+        let report = calculate(&trades, 2024);
+        let disposal = &report.disposals[0];
+        assert_eq!(rules(disposal), vec![(MatchingRule::BedAndBreakfast, 10.0, 130.0)]);
+        assert_eq!(disposal.gain, 20.0);
+        // The pool is as it stood on 5 April
+        assert_eq!(report.pools[0].quantity, 100.0);
+        assert_eq!(report.pools[0].cost, 1000.0);
+        assert!(calculate(&trades, 2025).disposals.is_empty());
+    }
+
+    #[test]
+    fn selling_more_than_held_is_unmatched() {
+        let trades = vec![
+            trade(1, Country::UK, "2023-05-01", TradeType::Buy, 10, 10.0),
+            trade(2, Country::UK, "2023-06-01", TradeType::Sell, 15, 20.0),
+        ];
+        let report = calculate(&trades, 2024);
+        assert_eq!(report.disposals[0].unmatched_quantity, 5.0);
+        assert_eq!(report.disposals[0].allowable_cost, 100.0);
+        assert!(report.pools.is_empty());
+    }
+}
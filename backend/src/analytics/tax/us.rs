@@ -0,0 +1,359 @@
+use std::collections::{BTreeSet, HashMap};
+use bigdecimal::ToPrimitive;
+use chrono::{Duration, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use strum_macros::{EnumString, Display};
+use crate::analytics::lots::sorted_trades;
+use crate::analytics::tax::{trades_for_country, TaxYear};
+use crate::models::trades::{Country, TradeModel, TradeType};
+
+/// Buying substantially identical shares within this many days either side of a loss is a wash sale
+pub const WASH_SALE_DAYS: i64 = 30;
+
+#[derive(Serialize, Deserialize, Debug, EnumString, Display, Clone, Copy, PartialEq)]
+pub enum HoldingTerm {
+    ShortTerm,
+    LongTerm,
+}
+
+/// A tax lot with its basis adjusted for any wash sales it replaced
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaxLot {
+    pub trade_id: i32,
+    pub ticker: String,
+    pub acquired: NaiveDate,
+    /// Start of the holding period, earlier than `acquired` when a wash sale's period was tacked on
+    pub holding_period_start: NaiveDate,
+    pub quantity: f64,
+    pub cost_basis: f64,
+    pub wash_sale_adjustment: f64,
+    #[serde(skip)]
+    replaces_wash_sale: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LotSale {
+    pub ticker: String,
+    pub sell_trade_id: i32,
+    pub lot_trade_id: i32,
+    pub acquired: NaiveDate,
+    pub sold: NaiveDate,
+    pub quantity: f64,
+    pub proceeds: f64,
+    pub cost_basis: f64,
+    pub gain: f64,
+    pub term: HoldingTerm,
+    /// Loss disallowed by the wash sale rule and deferred into a replacement lot
+    pub wash_sale_disallowed: f64,
+    pub reportable_gain: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TermTotals {
+    pub proceeds: f64,
+    pub cost_basis: f64,
+    pub wash_sale_disallowed: f64,
+    pub gain: f64,
+}
+
+impl TermTotals {
+    fn add(&mut self, sale: &LotSale) {
+        self.proceeds += sale.proceeds;
+        self.cost_basis += sale.cost_basis;
+        self.wash_sale_disallowed += sale.wash_sale_disallowed;
+        self.gain += sale.reportable_gain;
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UsTaxLotReport {
+    pub tax_year: TaxYear,
+    pub sales: Vec<LotSale>,
+    pub short_term: TermTotals,
+    pub long_term: TermTotals,
+    /// Lots still held at the end of the tax year, with wash sale adjustments applied
+    pub open_lots: Vec<TaxLot>,
+}
+
+pub fn holding_term(holding_period_start: NaiveDate, sold: NaiveDate) -> HoldingTerm {
+    match holding_period_start.checked_add_months(Months::new(12)) {
+        Some(anniversary) if sold > anniversary => HoldingTerm::LongTerm,
+        _ => HoldingTerm::ShortTerm,
+    }
+}
+
+/// Moves a disallowed loss onto replacement shares bought within the wash sale window,
+/// splitting a lot when only part of it is needed. Shares left over from the acquisition that
+/// was sold, `sold_trade_id`, aren't replacements. Returns the loss that was disallowed.
+fn defer_wash_sale(lots: &mut Vec<TaxLot>, sold_trade_id: i32, sold: NaiveDate, quantity: f64, loss_per_share: f64, holding_days: i64) -> f64 {
+    let window_start = sold - Duration::days(WASH_SALE_DAYS);
+    let window_end = sold + Duration::days(WASH_SALE_DAYS);
+    let mut remaining = quantity;
+    let mut disallowed = 0.0;
+    let mut index = 0;
+    while index < lots.len() && remaining > f64::EPSILON {
+        let lot = &lots[index];
+        if lot.replaces_wash_sale || lot.trade_id == sold_trade_id || lot.quantity <= f64::EPSILON
+            || lot.acquired < window_start || lot.acquired > window_end {
+            index += 1;
+            continue;
+        }
+        if lot.quantity > remaining {
+            let mut rest = lot.clone();
+            rest.quantity -= remaining;
+            rest.cost_basis = lot.cost_basis * rest.quantity / lot.quantity;
+            rest.wash_sale_adjustment = lot.wash_sale_adjustment * rest.quantity / lot.quantity;
+            let lot = &mut lots[index];
+            lot.cost_basis -= rest.cost_basis;
+            lot.wash_sale_adjustment -= rest.wash_sale_adjustment;
+            lot.quantity = remaining;
+            lots.insert(index + 1, rest);
+        }
+        let lot = &mut lots[index];
+        let deferred = lot.quantity * loss_per_share;
+        lot.cost_basis += deferred;
+        lot.wash_sale_adjustment += deferred;
+        lot.holding_period_start = lot.acquired - Duration::days(holding_days);
+        lot.replaces_wash_sale = true;
+        remaining -= lot.quantity;
+        disallowed += deferred;
+        index += 1;
+    }
+    disallowed
+}
+
+/// Sells one ticker's lots first in, first out, returning every lot sale and the lots left
+/// open at the end of `until`
+fn track_ticker(ticker: &str, trades: &[&TradeModel], until: NaiveDate) -> (Vec<LotSale>, Vec<TaxLot>) {
+    // Every buy gets a lot up front so later purchases can take a wash sale adjustment
+    let mut lots: Vec<TaxLot> = trades.iter()
+        .filter(|trade| trade.trade_type == TradeType::Buy)
+        .map(|trade| TaxLot {
+            trade_id: trade.id,
+            ticker: ticker.to_string(),
+            acquired: trade.date,
+            holding_period_start: trade.date,
+            quantity: trade.amount as f64,
+            cost_basis: trade.amount as f64 * trade.price.to_f64().unwrap_or(0.0),
+            wash_sale_adjustment: 0.0,
+            replaces_wash_sale: false,
+        })
+        .collect();
+    let mut sales = Vec::new();
+    for trade in trades.iter().filter(|trade| trade.trade_type == TradeType::Sell && trade.date <= until) {
+        let price = trade.price.to_f64().unwrap_or(0.0);
+        let mut remaining = trade.amount as f64;
+        let mut sold_lots = Vec::new();
+        // A buy later the same day can't be sold by this trade
+        for lot in lots.iter_mut().filter(|lot| (lot.acquired, lot.trade_id) < (trade.date, trade.id)) {
+            if remaining <= f64::EPSILON {
+                break;
+            }
+            let quantity = remaining.min(lot.quantity);
+            if quantity <= f64::EPSILON {
+                continue;
+            }
+            let cost_basis = lot.cost_basis * quantity / lot.quantity;
+            lot.wash_sale_adjustment -= lot.wash_sale_adjustment * quantity / lot.quantity;
+            lot.cost_basis -= cost_basis;
+            lot.quantity -= quantity;
+            remaining -= quantity;
+            sold_lots.push((lot.trade_id, lot.acquired, lot.holding_period_start, quantity, cost_basis));
+        }
+        for (lot_trade_id, acquired, holding_period_start, quantity, cost_basis) in sold_lots {
+            let proceeds = quantity * price;
+            let gain = proceeds - cost_basis;
+            let wash_sale_disallowed = match gain < 0.0 {
+                true => {
+                    let holding_days = (trade.date - holding_period_start).num_days();
+                    defer_wash_sale(&mut lots, lot_trade_id, trade.date, quantity, -gain / quantity, holding_days)
+                },
+                false => 0.0,
+            };
+            sales.push(LotSale {
+                ticker: ticker.to_string(),
+                sell_trade_id: trade.id,
+                lot_trade_id,
+                acquired,
+                sold: trade.date,
+                quantity,
+                proceeds,
+                cost_basis,
+                gain,
+                term: holding_term(holding_period_start, trade.date),
+                wash_sale_disallowed,
+                reportable_gain: gain + wash_sale_disallowed,
+            });
+        }
+    }
+    let open_lots = lots.into_iter()
+        .filter(|lot| lot.quantity > f64::EPSILON && lot.acquired <= until)
+        .collect();
+    (sales, open_lots)
+}
+
+/// Builds the tax lot report for calendar `year` from the US trades. Lots are sold first in,
+/// first out and purchases after the year end still count towards wash sales in December.
+pub fn calculate(trades: &[TradeModel], year: i32) -> UsTaxLotReport {
+    let tax_year = TaxYear::calendar(year);
+    let us_trades = trades_for_country(trades, Country::US);
+    let mut by_ticker: HashMap<String, Vec<&TradeModel>> = HashMap::new();
+    for trade in sorted_trades(&us_trades) {
+        by_ticker.entry(trade.ticker.clone()).or_default().push(trade);
+    }
+    let tickers: BTreeSet<&String> = by_ticker.keys().collect();
+    let mut sales = Vec::new();
+    let mut open_lots = Vec::new();
+    for ticker in tickers {
+        let (ticker_sales, ticker_lots) = track_ticker(ticker, &by_ticker[ticker], tax_year.end);
+        sales.extend(ticker_sales.into_iter().filter(|sale| tax_year.contains(sale.sold)));
+        open_lots.extend(ticker_lots);
+    }
+    sales.sort_by_key(|sale| (sale.sold, sale.sell_trade_id));
+    let mut short_term = TermTotals::default();
+    let mut long_term = TermTotals::default();
+    for sale in &sales {
+        match sale.term {
+            HoldingTerm::ShortTerm => short_term.add(sale),
+            HoldingTerm::LongTerm => long_term.add(sale),
+        }
+    }
+    UsTaxLotReport {
+        tax_year,
+        sales,
+        short_term,
+        long_term,
+        open_lots,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytics::lots::tests::{assert_close, trade};
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn long_term_needs_more_than_a_year() {
+        assert_eq!(holding_term(date("2022-01-01"), date("2023-01-01")), HoldingTerm::ShortTerm);
+        assert_eq!(holding_term(date("2022-01-01"), date("2023-01-02")), HoldingTerm::LongTerm);
+        let trades = vec![
+            trade(1, Country::US, "2022-01-01", TradeType::Buy, 10, 10.0),
+            trade(2, Country::US, "2023-01-01", TradeType::Sell, 5, 20.0),
+            trade(3, Country::US, "2023-01-02", TradeType::Sell, 5, 20.0),
+        ];
+        let report = calculate(&trades, 2023);
+        assert_eq!(report.short_term.gain, 50.0);
+        assert_eq!(report.long_term.gain, 50.0);
+        assert!(report.open_lots.is_empty());
+    }
+
+    #[test]
+    fn buying_back_within_thirty_days_defers_the_loss() {
+        let trades = vec![
+            trade(1, Country::US, "2023-01-10", TradeType::Buy, 100, 10.0),
+            trade(2, Country::US, "2023-06-01", TradeType::Sell, 100, 8.0),
+            trade(3, Country::US, "2023-07-01", TradeType::Buy, 100, 9.0),
+        ];
+        let report = calculate(&trades, 2023);
+        assert_eq!(report.sales[0].gain, -200.0);
+        assert_eq!(report.sales[0].wash_sale_disallowed, 200.0);
+        assert_eq!(report.sales[0].reportable_gain, 0.0);
+        let lot = &report.open_lots[0];
+        assert_eq!(lot.cost_basis, 1100.0);
+        assert_eq!(lot.wash_sale_adjustment, 200.0);
+        // The 142 days the sold shares were held carry over
+        assert_eq!(lot.holding_period_start, date("2023-02-09"));
+    }
+
+    #[test]
+    fn buying_back_on_day_thirty_one_is_not_a_wash_sale() {
+        let trades = vec![
+            trade(1, Country::US, "2023-01-10", TradeType::Buy, 100, 10.0),
+            trade(2, Country::US, "2023-06-01", TradeType::Sell, 100, 8.0),
+            trade(3, Country::US, "2023-07-02", TradeType::Buy, 100, 9.0),
+        ];
+        let report = calculate(&trades, 2023);
+        assert_eq!(report.sales[0].wash_sale_disallowed, 0.0);
+        assert_eq!(report.short_term.gain, -200.0);
+        assert_eq!(report.open_lots[0].cost_basis, 900.0);
+    }
+
+    #[test]
+    fn a_larger_replacement_lot_is_split() {
+        let trades = vec![
+            trade(1, Country::US, "2023-01-10", TradeType::Buy, 100, 10.0),
+            trade(2, Country::US, "2023-06-01", TradeType::Sell, 100, 8.0),
+            trade(3, Country::US, "2023-06-15", TradeType::Buy, 150, 9.0),
+        ];
+        let report = calculate(&trades, 2023);
+        let lots: Vec<(f64, f64, f64)> = report.open_lots.iter()
+            .map(|lot| (lot.quantity, lot.cost_basis, lot.wash_sale_adjustment))
+            .collect();
+        assert_eq!(lots, vec![(100.0, 1100.0, 200.0), (50.0, 450.0, 0.0)]);
+        assert_eq!(report.open_lots[1].holding_period_start, date("2023-06-15"));
+    }
+
+    #[test]
+    fn buying_within_thirty_days_before_defers_the_loss() {
+        let trades = vec![
+            trade(1, Country::US, "2023-01-10", TradeType::Buy, 100, 10.0),
+            trade(2, Country::US, "2023-05-10", TradeType::Buy, 100, 8.0),
+            trade(3, Country::US, "2023-06-01", TradeType::Sell, 100, 7.0),
+        ];
+        let report = calculate(&trades, 2023);
+        assert_eq!(report.sales[0].lot_trade_id, 1);
+        assert_close(report.sales[0].wash_sale_disallowed, 300.0);
+        let lot = &report.open_lots[0];
+        assert_eq!(lot.trade_id, 2);
+        assert_close(lot.cost_basis, 1100.0);
+        assert_eq!(lot.holding_period_start, date("2022-12-19"));
+    }
+
+    #[test]
+    fn buys_in_january_still_wash_december_losses() {
+        let trades = vec![
+            trade(1, Country::US, "2023-06-01", TradeType::Buy, 10, 10.0),
+            trade(2, Country::US, "2023-12-20", TradeType::Sell, 10, 5.0),
+            trade(3, Country::US, "2024-01-10", TradeType::Buy, 10, 6.0),
+        ];
+        let report = calculate(&trades, 2023);
+        assert_eq!(report.sales[0].wash_sale_disallowed, 50.0);
+        // The replacement is bought after the year end so it isn't open yet
+        assert!(report.open_lots.is_empty());
+        assert_eq!(calculate(&trades, 2024).open_lots[0].cost_basis, 110.0);
+    }
+
+    #[test]
+    fn the_rest_of_the_sold_lot_is_not_a_replacement() {
+        let trades = vec![
+            trade(1, Country::US, "2023-05-20", TradeType::Buy, 100, 10.0),
+            trade(2, Country::US, "2023-06-01", TradeType::Sell, 50, 8.0),
+        ];
+        let report = calculate(&trades, 2023);
+        assert_eq!(report.sales[0].wash_sale_disallowed, 0.0);
+        assert_eq!(report.short_term.gain, -100.0);
+        assert_eq!(report.open_lots[0].cost_basis, 500.0);
+    }
+
+    #[test]
+    fn a_buy_later_the_same_day_is_not_sold() {
+        let trades = vec![
+            trade(1, Country::US, "2023-01-10", TradeType::Buy, 50, 10.0),
+            trade(2, Country::US, "2023-06-01", TradeType::Sell, 100, 8.0),
+            trade(3, Country::US, "2023-06-01", TradeType::Buy, 100, 9.0),
+        ];
+        let report = calculate(&trades, 2023);
+        assert_eq!(report.sales.len(), 1);
+        assert_eq!(report.sales[0].lot_trade_id, 1);
+        // The later buy replaces the shares sold at a loss instead
+        assert_eq!(report.sales[0].wash_sale_disallowed, 100.0);
+        let lots: Vec<(i32, f64, f64)> = report.open_lots.iter()
+            .map(|lot| (lot.trade_id, lot.quantity, lot.cost_basis))
+            .collect();
+        assert_eq!(lots, vec![(3, 50.0, 550.0), (3, 50.0, 450.0)]);
+    }
+}
@@ -4,12 +4,63 @@ pub mod trades;
 pub mod admin;
 pub mod quotes;
 pub mod modelling;
+pub mod lots;
+pub mod pnl;
+pub mod tax;
+pub mod dividends;
+pub mod corporate_actions;
+pub mod performance;
+pub mod benchmarks;
+pub mod risk;
+pub mod allocations;
 use std::sync::Arc;
 use axum::Router;
+use axum::Json;
+use axum::http::StatusCode;
+use serde_json::json;
 
 use crate::AppState;
 
 pub fn build_router() -> Router<Arc<AppState>> {
     let stocks = stocks::build_router();
-    Router::new().merge(stocks)
+    let portfolio = portfolio::build_router();
+    let lots = lots::build_router();
+    let pnl = pnl::build_router();
+    let tax = tax::build_router();
+    let dividends = dividends::build_router();
+    let corporate_actions = corporate_actions::build_router();
+    let quotes = quotes::build_router();
+    let performance = performance::build_router();
+    let benchmarks = benchmarks::build_router();
+    let risk = risk::build_router();
+    let allocations = allocations::build_router();
+    let modelling = modelling::build_router();
+    Router::new()
+        .merge(stocks)
+        .merge(portfolio)
+        .merge(lots)
+        .merge(pnl)
+        .merge(tax)
+        .merge(dividends)
+        .merge(corporate_actions)
+        .merge(quotes)
+        .merge(performance)
+        .merge(benchmarks)
+        .merge(risk)
+        .merge(allocations)
+        .merge(modelling)
+}
+
+pub fn internal_error<E>(e: E) -> (StatusCode, Json<serde_json::Value>)
+where
+    E: std::error::Error,
+{
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() })))
+}
+
+pub fn bad_request<E>(e: E) -> (StatusCode, Json<serde_json::Value>)
+where
+    E: std::error::Error,
+{
+    (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() })))
 }
\ No newline at end of file
@@ -0,0 +1,121 @@
+use crate::{
+    AppState,
+    analytics::fx::FxTable,
+    analytics::rebalance::{self, Position},
+    models::allocations::TargetAllocationModel,
+    models::fx::{Currency, FxRateModel},
+    models::quotes::QuoteModel,
+    models::stocks::StockModel,
+    schema::allocations::{AllocationQuery, DriftQuery, RebalanceQuery, TargetAllocationJson},
+    handlers::{internal_error, bad_request},
+};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use axum::Router;
+use axum::{routing::get, response::IntoResponse, http::StatusCode};
+use axum::Json;
+use axum::extract::{Query, State};
+use bigdecimal::ToPrimitive;
+use serde_json::json;
+
+/// Current holdings and targets of `portfolio` priced at the latest close in `base`. Tickers that
+/// are held without a target have a target of zero.
+async fn load_positions(portfolio: String, base: Currency, db_pool: &sqlx::PgPool) -> Result<Vec<Position>, sqlx::Error> {
+    let mut positions: BTreeMap<String, Position> = BTreeMap::new();
+    for stock in StockModel::get_all(db_pool).await? {
+        positions.insert(stock.ticker.clone(), Position {
+            ticker: stock.ticker,
+            quantity: stock.amount_held as i64,
+            price: None,
+            target: 0.0,
+        });
+    }
+    for target in TargetAllocationModel::get_by_portfolio(portfolio, db_pool).await? {
+        positions.entry(target.ticker.clone())
+            .or_insert(Position {
+                ticker: target.ticker,
+                quantity: 0,
+                price: None,
+                target: 0.0,
+            })
+            .target = target.weight.to_f64().unwrap_or(0.0);
+    }
+    let as_of = chrono::Utc::now().date_naive();
+    let fx = FxTable::from_rates(FxRateModel::get_by_currency(base, db_pool).await?);
+    for position in positions.values_mut() {
+        let quote = match QuoteModel::get_closest_date(position.ticker.clone(), as_of, db_pool).await {
+            Ok(quote) => quote,
+            Err(sqlx::Error::RowNotFound) => continue,
+            Err(e) => return Err(e),
+        };
+        position.price = fx.rate(quote.currency, base, quote.date)
+            .map(|rate| quote.close.to_f64().unwrap_or(0.0) * rate);
+    }
+    Ok(positions.into_values().collect())
+}
+
+pub async fn get_targets(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<AllocationQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let targets = TargetAllocationModel::get_by_portfolio(query.portfolio, &app_state.db_pool).await.map_err(internal_error)?;
+    let targets: Vec<TargetAllocationJson> = targets.into_iter().map(|target| target.into()).collect();
+    Ok(Json(json!(targets)))
+}
+
+/// Replaces the portfolio's targets with the ones given
+pub async fn set_targets(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<AllocationQuery>,
+    Json(targets): Json<Vec<TargetAllocationJson>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let weights: Vec<(String, f64)> = targets.iter().map(|target| (target.ticker.clone(), target.weight)).collect();
+    rebalance::validate_targets(&weights).map_err(bad_request)?;
+    let targets: Vec<TargetAllocationModel> = targets.into_iter().map(|target| target.into()).collect();
+    let targets = TargetAllocationModel::replace(query.portfolio, &targets, &app_state.db_pool).await.map_err(internal_error)?;
+    let targets: Vec<TargetAllocationJson> = targets.into_iter().map(|target| target.into()).collect();
+    Ok(Json(json!(targets)))
+}
+
+pub async fn delete_targets(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<AllocationQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = TargetAllocationModel::delete_by_portfolio(query.portfolio, &app_state.db_pool).await.map_err(internal_error)?;
+    Ok(Json(json!({ "deleted": result.rows_affected() })))
+}
+
+/// Current weights of the holdings and cash against the portfolio's targets
+pub async fn get_drift(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<DriftQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let cash = rebalance::validate_cash(query.cash).map_err(bad_request)?;
+    let positions = load_positions(query.portfolio.clone(), query.base, &app_state.db_pool).await.map_err(internal_error)?;
+    Ok(Json(json!({
+        "portfolio": query.portfolio,
+        "base": query.base,
+        "drift": rebalance::drift(&positions, cash),
+    })))
+}
+
+/// Proposed whole share orders to move the holdings towards their targets while investing `cash`
+pub async fn get_rebalance(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<RebalanceQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let cash = rebalance::validate_cash(query.cash).map_err(bad_request)?;
+    let positions = load_positions(query.portfolio.clone(), query.base, &app_state.db_pool).await.map_err(internal_error)?;
+    Ok(Json(json!({
+        "portfolio": query.portfolio,
+        "base": query.base,
+        "plan": rebalance::rebalance(&positions, cash, query.buy_only),
+    })))
+}
+
+pub fn build_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/allocations/targets", get(get_targets).put(set_targets).delete(delete_targets))
+        .route("/allocations/drift", get(get_drift))
+        .route("/allocations/rebalance", get(get_rebalance))
+}
\ No newline at end of file
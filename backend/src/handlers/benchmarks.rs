@@ -0,0 +1,112 @@
+use crate::{
+    AppState,
+    analytics::benchmark,
+    analytics::performance::growth_index,
+    analytics::pnl::Mark,
+    analytics::prices::PriceAdjustment,
+    models::benchmarks::BenchmarkModel,
+    models::quotes::QuoteModel,
+    models::stocks::valid_ticker,
+    schema::benchmarks::BenchmarkJson,
+    schema::performance::PerformanceQuery,
+    handlers::{internal_error, bad_request},
+    handlers::performance::load_history,
+};
+use std::sync::Arc;
+use axum::Router;
+use axum::{routing::{get, delete}, response::IntoResponse, http::StatusCode};
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use bigdecimal::ToPrimitive;
+use chrono::Duration;
+use serde_json::json;
+use tokio::spawn;
+
+pub async fn add_benchmark(
+    State(app_state): State<Arc<AppState>>,
+    Json(benchmark): Json<BenchmarkJson>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    valid_ticker(&benchmark.ticker, app_state.market_data.as_ref()).await.map_err(bad_request)?;
+    let benchmark: BenchmarkModel = benchmark.into();
+    let benchmark = benchmark.insert(&app_state.db_pool).await.map_err(internal_error)?;
+    let db_pool = app_state.db_pool.clone();
+    let market_data = app_state.market_data.clone();
+    let ticker = benchmark.ticker.clone();
+    spawn(async move {
+        let _ = QuoteModel::populate_ticker(ticker, market_data.as_ref(), &db_pool).await;
+    });
+    let benchmark: BenchmarkJson = benchmark.into();
+    Ok(Json(json!(benchmark)))
+}
+
+pub async fn get_benchmarks(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let benchmarks = BenchmarkModel::get_all(&app_state.db_pool).await.map_err(internal_error)?;
+    let benchmarks: Vec<BenchmarkJson> = benchmarks.into_iter().map(|benchmark| benchmark.into()).collect();
+    Ok(Json(json!(benchmarks)))
+}
+
+pub async fn delete_benchmark(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let benchmark = BenchmarkModel::delete_by_id(id, &app_state.db_pool).await.map_err(|e| match e {
+        sqlx::Error::RowNotFound => (StatusCode::NOT_FOUND, Json(json!({ "error": "Benchmark not found" }))),
+        e => internal_error(e),
+    })?;
+    let benchmark: BenchmarkJson = benchmark.into();
+    Ok(Json(json!(benchmark)))
+}
+
+/// Compares the portfolio's time weighted returns with each benchmark's total return over a
+/// period, or `start` to `end`. Benchmarks are converted into `base` first, and `missing_fx` is
+/// set when the portfolio or a benchmark close had no rate to convert with.
+pub async fn compare_benchmarks(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<PerformanceQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &app_state.db_pool;
+    let end = query.end.unwrap_or(chrono::Utc::now().date_naive());
+    let history = load_history(query.base, end, db_pool).await.map_err(internal_error)?;
+    let inception = history.inception().unwrap_or(end);
+    let start = match query.period {
+        Some(period) => period.start(end, inception),
+        None => query.start.unwrap_or(inception),
+    };
+    // A week before the start covers the benchmark's last close before the period
+    let lookback = start - Duration::days(7);
+    let (points, mut missing_fx) = history.daily_values(lookback, end, false);
+    let portfolio = growth_index(&points);
+    let mut comparisons = Vec::new();
+    for benchmark in BenchmarkModel::get_all(db_pool).await.map_err(internal_error)? {
+        let quotes = QuoteModel::get_date_range(benchmark.ticker.clone(), lookback, end, PriceAdjustment::TotalReturn, db_pool).await.map_err(internal_error)?;
+        // Closes without a rate are skipped rather than priced at par
+        let mut closes = Vec::new();
+        for quote in &quotes {
+            match history.fx.rate(quote.currency, query.base, quote.date) {
+                Some(rate) => closes.push(Mark {
+                    date: quote.date,
+                    price: quote.close.to_f64().unwrap_or(0.0) * rate,
+                }),
+                None => missing_fx = true,
+            }
+        }
+        comparisons.push(benchmark::compare(&benchmark.ticker, benchmark.name, &portfolio, &closes, start, end));
+    }
+    Ok(Json(json!({
+        "period": query.period,
+        "start": start,
+        "end": end,
+        "base": query.base,
+        "missing_fx": missing_fx,
+        "benchmarks": comparisons,
+    })))
+}
+
+pub fn build_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/benchmarks", get(get_benchmarks).post(add_benchmark))
+        .route("/benchmarks/compare", get(compare_benchmarks))
+        .route("/benchmarks/:id", delete(delete_benchmark))
+}
\ No newline at end of file
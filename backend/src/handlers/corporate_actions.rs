@@ -0,0 +1,101 @@
+use crate::{
+    AppState,
+    models::corporate_actions::{CorporateActionError, CorporateActionModel},
+    schema::corporate_actions::{CorporateActionJson, CorporateActionResultJson},
+    handlers::{internal_error, bad_request},
+};
+use std::sync::Arc;
+use axum::Router;
+use axum::{routing::{get, post}, response::IntoResponse, http::StatusCode};
+use axum::Json;
+use axum::extract::{Path, State};
+use serde_json::json;
+
+fn not_found(e: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    match e {
+        sqlx::Error::RowNotFound => (StatusCode::NOT_FOUND, Json(json!({ "error": "Corporate action not found" }))),
+        e => internal_error(e),
+    }
+}
+
+fn action_error(e: CorporateActionError) -> (StatusCode, Json<serde_json::Value>) {
+    match e {
+        CorporateActionError::Invalid(_) => bad_request(e),
+        CorporateActionError::Database(e) => internal_error(e),
+        e => (StatusCode::CONFLICT, Json(json!({ "error": e.to_string() }))),
+    }
+}
+
+pub async fn add_corporate_action(
+    State(app_state): State<Arc<AppState>>,
+    Json(action): Json<CorporateActionJson>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let action: CorporateActionModel = action.into();
+    action.validate().map_err(action_error)?;
+    let action = action.insert(&app_state.db_pool).await.map_err(internal_error)?;
+    let action: CorporateActionJson = action.into();
+    Ok(Json(json!(action)))
+}
+
+pub async fn get_corporate_actions(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let actions = CorporateActionModel::get_all(&app_state.db_pool).await.map_err(internal_error)?;
+    let actions: Vec<CorporateActionJson> = actions.into_iter().map(|action| action.into()).collect();
+    Ok(Json(json!(actions)))
+}
+
+pub async fn get_corporate_action(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let action = CorporateActionModel::get_by_id(id, &app_state.db_pool).await.map_err(not_found)?;
+    let action: CorporateActionJson = action.into();
+    Ok(Json(json!(action)))
+}
+
+/// Applied actions have to be undone before they can be deleted
+pub async fn delete_corporate_action(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let action = CorporateActionModel::get_by_id(id, &app_state.db_pool).await.map_err(not_found)?;
+    if action.applied_at.is_some() {
+        return Err(action_error(CorporateActionError::AlreadyApplied(id)));
+    }
+    let action = CorporateActionModel::delete_by_id(id, &app_state.db_pool).await.map_err(not_found)?;
+    let action: CorporateActionJson = action.into();
+    Ok(Json(json!(action)))
+}
+
+pub async fn apply_corporate_action(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let action = CorporateActionModel::get_by_id(id, &app_state.db_pool).await.map_err(not_found)?;
+    let (action, adjustments) = action.apply(&app_state.db_pool).await.map_err(action_error)?;
+    Ok(Json(json!(CorporateActionResultJson {
+        action: action.into(),
+        adjustments,
+    })))
+}
+
+pub async fn undo_corporate_action(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let action = CorporateActionModel::get_by_id(id, &app_state.db_pool).await.map_err(not_found)?;
+    let (action, adjustments) = action.undo(&app_state.db_pool).await.map_err(action_error)?;
+    Ok(Json(json!(CorporateActionResultJson {
+        action: action.into(),
+        adjustments,
+    })))
+}
+
+pub fn build_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/corporate-actions", get(get_corporate_actions).post(add_corporate_action))
+        .route("/corporate-actions/:id", get(get_corporate_action).delete(delete_corporate_action))
+        .route("/corporate-actions/:id/apply", post(apply_corporate_action))
+        .route("/corporate-actions/:id/undo", post(undo_corporate_action))
+}
\ No newline at end of file
@@ -0,0 +1,106 @@
+use crate::{
+    AppState,
+    analytics::fx::FxTable,
+    analytics::income,
+    models::dividends::DividendModel,
+    models::fx::FxRateModel,
+    models::quotes::QuoteModel,
+    models::trades::TradeModel,
+    schema::dividends::{DividendJson, IncomeQuery},
+    handlers::{internal_error, bad_request},
+};
+use std::sync::Arc;
+use axum::Router;
+use axum::{routing::get, response::IntoResponse, http::StatusCode};
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use chrono::NaiveDate;
+use serde_json::json;
+
+/// Lookups by id answer 404 rather than 500 when the row doesn't exist
+fn dividend_error(e: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    match e {
+        sqlx::Error::RowNotFound => (StatusCode::NOT_FOUND, Json(json!({ "error": "Dividend not found" }))),
+        e => internal_error(e),
+    }
+}
+
+pub async fn add_dividend(
+    State(app_state): State<Arc<AppState>>,
+    Json(dividend): Json<DividendJson>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    dividend.validate().map_err(bad_request)?;
+    let dividend: DividendModel = dividend.into();
+    let dividend = dividend.insert(&app_state.db_pool).await.map_err(internal_error)?;
+    QuoteModel::refresh_adjusted_close(dividend.ticker.clone(), &app_state.db_pool).await.map_err(internal_error)?;
+    let dividend: DividendJson = dividend.into();
+    Ok(Json(json!(dividend)))
+}
+
+pub async fn get_dividends(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let dividends = DividendModel::get_all(&app_state.db_pool).await.map_err(internal_error)?;
+    let dividends: Vec<DividendJson> = dividends.into_iter().map(|dividend| dividend.into()).collect();
+    Ok(Json(json!(dividends)))
+}
+
+pub async fn get_dividend(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let dividend = DividendModel::get_by_id(id, &app_state.db_pool).await.map_err(dividend_error)?;
+    let dividend: DividendJson = dividend.into();
+    Ok(Json(json!(dividend)))
+}
+
+pub async fn update_dividend(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(dividend): Json<DividendJson>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    dividend.validate().map_err(bad_request)?;
+    let previous = DividendModel::get_by_id(id, &app_state.db_pool).await.map_err(dividend_error)?;
+    let mut dividend: DividendModel = dividend.into();
+    dividend.id = id;
+    let dividend = dividend.update(&app_state.db_pool).await.map_err(dividend_error)?;
+    QuoteModel::refresh_adjusted_close(dividend.ticker.clone(), &app_state.db_pool).await.map_err(internal_error)?;
+    if previous.ticker != dividend.ticker {
+        QuoteModel::refresh_adjusted_close(previous.ticker, &app_state.db_pool).await.map_err(internal_error)?;
+    }
+    let dividend: DividendJson = dividend.into();
+    Ok(Json(json!(dividend)))
+}
+
+pub async fn delete_dividend(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let dividend = DividendModel::delete_by_id(id, &app_state.db_pool).await.map_err(dividend_error)?;
+    QuoteModel::refresh_adjusted_close(dividend.ticker.clone(), &app_state.db_pool).await.map_err(internal_error)?;
+    let dividend: DividendJson = dividend.into();
+    Ok(Json(json!(dividend)))
+}
+
+/// Entitlements to dividends paid between `start` and `end`, worked out from the shares held
+/// the day before each ex-date. Defaults to everything paid up to today.
+pub async fn get_income(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<IncomeQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &app_state.db_pool;
+    let start = query.start.unwrap_or(NaiveDate::from_ymd_opt(2000, 1, 1).unwrap());
+    let end = query.end.unwrap_or(chrono::Utc::now().date_naive());
+    let dividends = DividendModel::get_pay_date_range(start, end, db_pool).await.map_err(internal_error)?;
+    let trades = TradeModel::get_all(db_pool).await.map_err(internal_error)?;
+    let fx = FxTable::from_rates(FxRateModel::get_by_currency(query.base, db_pool).await.map_err(internal_error)?);
+    let entitlements = income::entitlements(&dividends, &trades);
+    Ok(Json(json!(income::summarise(entitlements, &fx, query.base, start, end))))
+}
+
+pub fn build_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/dividends", get(get_dividends).post(add_dividend))
+        .route("/dividends/income", get(get_income))
+        .route("/dividends/:id", get(get_dividend).put(update_dividend).delete(delete_dividend))
+}
\ No newline at end of file
@@ -0,0 +1,61 @@
+use crate::{
+    AppState,
+    analytics::lots::LotEngine,
+    models::trades::TradeModel,
+    schema::lots::{LotQuery, HoldingLotsJson, DisposalsJson},
+    handlers::internal_error,
+};
+use std::sync::Arc;
+use axum::Router;
+use axum::{routing::get, response::IntoResponse, http::StatusCode};
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use serde_json::json;
+
+pub async fn get_lots(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<LotQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let trades = TradeModel::get_all(&app_state.db_pool).await.map_err(internal_error)?;
+    let engine = LotEngine::replay(query.method, &trades);
+    let holdings: Vec<HoldingLotsJson> = engine.tickers()
+        .iter()
+        .map(|ticker| HoldingLotsJson::from_engine(&engine, ticker))
+        .collect();
+    Ok(Json(json!(holdings)))
+}
+
+pub async fn get_lots_by_ticker(
+    State(app_state): State<Arc<AppState>>,
+    Path(ticker): Path<String>,
+    Query(query): Query<LotQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let trades = TradeModel::get_by_ticker(ticker.clone(), &app_state.db_pool).await.map_err(internal_error)?;
+    let engine = LotEngine::replay(query.method, &trades);
+    Ok(Json(json!(HoldingLotsJson::from_engine(&engine, &ticker))))
+}
+
+pub async fn get_disposals(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<LotQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let trades = match query.ticker {
+        Some(ticker) => TradeModel::get_by_ticker(ticker, &app_state.db_pool).await,
+        None => TradeModel::get_all(&app_state.db_pool).await,
+    }.map_err(internal_error)?;
+    let engine = LotEngine::replay(query.method, &trades);
+    let disposals = engine.disposals().to_vec();
+    let disposals = DisposalsJson {
+        method: query.method,
+        realised_gain: disposals.iter().fold(0.0, |total, disposal| total + disposal.gain),
+        disposals,
+    };
+    Ok(Json(json!(disposals)))
+}
+
+pub fn build_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/lots", get(get_lots))
+        .route("/lots/:ticker", get(get_lots_by_ticker))
+        .route("/disposals", get(get_disposals))
+}
\ No newline at end of file
@@ -1,8 +1,37 @@
 use std::collections::HashMap;
-use rand::thread_rng;
-use rand::seq::SliceRandom;
-use chrono::{DateTime, Utc, Duration};
-use ndarray::Array3;
+use std::sync::Arc;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+use ndarray::{Array2, Array3};
+use serde::{Deserialize, Serialize};
+use axum::Router;
+use axum::{routing::{get, post}, response::IntoResponse, http::StatusCode};
+use axum::Json;
+use axum::extract::{Path, Query, State as AxumState};
+use serde_json::json;
+use bigdecimal::ToPrimitive;
+use crate::{
+    AppState,
+    analytics::correlation::aligned_returns,
+    analytics::fire::{self, FireSummary, PathOutcome},
+    analytics::fx::FxTable,
+    analytics::markov::{self, MarkovFit},
+    analytics::rebalance,
+    analytics::pnl::Mark,
+    analytics::returns::{mean, simple_returns, standard_deviation, TRADING_DAYS_PER_YEAR},
+    models::fx::{Currency, FxRateModel},
+    models::markov::MarkovFitModel,
+    models::quotes::QuoteModel,
+    models::scenarios::{ScenarioModel, ScenarioResultModel},
+    models::stocks::StockModel,
+    schema::modelling::{
+        CompareQuery, CriteriaConfig, FactorConfig, FireRequest, MarkovFitJson, MarkovFitRequest, PriceModelConfig,
+        ScenarioConfig, ScenarioJson, ScenarioResultJson, StepSize,
+    },
+    handlers::{internal_error, bad_request},
+    handlers::risk::ticker_marks,
+};
 
 struct Model {
     id: i32,
@@ -17,14 +46,17 @@ struct Model {
 }
 impl Model {
     fn validate(&self) -> Result<(), ModelError> {
+        if self.parameters.end < self.parameters.start {
+            return Err(ModelError::Invalid("The simulation must end after it starts".to_string()));
+        }
         Ok(())
     }
-    fn update(&mut self) -> Result<State, ModelError> {
-        self.model_type.update(&mut self.assets);
+    fn update(&mut self, clock: &Clock) -> Result<State, ModelError> {
+        self.model_type.update(clock, &mut self.assets);
         for factor in &mut self.factors {
-            factor.update(&mut self.assets);
+            factor.update(clock, &mut self.assets);
         }
-        Ok(self.criteria.evaluate(&mut self.assets))
+        Ok(self.criteria.evaluate(clock, &mut self.assets))
     }
     fn calculate_results(&mut self) -> f64 {
         let mut total = 0.0;
@@ -33,44 +65,153 @@ impl Model {
         }
         total
     }
-    pub fn run(&mut self) {
-        let start_time = Utc::now();
-        let mut current_time = Utc::now();
-        let mut current_iteration = 0;
-        while current_time - start_time < self.parameters.max_time && current_iteration < self.parameters.max_iterations {
-            match self.update() {
-                Ok(state) => {
-                    match state {
-                        State::Running => {
-                            println!("Model is running");
-                        },
-                        State::Complete => {
-                            println!("Model is complete");
-                            break;
-                        },
-                        State::Failed => {
-                            println!("Model has failed due to criteria");
-                            break;
-                        }
-                    }
-                },
-                Err(e) => {
-                    println!("Model has failed due to error: {:?}", e);
-                    break;
-                }
+    fn snapshot(&mut self, clock: &Clock, state: State) -> Snapshot {
+        let assets: Vec<AssetSnapshot> = self.assets.iter_mut()
+            .map(|asset| AssetSnapshot {
+                ticker: asset.ticker.clone(),
+                amount_held: asset.amount_held,
+                price: asset.latest_price(),
+                value: asset.calculate_value(),
+            })
+            .collect();
+        Snapshot {
+            step: clock.steps,
+            date: clock.current,
+            total_value: assets.iter().fold(0.0, |total, asset| total + asset.value),
+            assets,
+            state,
+        }
+    }
+    /// Steps the simulated clock from `parameters.start` until the criteria are met or fail, the
+    /// clock passes `parameters.end` or `parameters.max_iterations` steps have run. Without
+    /// `parameters.stop_when_complete` meeting the criteria only records when it happened. The
+    /// first snapshot is the starting position, then there is one per step.
+    pub fn run(&mut self) -> Result<Trajectory, ModelError> {
+        self.validate()?;
+        let mut clock = Clock::new(self.parameters.start, self.parameters.step);
+        let mut snapshots = vec![self.snapshot(&clock, State::Running)];
+        let mut state = State::Running;
+        let mut completed = None;
+        while clock.steps < self.parameters.max_iterations {
+            clock.tick();
+            if clock.current > self.parameters.end {
+                break;
+            }
+            state = self.update(&clock)?;
+            snapshots.push(self.snapshot(&clock, state));
+            if state == State::Complete && completed.is_none() {
+                completed = Some(clock.current);
+            }
+            let finished = match state {
+                State::Running => false,
+                State::Complete => self.parameters.stop_when_complete,
+                State::Failed => true,
+            };
+            if finished {
+                break;
             }
-            current_time = Utc::now();
-            current_iteration += 1;
         }
-    } 
+        Ok(Trajectory {
+            start: self.parameters.start,
+            end: clock.current.min(self.parameters.end),
+            step: self.parameters.step,
+            state,
+            completed,
+            snapshots,
+        })
+    }
 }
 struct Parameters {
     max_iterations: i32,
-    max_time: Duration,
+    start: NaiveDate,
+    end: NaiveDate,
+    step: StepSize,
+    stop_when_complete: bool,
+}
+
+/// Average calendar days in a year, leap years included
+const DAYS_PER_YEAR: f64 = 365.25;
+
+/// Simulated time, so a run covers years in however long it takes to compute
+struct Clock {
+    start: NaiveDate,
+    current: NaiveDate,
+    /// Date of the step before the current one
+    previous: NaiveDate,
+    step: StepSize,
+    steps: i32,
+}
+impl Clock {
+    fn new(start: NaiveDate, step: StepSize) -> Self {
+        Self {
+            start,
+            current: start,
+            previous: start,
+            step,
+            steps: 0,
+        }
+    }
+    fn tick(&mut self) {
+        self.previous = self.current;
+        self.steps += 1;
+        self.current = self.step.advance(self.start, self.steps);
+    }
+    /// Fraction of a year the current step covered, from the calendar days it spanned
+    fn step_years(&self) -> f64 {
+        (self.current - self.previous).num_days() as f64 / DAYS_PER_YEAR
+    }
+    /// Whether the current step moved into a new calendar month
+    fn new_month(&self) -> bool {
+        (self.current.year(), self.current.month()) != (self.previous.year(), self.previous.month())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct AssetSnapshot {
+    ticker: String,
+    amount_held: i64,
+    price: f64,
+    value: f64,
+}
+
+/// Holdings at the end of one simulated step
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Snapshot {
+    step: i32,
+    date: NaiveDate,
+    total_value: f64,
+    assets: Vec<AssetSnapshot>,
+    state: State,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Trajectory {
+    start: NaiveDate,
+    end: NaiveDate,
+    step: StepSize,
+    state: State,
+    /// First date the criteria were met
+    completed: Option<NaiveDate>,
+    snapshots: Vec<Snapshot>,
+}
+
+impl Trajectory {
+    /// Total value at the start and at the end of each whole year simulated
+    fn yearly_values(&self) -> Vec<f64> {
+        let mut values = Vec::new();
+        let mut year = 0;
+        while self.start + Months::new(12 * year) <= self.end {
+            let date = self.start + Months::new(12 * year);
+            let index = self.snapshots.partition_point(|snapshot| snapshot.date <= date);
+            values.push(self.snapshots[index.max(1) - 1].total_value);
+            year += 1;
+        }
+        values
+    }
 }
 struct Asset {
     ticker: String,
-    amount_held: i32,
+    amount_held: i64,
     price_history: Option<Vec<f64>>,
 }
 impl Asset {
@@ -92,6 +233,8 @@ impl Asset {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
 enum State {
     Running,
     Complete,
@@ -99,27 +242,42 @@ enum State {
 }
 #[derive(Debug)]
 enum ModelError {
+    Invalid(String),
+}
 
+impl std::fmt::Display for ModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
 }
 
+impl std::error::Error for ModelError {}
+
 trait InvestmentFactors {
-    fn update(&mut self, assets: &mut Vec<Asset>);
+    fn update(&mut self, clock: &Clock, assets: &mut Vec<Asset>);
 }
+/// Moves asset prices forward by one step of `clock`
 trait InvestmentModel {
-    fn update(&mut self, assets: &mut Vec<Asset>);
+    fn update(&mut self, clock: &Clock, assets: &mut Vec<Asset>);
 }
 
 trait Criteria {
-    fn evaluate(&mut self, assets: &mut Vec<Asset>) -> State;
+    fn evaluate(&mut self, clock: &Clock, assets: &mut Vec<Asset>) -> State;
     fn update_inflation(&mut self, inflation: f64);
 }
 
+/// Complete once `fire_rate` of the portfolio covers a year's `expenses`, which grow by the annual
+/// `inflation` rate as the clock moves
 struct BasicFIRECriteria {
     fire_rate: f64,
-    expenses: f64
+    expenses: f64,
+    inflation: f64,
 }
 impl Criteria for BasicFIRECriteria {
-    fn evaluate(&mut self, assets: &mut Vec<Asset>) -> State {
+    fn evaluate(&mut self, clock: &Clock, assets: &mut Vec<Asset>) -> State {
+        self.update_inflation((1.0 + self.inflation).powf(clock.step_years()) - 1.0);
         let total_value = assets.into_iter().fold(0.0, |acc, asset| acc + asset.calculate_value());
         match total_value * self.fire_rate > self.expenses {
             true => State::Complete,
@@ -134,42 +292,1089 @@ impl Criteria for BasicFIRECriteria {
 struct MonthlyInvestmentFactor {
     leftover: f64,
     monthly_investment: f64,
-    last_investment: DateTime<Utc>,
+    last_investment: Option<NaiveDate>,
     desired_allocation: HashMap<String, f64>,
 }
 
 impl InvestmentFactors for MonthlyInvestmentFactor {
-    fn update(&mut self, assets: &mut Vec<Asset>) {
+    /// Invests once in each simulated month, on the first step that lands in it
+    fn update(&mut self, clock: &Clock, assets: &mut Vec<Asset>) {
+        if self.last_investment.is_some() && !clock.new_month() {
+            return;
+        }
+        self.last_investment = Some(clock.current);
         let to_spend = self.monthly_investment + self.leftover;
         let mut spent = 0.0;
-        let rng = &mut thread_rng();
-        assets.shuffle(rng); // Shuffle the assets to avoid bias in the order of buying
+        // Tickers without an allocation get no new money
         for asset in assets {
-            let allocation = self.desired_allocation.get(&asset.ticker);
-            match allocation {
-                Some(allocation) => {
-                    let price = asset.latest_price();
-                    let amount_to_spend = to_spend * allocation;
-                    let amount_to_buy = (amount_to_spend / price) as i32;
-                    spent += amount_to_buy as f64 * price;
-                    asset.amount_held += amount_to_buy;
-                },
-                None => {
-                    println!("No allocation for asset {}", asset.ticker);
-                }
+            if let Some(allocation) = self.desired_allocation.get(&asset.ticker) {
+                let price = asset.latest_price();
+                let amount_to_spend = to_spend * allocation;
+                let amount_to_buy = match (amount_to_spend / price).floor().to_i64() {
+                    Some(amount) if price > 0.0 && amount > 0 => amount,
+                    _ => continue,
+                };
+                let amount_held = asset.amount_held.saturating_add(amount_to_buy);
+                spent += (amount_held - asset.amount_held) as f64 * price;
+                asset.amount_held = amount_held;
             }
         }
         self.leftover = to_spend - spent;
     }
 }
 
+/// Steps prices through a fitted chain of daily return buckets, one chain per ticker. Seeded so a
+/// run with the same fit and seed gives the same prices.
 struct MarkovChainModel {
+    tickers: Vec<String>,
     transition_matrix: Array3<f64>,
-    current_state: usize,
+    bucket_returns: Array2<f64>,
+    current_states: Vec<usize>,
+    rng: StdRng,
+}
+
+impl MarkovChainModel {
+    fn new(fit: MarkovFit, seed: u64) -> Self {
+        Self {
+            tickers: fit.tickers,
+            transition_matrix: fit.transitions,
+            bucket_returns: fit.bucket_returns,
+            current_states: fit.initial_states,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+    fn next_state(&mut self, ticker: usize) -> usize {
+        let from = self.current_states[ticker];
+        let draw: f64 = self.rng.gen();
+        let row = self.transition_matrix.slice(ndarray::s![ticker, from, ..]);
+        let mut cumulative = 0.0;
+        let next = row.iter()
+            .position(|probability| {
+                cumulative += probability;
+                draw < cumulative
+            })
+            .unwrap_or(row.len() - 1);
+        self.current_states[ticker] = next;
+        next
+    }
 }
 
 impl InvestmentModel for MarkovChainModel {
-    fn update(&mut self, assets: &mut Vec<Asset>) {
-        
+    /// Walks each fitted ticker through the trading days in one step and appends the closing price.
+    /// Tickers are stepped in the fitted order so the draws don't depend on the order of the assets.
+    fn update(&mut self, clock: &Clock, assets: &mut Vec<Asset>) {
+        let days = trading_days(clock);
+        for ticker in 0..self.tickers.len() {
+            let asset = match assets.iter_mut().find(|asset| asset.ticker == self.tickers[ticker]) {
+                Some(asset) => asset,
+                None => continue,
+            };
+            let mut price = match asset.price_history.as_ref().and_then(|history| history.last()) {
+                Some(price) => *price,
+                None => continue,
+            };
+            for _ in 0..days {
+                let state = self.next_state(ticker);
+                price *= 1.0 + self.bucket_returns[[ticker, state]];
+            }
+            if let Some(history) = asset.price_history.as_mut() {
+                history.push(price);
+            }
+        }
+    }
+}
+
+/// Trading days one step of `clock` covers. Counted from the start of the run so the fractions
+/// left over each step add up, a short step can cover none and a year covers
+/// `TRADING_DAYS_PER_YEAR` whatever the step size.
+fn trading_days(clock: &Clock) -> usize {
+    let elapsed = |date: NaiveDate| (TRADING_DAYS_PER_YEAR * (date - clock.start).num_days() as f64 / DAYS_PER_YEAR).floor() as usize;
+    elapsed(clock.current) - elapsed(clock.previous)
+}
+
+/// Standard normal draw using the Box-Muller transform
+fn standard_normal(rng: &mut StdRng) -> f64 {
+    let uniform: f64 = 1.0 - rng.gen::<f64>();
+    let angle: f64 = rng.gen::<f64>() * 2.0 * std::f64::consts::PI;
+    (-2.0 * uniform.ln()).sqrt() * angle.cos()
+}
+
+/// Geometric Brownian motion with annual drift and volatility per ticker. Tickers move
+/// independently of each other.
+struct GeometricBrownianMotionModel {
+    tickers: Vec<String>,
+    drift: Vec<f64>,
+    volatility: Vec<f64>,
+    rng: StdRng,
+}
+
+impl GeometricBrownianMotionModel {
+    /// Annual drift and volatility from the daily log returns of `marks`
+    fn estimate(marks: &[Mark]) -> Option<(f64, f64)> {
+        let log_returns: Vec<f64> = simple_returns(marks).iter().map(|value| value.ln_1p()).collect();
+        let volatility = standard_deviation(&log_returns)? * TRADING_DAYS_PER_YEAR.sqrt();
+        let drift = mean(&log_returns)? * TRADING_DAYS_PER_YEAR + volatility.powi(2) / 2.0;
+        Some((drift, volatility))
+    }
+}
+
+impl InvestmentModel for GeometricBrownianMotionModel {
+    fn update(&mut self, clock: &Clock, assets: &mut Vec<Asset>) {
+        let years = clock.step_years();
+        for ticker in 0..self.tickers.len() {
+            let asset = match assets.iter_mut().find(|asset| asset.ticker == self.tickers[ticker]) {
+                Some(asset) => asset,
+                None => continue,
+            };
+            let draw = standard_normal(&mut self.rng);
+            if let Some(history) = asset.price_history.as_mut() {
+                let price = match history.last() {
+                    Some(price) => *price,
+                    None => continue,
+                };
+                let (drift, volatility) = (self.drift[ticker], self.volatility[ticker]);
+                history.push(price * ((drift - volatility.powi(2) / 2.0) * years + volatility * years.sqrt() * draw).exp());
+            }
+        }
+    }
+}
+
+/// Replays blocks of consecutive historical days, every ticker on the same day together so the
+/// correlation between them is kept
+struct BlockBootstrapModel {
+    tickers: Vec<String>,
+    /// Daily returns, one row per day and one column per ticker
+    returns: Array2<f64>,
+    block_days: usize,
+    /// Next row to replay and how many rows are left in the current block
+    row: usize,
+    remaining: usize,
+    rng: StdRng,
+}
+
+impl InvestmentModel for BlockBootstrapModel {
+    fn update(&mut self, clock: &Clock, assets: &mut Vec<Asset>) {
+        let mut growth = vec![1.0; self.tickers.len()];
+        for _ in 0..trading_days(clock) {
+            if self.remaining == 0 {
+                self.row = self.rng.gen_range(0..=self.returns.nrows() - self.block_days);
+                self.remaining = self.block_days;
+            }
+            for (ticker, value) in self.returns.row(self.row).iter().enumerate() {
+                growth[ticker] *= 1.0 + value;
+            }
+            self.row += 1;
+            self.remaining -= 1;
+        }
+        for (ticker, growth) in self.tickers.iter().zip(growth) {
+            let history = assets.iter_mut()
+                .find(|asset| asset.ticker == *ticker)
+                .and_then(|asset| asset.price_history.as_mut());
+            if let Some(history) = history {
+                if let Some(price) = history.last() {
+                    history.push(price * growth);
+                }
+            }
+        }
+    }
+}
+
+/// Every asset grows at the same annual rate
+struct FixedReturnModel {
+    annual_return: f64,
+}
+
+impl InvestmentModel for FixedReturnModel {
+    fn update(&mut self, clock: &Clock, assets: &mut Vec<Asset>) {
+        let growth = (1.0 + self.annual_return).powf(clock.step_years());
+        for asset in assets {
+            if let Some(history) = asset.price_history.as_mut() {
+                if let Some(price) = history.last() {
+                    history.push(price * growth);
+                }
+            }
+        }
+    }
+}
+
+/// A price model with its parameters estimated, ready to be built once per seeded path
+#[derive(Clone)]
+enum FittedModel {
+    Markov(MarkovFit),
+    Gbm {
+        tickers: Vec<String>,
+        drift: Vec<f64>,
+        volatility: Vec<f64>,
+    },
+    Bootstrap {
+        tickers: Vec<String>,
+        returns: Array2<f64>,
+        block_days: usize,
+    },
+    Fixed {
+        annual_return: f64,
+    },
+}
+
+impl FittedModel {
+    fn build(&self, seed: u64) -> Box<dyn InvestmentModel> {
+        match self {
+            FittedModel::Markov(fit) => Box::new(MarkovChainModel::new(fit.clone(), seed)),
+            FittedModel::Gbm { tickers, drift, volatility } => Box::new(GeometricBrownianMotionModel {
+                tickers: tickers.clone(),
+                drift: drift.clone(),
+                volatility: volatility.clone(),
+                rng: StdRng::seed_from_u64(seed),
+            }),
+            FittedModel::Bootstrap { tickers, returns, block_days } => Box::new(BlockBootstrapModel {
+                tickers: tickers.clone(),
+                returns: returns.clone(),
+                block_days: *block_days,
+                row: 0,
+                remaining: 0,
+                rng: StdRng::seed_from_u64(seed),
+            }),
+            FittedModel::Fixed { annual_return } => Box::new(FixedReturnModel {
+                annual_return: *annual_return,
+            }),
+        }
+    }
+}
+
+/// Years of quotes price models are estimated from
+const HISTORY_YEARS: u32 = 5;
+
+async fn load_series(tickers: &[String], start: NaiveDate, end: NaiveDate, db_pool: &sqlx::PgPool) -> Result<Vec<(String, Vec<Mark>)>, sqlx::Error> {
+    let mut series = Vec::new();
+    for ticker in tickers {
+        series.push((ticker.clone(), ticker_marks(ticker.clone(), start, end, db_pool).await?));
+    }
+    Ok(series)
+}
+
+/// Estimates the chosen price model for `tickers` from their total return closes over the
+/// `HISTORY_YEARS` before `end`, or loads a stored Markov model
+async fn fit_price_model(config: &PriceModelConfig, tickers: &[String], end: NaiveDate, db_pool: &sqlx::PgPool) -> Result<FittedModel, (StatusCode, Json<serde_json::Value>)> {
+    let invalid = |message: String| bad_request(ModelError::Invalid(message));
+    let start = end - Months::new(12 * HISTORY_YEARS);
+    match config {
+        PriceModelConfig::Markov { markov_model_id: Some(id), .. } => {
+            let model = MarkovFitModel::get_by_id(*id, db_pool).await.map_err(not_found)?;
+            let fit = MarkovFit::from_model(model).map_err(internal_error)?;
+            let missing: Vec<String> = tickers.iter().filter(|ticker| !fit.tickers.contains(ticker)).cloned().collect();
+            match missing.is_empty() {
+                true => Ok(FittedModel::Markov(fit)),
+                false => Err(invalid(format!("Markov model {} was not fitted to {}", id, missing.join(", ")))),
+            }
+        },
+        PriceModelConfig::Markov { markov_model_id: None, buckets } => {
+            let series = load_series(tickers, start, end, db_pool).await.map_err(internal_error)?;
+            markov::fit(&series, *buckets, start, end).map(FittedModel::Markov).map_err(bad_request)
+        },
+        PriceModelConfig::Gbm => {
+            let series = load_series(tickers, start, end, db_pool).await.map_err(internal_error)?;
+            let mut drift = Vec::new();
+            let mut volatility = Vec::new();
+            for (ticker, marks) in &series {
+                let (ticker_drift, ticker_volatility) = GeometricBrownianMotionModel::estimate(marks)
+                    .ok_or(invalid(format!("{} doesn't have enough quotes to estimate", ticker)))?;
+                drift.push(ticker_drift);
+                volatility.push(ticker_volatility);
+            }
+            Ok(FittedModel::Gbm {
+                tickers: tickers.to_vec(),
+                drift,
+                volatility,
+            })
+        },
+        PriceModelConfig::Bootstrap { block_days } => {
+            let series = load_series(tickers, start, end, db_pool).await.map_err(internal_error)?;
+            let days = series.iter().map(|(_, marks)| marks.len()).max().unwrap_or(0);
+            let (_, returns) = aligned_returns(&series, days);
+            if *block_days == 0 || returns.nrows() < *block_days {
+                return Err(invalid(format!("Blocks must be between 1 and the {} days every ticker has quotes for", returns.nrows())));
+            }
+            Ok(FittedModel::Bootstrap {
+                tickers: tickers.to_vec(),
+                returns,
+                block_days: *block_days,
+            })
+        },
+        PriceModelConfig::Fixed { annual_return } => match *annual_return > -1.0 {
+            true => Ok(FittedModel::Fixed { annual_return: *annual_return }),
+            false => Err(invalid("The annual return must be more than -100%".to_string())),
+        },
+    }
+}
+
+/// Upper bounds on a run so one request can't tie the server up. Steps across every path are
+/// capped as well since a daily run takes about 30 times as many steps as a monthly one.
+const MAX_PATHS: usize = 10000;
+const MAX_YEARS: u32 = 100;
+const MAX_STEPS: u64 = 12_000_000;
+const MAX_MONTHLY_INVESTMENT: f64 = 1e9;
+
+/// Checks a scenario's settings before it is stored or run. Price models are otherwise checked
+/// when they are estimated.
+fn validate_scenario(config: &ScenarioConfig) -> Result<(), ModelError> {
+    let invalid = |message: String| Err(ModelError::Invalid(message));
+    if config.paths == 0 || config.paths > MAX_PATHS {
+        return invalid(format!("Paths must be between 1 and {}", MAX_PATHS));
+    }
+    if config.years == 0 || config.years > MAX_YEARS {
+        return invalid(format!("Years must be between 1 and {}", MAX_YEARS));
+    }
+    let steps = config.paths as u64 * config.years as u64 * config.step.max_steps_per_year();
+    if steps > MAX_STEPS {
+        return invalid(format!("{} paths of {} years takes {} steps, at most {} are allowed", config.paths, config.years, steps, MAX_STEPS));
+    }
+    if let PriceModelConfig::Markov { markov_model_id: None, buckets } = config.model {
+        markov::validate_buckets(buckets).map_err(|e| ModelError::Invalid(e.to_string()))?;
+    }
+    if config.assets.iter().flatten().any(|asset| asset.amount_held < 0) {
+        return invalid("Amounts held must not be negative".to_string());
+    }
+    match config.criteria {
+        CriteriaConfig::BasicFire { expenses, fire_rate, inflation } => {
+            if !(expenses > 0.0) {
+                return invalid("Expenses must be more than zero".to_string());
+            }
+            if !(fire_rate > 0.0 && fire_rate <= 1.0) {
+                return invalid("The FIRE rate must be between 0 and 1".to_string());
+            }
+            if !(inflation > -1.0 && inflation <= 1.0) {
+                return invalid("Inflation must be more than -100% and at most 100%".to_string());
+            }
+        },
+    }
+    for factor in &config.factors {
+        match factor {
+            FactorConfig::MonthlyInvestment { monthly_investment, allocation } => {
+                if !(0.0..=MAX_MONTHLY_INVESTMENT).contains(monthly_investment) {
+                    return invalid(format!("The monthly investment must be between 0 and {}", MAX_MONTHLY_INVESTMENT));
+                }
+                if let Some(allocation) = allocation {
+                    let targets: Vec<(String, f64)> = allocation.iter().map(|(ticker, weight)| (ticker.clone(), *weight)).collect();
+                    rebalance::validate_targets(&targets).map_err(|e| ModelError::Invalid(e.to_string()))?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+/// A scenario with its starting prices looked up and price model estimated, ready to run paths
+#[derive(Clone)]
+struct PreparedScenario {
+    id: i32,
+    name: String,
+    description: String,
+    created_at: DateTime<Utc>,
+    config: ScenarioConfig,
+    start: NaiveDate,
+    /// Ticker, amount held and price in the base currency
+    assets: Vec<(String, i64, f64)>,
+    /// Allocation for each factor, the starting weights where the factor has none
+    allocations: Vec<HashMap<String, f64>>,
+    prices: FittedModel,
+    unpriced: Vec<String>,
+}
+
+impl PreparedScenario {
+    fn model(&self, seed: u64) -> Model {
+        let criteria: Box<dyn Criteria> = match self.config.criteria {
+            CriteriaConfig::BasicFire { expenses, fire_rate, inflation } => Box::new(BasicFIRECriteria {
+                fire_rate,
+                expenses,
+                inflation,
+            }),
+        };
+        let factors = self.config.factors.iter().zip(&self.allocations)
+            .map(|(factor, allocation)| -> Box<dyn InvestmentFactors> {
+                match factor {
+                    FactorConfig::MonthlyInvestment { monthly_investment, .. } => Box::new(MonthlyInvestmentFactor {
+                        leftover: 0.0,
+                        monthly_investment: *monthly_investment,
+                        last_investment: None,
+                        desired_allocation: allocation.clone(),
+                    }),
+                }
+            })
+            .collect();
+        Model {
+            id: self.id,
+            name: self.name.clone(),
+            description: self.description.clone(),
+            created_at: self.created_at,
+            parameters: Parameters {
+                max_iterations: i32::MAX,
+                start: self.start,
+                end: self.start + Months::new(12 * self.config.years),
+                step: self.config.step,
+                stop_when_complete: self.config.stop_when_complete,
+            },
+            assets: self.assets.iter()
+                .map(|(ticker, amount_held, price)| Asset {
+                    ticker: ticker.clone(),
+                    amount_held: *amount_held,
+                    price_history: Some(vec![*price]),
+                })
+                .collect(),
+            model_type: self.prices.build(seed),
+            criteria,
+            factors,
+        }
+    }
+}
+
+/// Looks up starting prices in the base currency and estimates the price model. Runs start today.
+async fn prepare_scenario(id: i32, name: String, description: String, created_at: DateTime<Utc>, config: ScenarioConfig, db_pool: &sqlx::PgPool) -> Result<PreparedScenario, (StatusCode, Json<serde_json::Value>)> {
+    validate_scenario(&config).map_err(bad_request)?;
+    let start = chrono::Utc::now().date_naive();
+    let fx = FxTable::from_rates(FxRateModel::get_by_currency(config.base, db_pool).await.map_err(internal_error)?);
+    let mut holdings: HashMap<String, i64> = match &config.assets {
+        Some(assets) => assets.iter().map(|asset| (asset.ticker.clone(), asset.amount_held)).collect(),
+        None => StockModel::get_all(db_pool).await.map_err(internal_error)?
+            .into_iter()
+            .map(|stock| (stock.ticker, stock.amount_held.into()))
+            .collect(),
+    };
+    for factor in &config.factors {
+        match factor {
+            FactorConfig::MonthlyInvestment { allocation, .. } => {
+                for ticker in allocation.iter().flat_map(|allocation| allocation.keys()) {
+                    holdings.entry(ticker.clone()).or_insert(0);
+                }
+            },
+        }
+    }
+    let mut assets = Vec::new();
+    let mut unpriced = Vec::new();
+    for (ticker, amount_held) in holdings {
+        let quote = match QuoteModel::get_closest_date(ticker.clone(), start, db_pool).await {
+            Ok(quote) => quote,
+            Err(sqlx::Error::RowNotFound) => {
+                unpriced.push(ticker);
+                continue;
+            },
+            Err(e) => return Err(internal_error(e)),
+        };
+        match fx.rate(quote.currency, config.base, quote.date) {
+            Some(rate) => assets.push((ticker, amount_held, quote.close.to_f64().unwrap_or(0.0) * rate)),
+            None => unpriced.push(ticker),
+        }
+    }
+    if assets.is_empty() {
+        return Err(bad_request(ModelError::Invalid("Nothing is held or allocated with a price to simulate from".to_string())));
+    }
+    assets.sort_by(|a, b| a.0.cmp(&b.0));
+    unpriced.sort();
+    // Without an allocation new money follows the starting weights
+    let total = assets.iter().fold(0.0, |total, (_, amount_held, price)| total + *amount_held as f64 * price);
+    let starting_weights: HashMap<String, f64> = assets.iter()
+        .map(|(ticker, amount_held, price)| (ticker.clone(), match total > 0.0 {
+            true => *amount_held as f64 * price / total,
+            false => 1.0 / assets.len() as f64,
+        }))
+        .collect();
+    let allocations = config.factors.iter()
+        .map(|factor| match factor {
+            FactorConfig::MonthlyInvestment { allocation, .. } => allocation.clone().unwrap_or(starting_weights.clone()),
+        })
+        .collect();
+    let tickers: Vec<String> = assets.iter().map(|(ticker, _, _)| ticker.clone()).collect();
+    let prices = fit_price_model(&config.model, &tickers, start, db_pool).await?;
+    Ok(PreparedScenario {
+        id,
+        name,
+        description,
+        created_at,
+        config,
+        start,
+        assets,
+        allocations,
+        prices,
+        unpriced,
+    })
+}
+
+/// Summary of every path of a run and the full trajectory of the first
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ScenarioRun {
+    start: NaiveDate,
+    base: Currency,
+    seed: u64,
+    model: PriceModelConfig,
+    unpriced: Vec<String>,
+    summary: FireSummary,
+    trajectory: Trajectory,
+}
+
+/// Runs every path of a scenario off the async runtime. Path `n` is seeded with `seed + n` so
+/// running the same scenario on the same day gives the same answer.
+async fn run_scenario(scenario: PreparedScenario) -> Result<ScenarioRun, (StatusCode, Json<serde_json::Value>)> {
+    tokio::task::spawn_blocking(move || -> Result<ScenarioRun, ModelError> {
+        let seed = scenario.config.seed;
+        let mut first = None;
+        let mut outcomes = Vec::new();
+        for path in 0..scenario.config.paths as u64 {
+            let trajectory = scenario.model(seed.wrapping_add(path)).run()?;
+            outcomes.push(PathOutcome {
+                years_to_fire: trajectory.completed.map(|date| (date - scenario.start).num_days() as f64 / DAYS_PER_YEAR),
+                yearly_values: trajectory.yearly_values(),
+            });
+            first.get_or_insert(trajectory);
+        }
+        let trajectory = first.ok_or(ModelError::Invalid("A run needs at least one path".to_string()))?;
+        Ok(ScenarioRun {
+            start: scenario.start,
+            base: scenario.config.base,
+            seed,
+            model: scenario.config.model.clone(),
+            unpriced: scenario.unpriced.clone(),
+            summary: fire::summarise(&outcomes),
+            trajectory,
+        })
+    }).await.map_err(internal_error)?.map_err(bad_request)
+}
+
+/// Monte Carlo simulation of reaching FIRE from the current holdings, with prices stepped by the
+/// chosen price model. Paths carry on after FIRE is reached so every year has a value.
+pub async fn simulate_fire(
+    AxumState(app_state): AxumState<Arc<AppState>>,
+    Json(request): Json<FireRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let config: ScenarioConfig = request.into();
+    let scenario = prepare_scenario(-1, "FIRE".to_string(), "Monte Carlo FIRE simulation".to_string(), Utc::now(), config, &app_state.db_pool).await?;
+    let run = run_scenario(scenario).await?;
+    Ok(Json(json!({
+        "start": run.start,
+        "base": run.base,
+        "seed": run.seed,
+        "model": run.model,
+        "unpriced": run.unpriced,
+        "summary": run.summary,
+    })))
+}
+
+fn scenario_not_found(e: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    match e {
+        sqlx::Error::RowNotFound => (StatusCode::NOT_FOUND, Json(json!({ "error": "Scenario not found" }))),
+        e => internal_error(e),
     }
-}
\ No newline at end of file
+}
+
+pub async fn add_scenario(
+    AxumState(app_state): AxumState<Arc<AppState>>,
+    Json(scenario): Json<ScenarioJson>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    validate_scenario(&scenario.config).map_err(bad_request)?;
+    let scenario: ScenarioModel = scenario.try_into().map_err(internal_error)?;
+    let scenario = scenario.insert(&app_state.db_pool).await.map_err(internal_error)?;
+    let scenario: ScenarioJson = scenario.try_into().map_err(internal_error)?;
+    Ok(Json(json!(scenario)))
+}
+
+pub async fn get_scenarios(
+    AxumState(app_state): AxumState<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let scenarios = ScenarioModel::get_all(&app_state.db_pool).await.map_err(internal_error)?;
+    let scenarios = scenarios.into_iter()
+        .map(ScenarioJson::try_from)
+        .collect::<Result<Vec<ScenarioJson>, _>>()
+        .map_err(internal_error)?;
+    Ok(Json(json!(scenarios)))
+}
+
+pub async fn get_scenario(
+    AxumState(app_state): AxumState<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let scenario = ScenarioModel::get_by_id(id, &app_state.db_pool).await.map_err(scenario_not_found)?;
+    let scenario: ScenarioJson = scenario.try_into().map_err(internal_error)?;
+    Ok(Json(json!(scenario)))
+}
+
+/// Replaces a scenario. Its stored runs are deleted with the config they were run from.
+pub async fn update_scenario(
+    AxumState(app_state): AxumState<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(scenario): Json<ScenarioJson>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    validate_scenario(&scenario.config).map_err(bad_request)?;
+    let mut scenario: ScenarioModel = scenario.try_into().map_err(internal_error)?;
+    scenario.id = id;
+    let scenario = scenario.update(&app_state.db_pool).await.map_err(scenario_not_found)?;
+    let scenario: ScenarioJson = scenario.try_into().map_err(internal_error)?;
+    Ok(Json(json!(scenario)))
+}
+
+/// Deletes a scenario and every stored run of it
+pub async fn delete_scenario(
+    AxumState(app_state): AxumState<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let scenario = ScenarioModel::delete_by_id(id, &app_state.db_pool).await.map_err(scenario_not_found)?;
+    let scenario: ScenarioJson = scenario.try_into().map_err(internal_error)?;
+    Ok(Json(json!(scenario)))
+}
+
+/// Runs a stored scenario from today's prices and stores the result
+pub async fn run_stored_scenario(
+    AxumState(app_state): AxumState<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &app_state.db_pool;
+    let stored = ScenarioModel::get_by_id(id, db_pool).await.map_err(scenario_not_found)?;
+    let config: ScenarioConfig = serde_json::from_str(&stored.config).map_err(internal_error)?;
+    let created_at = DateTime::<Utc>::from_naive_utc_and_offset(stored.created_at, Utc);
+    let scenario = prepare_scenario(stored.id, stored.name, stored.description, created_at, config, db_pool).await?;
+    let run = run_scenario(scenario).await?;
+    let result = serde_json::to_string(&run).map_err(internal_error)?;
+    let result = ScenarioResultModel::insert(id, result, db_pool).await.map_err(internal_error)?;
+    let result: ScenarioResultJson = result.try_into().map_err(internal_error)?;
+    Ok(Json(json!(result)))
+}
+
+pub async fn get_scenario_results(
+    AxumState(app_state): AxumState<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &app_state.db_pool;
+    ScenarioModel::get_by_id(id, db_pool).await.map_err(scenario_not_found)?;
+    let results = ScenarioResultModel::get_by_scenario(id, db_pool).await.map_err(internal_error)?;
+    let results = results.into_iter()
+        .map(ScenarioResultJson::try_from)
+        .collect::<Result<Vec<ScenarioResultJson>, _>>()
+        .map_err(internal_error)?;
+    Ok(Json(json!(results)))
+}
+
+/// Latest run of a scenario alongside the scenario itself
+async fn latest_run(id: i32, db_pool: &sqlx::PgPool) -> Result<(ScenarioJson, ScenarioRun), (StatusCode, Json<serde_json::Value>)> {
+    let scenario: ScenarioJson = ScenarioModel::get_by_id(id, db_pool).await.map_err(scenario_not_found)?
+        .try_into()
+        .map_err(internal_error)?;
+    let result = ScenarioResultModel::get_latest(id, db_pool).await.map_err(|e| match e {
+        sqlx::Error::RowNotFound => (StatusCode::NOT_FOUND, Json(json!({ "error": format!("Scenario {} has not been run", id) }))),
+        e => internal_error(e),
+    })?;
+    let run: ScenarioRun = serde_json::from_str(&result.result).map_err(internal_error)?;
+    Ok((scenario, run))
+}
+
+/// Side by side comparison of the latest runs of two scenarios
+pub async fn compare_scenarios(
+    AxumState(app_state): AxumState<Arc<AppState>>,
+    Query(query): Query<CompareQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &app_state.db_pool;
+    let (left, left_run) = latest_run(query.left, db_pool).await?;
+    let (right, right_run) = latest_run(query.right, db_pool).await?;
+    Ok(Json(json!({
+        "left": {
+            "scenario": left,
+            "start": left_run.start,
+            "summary": left_run.summary,
+        },
+        "right": {
+            "scenario": right,
+            "start": right_run.start,
+            "summary": right_run.summary,
+        },
+        "comparison": fire::compare(&left_run.summary, &right_run.summary),
+    })))
+}
+
+fn not_found(e: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    match e {
+        sqlx::Error::RowNotFound => (StatusCode::NOT_FOUND, Json(json!({ "error": "Markov model not found" }))),
+        e => internal_error(e),
+    }
+}
+
+/// Fits a Markov chain to the daily total returns of the given tickers, or every active ticker,
+/// and stores it for simulations
+pub async fn fit_markov_model(
+    AxumState(app_state): AxumState<Arc<AppState>>,
+    Json(request): Json<MarkovFitRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &app_state.db_pool;
+    let end = request.end.unwrap_or(chrono::Utc::now().date_naive());
+    let start = request.start.unwrap_or(end - Months::new(60));
+    let tickers = match request.tickers {
+        Some(tickers) => tickers,
+        None => QuoteModel::get_active_tickers(db_pool).await.map_err(internal_error)?,
+    };
+    let mut series = Vec::new();
+    for ticker in tickers {
+        let marks = ticker_marks(ticker.clone(), start, end, db_pool).await.map_err(internal_error)?;
+        series.push((ticker, marks));
+    }
+    let fit = markov::fit(&series, request.buckets, start, end).map_err(bad_request)?;
+    let model = fit.to_model().insert(db_pool).await.map_err(internal_error)?;
+    let model: MarkovFitJson = model.into();
+    Ok(Json(json!(model)))
+}
+
+pub async fn get_markov_models(
+    AxumState(app_state): AxumState<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let models = MarkovFitModel::get_all(&app_state.db_pool).await.map_err(internal_error)?;
+    let models: Vec<MarkovFitJson> = models.into_iter().map(|model| model.into()).collect();
+    Ok(Json(json!(models)))
+}
+
+pub async fn get_markov_model(
+    AxumState(app_state): AxumState<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let model = MarkovFitModel::get_by_id(id, &app_state.db_pool).await.map_err(not_found)?;
+    let model: MarkovFitJson = model.into();
+    Ok(Json(json!(model)))
+}
+
+pub async fn delete_markov_model(
+    AxumState(app_state): AxumState<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let model = MarkovFitModel::delete_by_id(id, &app_state.db_pool).await.map_err(not_found)?;
+    let model: MarkovFitJson = model.into();
+    Ok(Json(json!(model)))
+}
+
+pub fn build_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/modelling/markov", get(get_markov_models).post(fit_markov_model))
+        .route("/modelling/markov/:id", get(get_markov_model).delete(delete_markov_model))
+        .route("/modelling/fire", post(simulate_fire))
+        .route("/modelling/scenarios", get(get_scenarios).post(add_scenario))
+        .route("/modelling/scenarios/compare", get(compare_scenarios))
+        .route("/modelling/scenarios/:id", get(get_scenario).put(update_scenario).delete(delete_scenario))
+        .route("/modelling/scenarios/:id/run", post(run_stored_scenario))
+        .route("/modelling/scenarios/:id/results", get(get_scenario_results))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytics::lots::tests::assert_close;
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    /// Dates of the first `steps` ticks of a clock
+    fn ticks(start: &str, step: StepSize, steps: usize) -> Vec<NaiveDate> {
+        let mut clock = Clock::new(date(start), step);
+        (0..steps)
+            .map(|_| {
+                clock.tick();
+                clock.current
+            })
+            .collect()
+    }
+
+    /// One asset of 10 shares at 100 with a criteria that is never met
+    fn model(prices: Box<dyn InvestmentModel>, start: &str, end: &str, step: StepSize) -> Model {
+        Model {
+            id: -1,
+            name: "test".to_string(),
+            description: String::new(),
+            created_at: Utc::now(),
+            parameters: Parameters {
+                max_iterations: i32::MAX,
+                start: date(start),
+                end: date(end),
+                step,
+                stop_when_complete: false,
+            },
+            assets: vec![Asset {
+                ticker: "A".to_string(),
+                amount_held: 10,
+                price_history: Some(vec![100.0]),
+            }],
+            model_type: prices,
+            criteria: Box::new(BasicFIRECriteria {
+                fire_rate: 0.04,
+                expenses: f64::MAX,
+                inflation: 0.0,
+            }),
+            factors: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn months_are_counted_from_the_start() {
+        assert_eq!(
+            ticks("2024-01-31", StepSize::Month, 3),
+            vec![date("2024-02-29"), date("2024-03-31"), date("2024-04-30")],
+        );
+        assert_eq!(ticks("2024-01-31", StepSize::Week, 2), vec![date("2024-02-07"), date("2024-02-14")]);
+        assert_eq!(ticks("2024-02-28", StepSize::Day, 2), vec![date("2024-02-29"), date("2024-03-01")]);
+    }
+
+    #[test]
+    fn steps_cover_the_calendar_days_they_span() {
+        let mut clock = Clock::new(date("2024-01-31"), StepSize::Month);
+        clock.tick();
+        assert_close(clock.step_years(), 29.0 / DAYS_PER_YEAR);
+        clock.tick();
+        assert_close(clock.step_years(), 31.0 / DAYS_PER_YEAR);
+        let mut clock = Clock::new(date("2024-01-31"), StepSize::Day);
+        clock.tick();
+        assert_close(clock.step_years(), 1.0 / DAYS_PER_YEAR);
+    }
+
+    #[test]
+    fn new_months_are_only_seen_once() {
+        let mut clock = Clock::new(date("2024-01-29"), StepSize::Week);
+        clock.tick();
+        assert!(clock.new_month());
+        clock.tick();
+        assert!(!clock.new_month());
+    }
+
+    #[test]
+    fn trading_days_carry_over_between_steps() {
+        // Four years are 1461 days, or 1008 trading days
+        let total = |step: StepSize, steps: usize| {
+            let mut clock = Clock::new(date("2020-01-01"), step);
+            let mut days = Vec::new();
+            for _ in 0..steps {
+                clock.tick();
+                days.push(trading_days(&clock));
+            }
+            days
+        };
+        let days = total(StepSize::Day, 1461);
+        assert_eq!(days.iter().sum::<usize>(), 1008);
+        assert!(days.iter().all(|days| *days <= 1));
+        assert_eq!(total(StepSize::Month, 48).iter().sum::<usize>(), 1008);
+        // 208 weeks are 1456 days
+        assert_eq!(total(StepSize::Week, 208).iter().sum::<usize>(), 1004);
+    }
+
+    #[test]
+    fn a_run_records_every_step_until_the_end() {
+        let prices = Box::new(FixedReturnModel { annual_return: 0.1 });
+        let trajectory = model(prices, "2023-01-01", "2024-01-01", StepSize::Month).run().unwrap();
+        assert_eq!(trajectory.snapshots.len(), 13);
+        assert_eq!(trajectory.snapshots[12].date, date("2024-01-01"));
+        assert_eq!(trajectory.state, State::Running);
+        assert_eq!(trajectory.completed, None);
+        assert_close(trajectory.snapshots[12].total_value, 1000.0 * 1.1f64.powf(365.0 / DAYS_PER_YEAR));
+        assert_eq!(trajectory.yearly_values().len(), 2);
+    }
+
+    #[test]
+    fn a_run_must_end_after_it_starts() {
+        let prices = Box::new(FixedReturnModel { annual_return: 0.1 });
+        assert!(model(prices, "2024-01-01", "2023-01-01", StepSize::Month).run().is_err());
+    }
+
+    /// Fit over two buckets that step prices by -10% and +10%
+    fn markov_fit(transitions: Vec<f64>) -> MarkovFit {
+        MarkovFit {
+            tickers: vec!["A".to_string()],
+            buckets: 2,
+            start: date("2023-01-01"),
+            end: date("2024-01-01"),
+            transitions: Array3::from_shape_vec((1, 2, 2), transitions).unwrap(),
+            bucket_returns: Array2::from_shape_vec((1, 2), vec![-0.1, 0.1]).unwrap(),
+            initial_states: vec![0],
+        }
+    }
+
+    /// Prices of asset A at the start and after each of `steps` monthly steps from 2023
+    fn prices(prices: Box<dyn InvestmentModel>, steps: usize) -> Vec<f64> {
+        let mut model = model(prices, "2023-01-01", "2100-01-01", StepSize::Month);
+        model.parameters.max_iterations = steps as i32;
+        model.run().unwrap().snapshots.iter().map(|snapshot| snapshot.assets[0].price).collect()
+    }
+
+    #[test]
+    fn markov_chains_follow_the_transitions() {
+        // Always switching bucket from a fall gives a rise
+        let fit = markov_fit(vec![0.0, 1.0, 1.0, 0.0]);
+        let mut model = MarkovChainModel::new(fit, 1);
+        let mut clock = Clock::new(date("2024-01-01"), StepSize::Day);
+        let mut assets = vec![Asset {
+            ticker: "A".to_string(),
+            amount_held: 1,
+            price_history: Some(vec![100.0]),
+        }];
+        // The first day is short of a whole trading day, the next two are one each
+        for _ in 0..3 {
+            clock.tick();
+            model.update(&clock, &mut assets);
+        }
+        let history = assets[0].price_history.as_ref().unwrap();
+        assert_close(history[1], 100.0);
+        assert_close(history[2], 110.0);
+        assert_close(history[3], 99.0);
+        assert_eq!(model.current_states, vec![0]);
+    }
+
+    #[test]
+    fn markov_chains_are_seeded() {
+        let fit = markov_fit(vec![0.5, 0.5, 0.5, 0.5]);
+        let first = prices(Box::new(MarkovChainModel::new(fit.clone(), 7)), 12);
+        assert_eq!(first.len(), 13);
+        assert_eq!(first, prices(Box::new(MarkovChainModel::new(fit.clone(), 7)), 12));
+        assert_ne!(first, prices(Box::new(MarkovChainModel::new(fit, 8)), 12));
+    }
+
+    /// A FIRE request's config with everything else left at its default
+    fn scenario(monthly_investment: f64, inflation: f64) -> ScenarioConfig {
+        let mut request: FireRequest = serde_json::from_value(json!({ "expenses": 40000.0 })).unwrap();
+        request.monthly_investment = monthly_investment;
+        request.inflation = inflation;
+        request.into()
+    }
+
+    #[test]
+    fn monthly_investment_and_inflation_are_bounded() {
+        assert!(validate_scenario(&scenario(1000.0, 0.03)).is_ok());
+        assert!(validate_scenario(&scenario(-1.0, 0.03)).is_err());
+        assert!(validate_scenario(&scenario(1e30, 0.03)).is_err());
+        assert!(validate_scenario(&scenario(f64::NAN, 0.03)).is_err());
+        assert!(validate_scenario(&scenario(1000.0, -1.0)).is_err());
+        assert!(validate_scenario(&scenario(1000.0, f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn investing_in_penny_prices_does_not_overflow() {
+        let mut factor = MonthlyInvestmentFactor {
+            leftover: 0.0,
+            monthly_investment: MAX_MONTHLY_INVESTMENT,
+            last_investment: None,
+            desired_allocation: HashMap::from([("A".to_string(), 1.0)]),
+        };
+        let mut assets = vec![Asset {
+            ticker: "A".to_string(),
+            amount_held: i64::MAX - 10,
+            price_history: Some(vec![0.001]),
+        }];
+        let mut clock = Clock::new(date("2024-01-01"), StepSize::Month);
+        clock.tick();
+        factor.update(&clock, &mut assets);
+        assert_eq!(assets[0].amount_held, i64::MAX);
+        assert_close(factor.leftover, MAX_MONTHLY_INVESTMENT - 0.01);
+    }
+
+    #[test]
+    fn monthly_investment_buys_whole_shares_and_carries_the_rest() {
+        let mut factor = MonthlyInvestmentFactor {
+            leftover: 0.0,
+            monthly_investment: 250.0,
+            last_investment: None,
+            desired_allocation: HashMap::from([("A".to_string(), 1.0)]),
+        };
+        let mut assets = vec![Asset {
+            ticker: "A".to_string(),
+            amount_held: 0,
+            price_history: Some(vec![100.0]),
+        }];
+        let mut clock = Clock::new(date("2024-01-01"), StepSize::Week);
+        for _ in 0..5 {
+            clock.tick();
+            factor.update(&clock, &mut assets);
+        }
+        // Invested on 2024-01-08 and again on 2024-02-05
+        assert_eq!(assets[0].amount_held, 5);
+        assert_close(factor.leftover, 0.0);
+    }
+
+    fn gbm(drift: f64, volatility: f64, seed: u64) -> Box<dyn InvestmentModel> {
+        Box::new(GeometricBrownianMotionModel {
+            tickers: vec!["A".to_string()],
+            drift: vec![drift],
+            volatility: vec![volatility],
+            rng: StdRng::seed_from_u64(seed),
+        })
+    }
+
+    #[test]
+    fn gbm_is_estimated_from_log_returns() {
+        let marks: Vec<Mark> = (0..100)
+            .map(|day| Mark {
+                date: date("2024-01-01") + chrono::Days::new(day),
+                price: 100.0 * 1.001f64.powi(day as i32),
+            })
+            .collect();
+        let (drift, volatility) = GeometricBrownianMotionModel::estimate(&marks).unwrap();
+        assert_close(drift, 1.001f64.ln() * TRADING_DAYS_PER_YEAR);
+        assert!(volatility.abs() < 1e-9);
+        assert!(GeometricBrownianMotionModel::estimate(&marks[..1]).is_none());
+    }
+
+    #[test]
+    fn gbm_without_volatility_grows_at_the_drift() {
+        let prices = prices(gbm(0.05, 0.0, 1), 12);
+        assert_close(prices[12], 100.0 * (0.05 * 365.0 / DAYS_PER_YEAR).exp());
+    }
+
+    #[test]
+    fn gbm_paths_are_seeded() {
+        let first = prices(gbm(0.05, 0.2, 7), 12);
+        assert_eq!(first, prices(gbm(0.05, 0.2, 7), 12));
+        assert_ne!(first, prices(gbm(0.05, 0.2, 8), 12));
+    }
+
+    fn bootstrap(returns: Vec<f64>, block_days: usize, seed: u64) -> BlockBootstrapModel {
+        BlockBootstrapModel {
+            tickers: vec!["A".to_string(), "B".to_string()],
+            returns: Array2::from_shape_vec((returns.len() / 2, 2), returns).unwrap(),
+            block_days,
+            row: 0,
+            remaining: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Prices of A and B after each of `steps` monthly steps, both starting at 100
+    fn bootstrap_prices(mut model: BlockBootstrapModel, steps: usize) -> Vec<(f64, f64)> {
+        let mut clock = Clock::new(date("2023-01-01"), StepSize::Month);
+        let mut assets: Vec<Asset> = ["A", "B"].iter()
+            .map(|ticker| Asset {
+                ticker: ticker.to_string(),
+                amount_held: 1,
+                price_history: Some(vec![100.0]),
+            })
+            .collect();
+        (0..steps)
+            .map(|_| {
+                clock.tick();
+                model.update(&clock, &mut assets);
+                (assets[0].latest_price(), assets[1].latest_price())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bootstrap_replays_every_trading_day() {
+        // A always rises 1% and B 2%, so only the number of days replayed matters
+        let model = bootstrap([0.01, 0.02].repeat(30), 5, 1);
+        let prices = bootstrap_prices(model, 12);
+        let (a, b) = prices[11];
+        assert_close(a, 100.0 * 1.01f64.powi(251));
+        assert_close(b, 100.0 * 1.02f64.powi(251));
+    }
+
+    #[test]
+    fn bootstrap_keeps_tickers_on_the_same_day() {
+        let returns = vec![0.01, 0.01, -0.02, -0.02, 0.03, 0.03, 0.0, 0.0, -0.01, -0.01];
+        let first = bootstrap_prices(bootstrap(returns.clone(), 2, 7), 12);
+        assert!(first.iter().all(|(a, b)| (a - b).abs() < 1e-9));
+        assert_eq!(first, bootstrap_prices(bootstrap(returns.clone(), 2, 7), 12));
+        assert_ne!(first, bootstrap_prices(bootstrap(returns, 2, 8), 12));
+    }
+
+    #[test]
+    fn fixed_returns_compound_over_the_days_stepped() {
+        let prices = prices(Box::new(FixedReturnModel { annual_return: 0.1 }), 24);
+        assert_close(prices[12], 100.0 * 1.1f64.powf(365.0 / DAYS_PER_YEAR));
+        assert_close(prices[24], 100.0 * 1.1f64.powf(731.0 / DAYS_PER_YEAR));
+    }
+}
+
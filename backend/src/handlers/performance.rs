@@ -0,0 +1,95 @@
+use crate::{
+    AppState,
+    analytics::fx::FxTable,
+    analytics::income,
+    analytics::performance::{Period, PortfolioHistory},
+    analytics::pnl::Mark,
+    analytics::prices::PriceAdjustment,
+    models::dividends::DividendModel,
+    models::fx::{Currency, FxRateModel},
+    models::quotes::QuoteModel,
+    models::trades::TradeModel,
+    schema::performance::PerformanceQuery,
+    handlers::internal_error,
+};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use axum::Router;
+use axum::{routing::get, response::IntoResponse, http::StatusCode};
+use axum::Json;
+use axum::extract::{Query, State};
+use bigdecimal::ToPrimitive;
+use chrono::NaiveDate;
+use serde_json::json;
+
+/// Loads every trade up to `end` with the split adjusted closes, dividends and FX rates needed to value them
+pub async fn load_history(base: Currency, end: NaiveDate, db_pool: &sqlx::PgPool) -> Result<PortfolioHistory, sqlx::Error> {
+    let trades: Vec<TradeModel> = TradeModel::get_all(db_pool).await?
+        .into_iter()
+        .filter(|trade| trade.date <= end)
+        .collect();
+    let inception = trades.iter().map(|trade| trade.date).min().unwrap_or(end);
+    let tickers: BTreeSet<String> = trades.iter().map(|trade| trade.ticker.clone()).collect();
+    let mut prices = HashMap::new();
+    for ticker in tickers {
+        let quotes = QuoteModel::get_date_range(ticker.clone(), inception, end, PriceAdjustment::SplitAdjusted, db_pool).await?;
+        let marks: Vec<Mark> = quotes.iter()
+            .map(|quote| Mark {
+                date: quote.date,
+                price: quote.close.to_f64().unwrap_or(0.0),
+            })
+            .collect();
+        prices.insert(ticker, marks);
+    }
+    let dividends = DividendModel::get_all(db_pool).await?;
+    let dividends = income::entitlements(&dividends, &trades);
+    let fx = FxTable::from_rates(FxRateModel::get_by_currency(base, db_pool).await?);
+    Ok(PortfolioHistory {
+        trades,
+        prices,
+        dividends,
+        fx,
+        base,
+    })
+}
+
+/// Time and money weighted returns for a period, or for `start` to `end`
+pub async fn get_performance(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<PerformanceQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let end = query.end.unwrap_or(chrono::Utc::now().date_naive());
+    let history = load_history(query.base, end, &app_state.db_pool).await.map_err(internal_error)?;
+    let inception = history.inception().unwrap_or(end);
+    let start = match query.period {
+        Some(period) => period.start(end, inception),
+        None => query.start.unwrap_or(inception),
+    };
+    let mut report = history.performance(start, end);
+    report.period = query.period;
+    Ok(Json(json!(report)))
+}
+
+/// Returns for 1M, 3M, YTD, 1Y and since inception in one go
+pub async fn get_performance_periods(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<PerformanceQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let end = query.end.unwrap_or(chrono::Utc::now().date_naive());
+    let history = load_history(query.base, end, &app_state.db_pool).await.map_err(internal_error)?;
+    let inception = history.inception().unwrap_or(end);
+    let reports: Vec<_> = Period::ALL.iter()
+        .map(|period| {
+            let mut report = history.performance(period.start(end, inception), end);
+            report.period = Some(*period);
+            report
+        })
+        .collect();
+    Ok(Json(json!(reports)))
+}
+
+pub fn build_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/performance", get(get_performance))
+        .route("/performance/periods", get(get_performance_periods))
+}
\ No newline at end of file
@@ -0,0 +1,59 @@
+use crate::{
+    AppState,
+    analytics::fx::FxTable,
+    analytics::income,
+    analytics::lots::LotEngine,
+    analytics::pnl::{self, Mark},
+    models::dividends::DividendModel,
+    models::fx::{Currency, FxRateModel},
+    models::quotes::QuoteModel,
+    models::trades::TradeModel,
+    schema::pnl::PnlQuery,
+    handlers::internal_error,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use axum::Router;
+use axum::{routing::get, response::IntoResponse, http::StatusCode};
+use axum::Json;
+use axum::extract::{Query, State};
+use bigdecimal::ToPrimitive;
+use serde_json::json;
+
+/// Closing price on or before `as_of` for each ticker with the currency it is quoted in; tickers
+/// without quotes are left out
+pub async fn get_marks(tickers: &[String], as_of: chrono::NaiveDate, db_pool: &sqlx::PgPool) -> HashMap<String, (Mark, Currency)> {
+    let mut marks = HashMap::new();
+    for ticker in tickers {
+        if let Ok(quote) = QuoteModel::get_closest_date(ticker.clone(), as_of, db_pool).await {
+            marks.insert(ticker.clone(), (Mark {
+                date: quote.date,
+                price: quote.close.to_f64().unwrap_or(0.0),
+            }, quote.currency));
+        }
+    }
+    marks
+}
+
+pub async fn get_pnl(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<PnlQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &app_state.db_pool;
+    let as_of = query.as_of.unwrap_or(chrono::Utc::now().date_naive());
+    let trades: Vec<TradeModel> = TradeModel::get_all(db_pool).await
+        .map_err(internal_error)?
+        .into_iter()
+        .filter(|trade| trade.date <= as_of)
+        .collect();
+    let engine = LotEngine::replay(query.method, &trades);
+    let marks = get_marks(&engine.tickers(), as_of, db_pool).await;
+    let dividends = DividendModel::get_all(db_pool).await.map_err(internal_error)?;
+    let entitlements = income::entitlements(&dividends, &trades);
+    let fx = FxTable::from_rates(FxRateModel::get_by_currency(query.base, db_pool).await.map_err(internal_error)?);
+    Ok(Json(json!(pnl::calculate(&engine, &marks, &entitlements, &fx, query.base, as_of))))
+}
+
+pub fn build_router() -> Router<Arc<AppState>> {
+    Router::new().route("/pnl", get(get_pnl))
+}
\ No newline at end of file
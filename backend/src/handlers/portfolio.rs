@@ -1,34 +1,82 @@
-
-use actix_web::{get, web, HttpResponse, Responder};
 use crate::{
     AppState,
+    analytics::fx::FxTable,
+    analytics::performance,
+    models::fx::{Currency, FxRateModel},
     models::stocks::StockModel,
-    schema::stocks::{StockJson, PortfolioJson}
+    models::trades::TradeModel,
+    schema::AsOfQuery,
+    schema::performance::HistoryQuery,
+    schema::stocks::{StockJson, PortfolioJson},
+    handlers::internal_error,
+    handlers::performance::load_history,
 };
+use std::sync::Arc;
+use axum::Router;
+use axum::{routing::get, response::IntoResponse, http::StatusCode};
+use axum::Json;
+use axum::extract::{Query, State};
+use serde_json::json;
 
-#[get("/portfolio")]
-pub async fn calculate_porfolio(state: web::Data<AppState>) -> impl Responder {
-    let result = StockModel::get_all(&state.db_pool).await;
-    match result {
-        Ok(_) => (),
-        Err(_) => return HttpResponse::InternalServerError().body("Failed to get stocks")
-    }
-    let mut stocks = result.unwrap();
+/// Values the portfolio from stored closing prices. Without `as_of` the current holdings in
+/// `stocks` are valued at the latest close; with `as_of` holdings are rebuilt from the trade
+/// history up to that date. Holdings are converted into `base` (AUD by default) at the rate on
+/// the day they were priced.
+pub async fn calculate_porfolio(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<AsOfQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &app_state.db_pool;
+    let stocks = match query.as_of {
+        Some(as_of) => TradeModel::get_holdings_at(as_of, db_pool).await,
+        None => StockModel::get_all(db_pool).await,
+    }.map_err(internal_error)?;
+    let as_of = query.as_of.unwrap_or(chrono::Utc::now().date_naive());
+    let fx = FxTable::from_rates(FxRateModel::get_by_currency(query.base, db_pool).await.map_err(internal_error)?);
     let mut stocks_json: Vec<StockJson> = Vec::new();
     let mut total = 0.0;
-    for stock in stocks.iter_mut() {
-        let mut stock_json = StockJson::from_model(stock.clone());
-        stock_json.calculate_value().await;
-        total += stock_json.value.unwrap_or_default() * stock_json.amount_held as f64;
+    let mut stale = false;
+    for stock in stocks {
+        let mut stock_json = StockJson::from_model(stock);
+        stock_json.value_from_quotes(as_of, db_pool).await;
+        let currency = stock_json.currency.unwrap_or(Currency::from_ticker(&stock_json.ticker));
+        let fx_rate = fx.rate(currency, query.base, stock_json.as_of.unwrap_or(as_of));
+        stock_json.fx_rate = fx_rate;
+        total += stock_json.value.unwrap_or_default() * stock_json.amount_held as f64 * fx_rate.unwrap_or(1.0);
+        stale |= stock_json.stale.unwrap_or(true) || fx_rate.is_none();
         stocks_json.push(stock_json);
     }
     let portfolio = PortfolioJson {
         stocks: stocks_json,
-        total: total
+        total,
+        as_of,
+        stale,
+        base: query.base,
     };
-    HttpResponse::Ok().json(portfolio)
+    Ok(Json(json!(portfolio)))
+}
+
+/// Value, cost basis and cash flows day by day, or for the last day of each week or month
+pub async fn get_portfolio_history(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let end = query.end.unwrap_or(chrono::Utc::now().date_naive());
+    let history = load_history(query.base, end, &app_state.db_pool).await.map_err(internal_error)?;
+    let start = query.start.unwrap_or(history.inception().unwrap_or(end));
+    let (points, missing_fx) = history.daily_values(start, end, query.breakdown);
+    Ok(Json(json!({
+        "start": start,
+        "end": end,
+        "base": query.base,
+        "interval": query.interval,
+        "missing_fx": missing_fx,
+        "points": performance::downsample(points, query.interval),
+    })))
 }
 
-pub fn configure_portfolio(config: &mut web::ServiceConfig) {
-    config.service(calculate_porfolio);
+pub fn build_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/portfolio", get(calculate_porfolio))
+        .route("/portfolio/history", get(get_portfolio_history))
 }
\ No newline at end of file
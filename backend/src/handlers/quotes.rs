@@ -1,10 +1,18 @@
 use actix_web::{get, post, web, HttpResponse, Responder};
 use crate::{
     AppState,
-    models::quotes::QuoteModel, schema::quotes::QuoteJson,
+    models::quotes::QuoteModel, schema::quotes::{IndicatorQuery, QuoteJson, QuoteRangeQuery},
+    analytics::indicators::{self, IndicatorSettings},
     schema::Pagination,
-    models::stocks::is_valid_ticker
+    models::stocks::is_valid_ticker,
+    handlers::{internal_error, bad_request},
 };
+use std::sync::Arc;
+use axum::{Router, routing, response::IntoResponse, http::StatusCode};
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use chrono::NaiveDate;
+use serde_json::json;
 
 #[get("/quotes")]
 pub async fn get_all_quotes(page: web::Query<Pagination>, state: web::Data<AppState>) -> impl Responder {
@@ -17,11 +25,11 @@ pub async fn get_all_quotes(page: web::Query<Pagination>, state: web::Data<AppSt
 
 #[post("/quotes")]
 pub async fn add_ticker(state: web::Data<AppState>, ticker: web::Json<String>) -> impl Responder {
-    let valid = is_valid_ticker(&ticker.clone()).await;
+    let valid = is_valid_ticker(&ticker.clone(), state.market_data.as_ref()).await;
     if !valid {
         return HttpResponse::BadRequest().body("Invalid ticker");
     }
-    let result = QuoteModel::populate_ticker(ticker.into_inner(), &state.db_pool).await;
+    let result = QuoteModel::populate_ticker(ticker.into_inner(), state.market_data.as_ref(), &state.db_pool).await;
     match result {
         Ok(_) => HttpResponse::Ok().body("Ticker added successfully!"),
         Err(_) => HttpResponse::InternalServerError().body("Failed to add ticker")
@@ -33,3 +41,42 @@ pub fn configure_quotes(config: &mut web::ServiceConfig) {
     config.service(add_ticker);
 }
 
+/// Daily quotes for one ticker, raw, split adjusted (the default) or total return adjusted
+pub async fn get_quote_range(
+    State(app_state): State<Arc<AppState>>,
+    Path(ticker): Path<String>,
+    Query(query): Query<QuoteRangeQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let start = query.start.unwrap_or(NaiveDate::from_ymd_opt(1970, 1, 1).unwrap());
+    let end = query.end.unwrap_or(chrono::Utc::now().date_naive());
+    let quotes = QuoteModel::get_date_range(ticker, start, end, query.adjustment, &app_state.db_pool).await.map_err(internal_error)?;
+    Ok(Json(json!(quotes.into_iter().map(|quote| quote.into()).collect::<Vec<QuoteJson>>())))
+}
+
+/// Technical indicator lines for a ticker. Earlier quotes are used to warm the indicator up so
+/// values from `start` match those over the full history.
+pub async fn get_indicators(
+    State(app_state): State<Arc<AppState>>,
+    Path(ticker): Path<String>,
+    Query(query): Query<IndicatorQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let start = query.start.unwrap_or(NaiveDate::from_ymd_opt(1970, 1, 1).unwrap());
+    let end = query.end.unwrap_or(chrono::Utc::now().date_naive());
+    let history_start = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+    let quotes = QuoteModel::get_date_range(ticker, history_start, end, query.adjustment, &app_state.db_pool).await.map_err(internal_error)?;
+    let settings = IndicatorSettings {
+        kind: query.kind,
+        window: query.window.unwrap_or(query.kind.default_window()),
+        fast: query.fast,
+        slow: query.slow,
+        deviations: query.deviations,
+    };
+    let points = indicators::calculate(&quotes, settings).map_err(bad_request)?;
+    Ok(Json(json!(points.into_iter().filter(|point| point.date >= start).collect::<Vec<_>>())))
+}
+
+pub fn build_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/quotes/:ticker", routing::get(get_quote_range))
+        .route("/quotes/:ticker/indicators", routing::get(get_indicators))
+}
\ No newline at end of file
@@ -0,0 +1,113 @@
+use crate::{
+    AppState,
+    analytics::performance::{growth_index, Period},
+    analytics::pnl::Mark,
+    analytics::prices::PriceAdjustment,
+    analytics::risk,
+    analytics::correlation,
+    models::quotes::QuoteModel,
+    schema::risk::{CorrelationQuery, RiskQuery},
+    handlers::{internal_error, bad_request},
+    handlers::performance::load_history,
+};
+use std::sync::Arc;
+use axum::Router;
+use axum::{routing::get, response::IntoResponse, http::StatusCode};
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use bigdecimal::ToPrimitive;
+use chrono::{Duration, NaiveDate};
+use serde_json::json;
+
+/// Total return closes for `ticker` between `start` and `end`
+pub async fn ticker_marks(ticker: String, start: NaiveDate, end: NaiveDate, db_pool: &sqlx::PgPool) -> Result<Vec<Mark>, sqlx::Error> {
+    let quotes = QuoteModel::get_date_range(ticker, start, end, PriceAdjustment::TotalReturn, db_pool).await?;
+    Ok(quotes.iter()
+        .map(|quote| Mark {
+            date: quote.date,
+            price: quote.close.to_f64().unwrap_or(0.0),
+        })
+        .collect())
+}
+
+/// Risk of the whole portfolio, from its time weighted growth on days with quotes, and of each ticker
+/// held at the end of the range. `missing_fx` is set when any value was converted at par.
+pub async fn get_portfolio_risk(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<RiskQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &app_state.db_pool;
+    let end = query.end.unwrap_or(chrono::Utc::now().date_naive());
+    let history = load_history(query.base, end, db_pool).await.map_err(internal_error)?;
+    let inception = history.inception().unwrap_or(end);
+    let start = match query.period {
+        Some(period) => period.start(end, inception),
+        None => query.start.unwrap_or(inception),
+    };
+    let (points, missing_fx) = history.daily_values(start, end, true);
+    let portfolio = risk::on_quote_dates(growth_index(&points), &history.prices);
+    let mut tickers = Vec::new();
+    for holding in points.last().map(|point| point.holdings.clone()).unwrap_or_default() {
+        let marks = ticker_marks(holding.ticker.clone(), start, end, db_pool).await.map_err(internal_error)?;
+        tickers.push(json!({
+            "ticker": holding.ticker,
+            "metrics": risk::calculate(&marks, query.risk_free_rate, query.confidence),
+        }));
+    }
+    Ok(Json(json!({
+        "period": query.period,
+        "start": start,
+        "end": end,
+        "base": query.base,
+        "missing_fx": missing_fx,
+        "portfolio": risk::calculate(&portfolio, query.risk_free_rate, query.confidence),
+        "tickers": tickers,
+    })))
+}
+
+pub async fn get_ticker_risk(
+    State(app_state): State<Arc<AppState>>,
+    Path(ticker): Path<String>,
+    Query(query): Query<RiskQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let end = query.end.unwrap_or(chrono::Utc::now().date_naive());
+    let earliest = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+    let start = match query.period {
+        Some(period) => period.start(end, earliest),
+        None => query.start.unwrap_or(Period::OneYear.start(end, earliest)),
+    };
+    let marks = ticker_marks(ticker.clone(), start, end, &app_state.db_pool).await.map_err(internal_error)?;
+    Ok(Json(json!({
+        "ticker": ticker,
+        "start": start,
+        "end": end,
+        "metrics": risk::calculate(&marks, query.risk_free_rate, query.confidence),
+    })))
+}
+
+/// Covariance and correlation of daily total returns across the active tickers
+pub async fn get_correlation(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<CorrelationQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &app_state.db_pool;
+    let window = correlation::validate_window(query.window).map_err(bad_request)?;
+    let end = query.end.unwrap_or(chrono::Utc::now().date_naive());
+    // Enough calendar days to cover the window's trading days plus holidays
+    let start = end - Duration::days(window as i64 * 7 / 5 + 14);
+    let mut tickers = QuoteModel::get_active_tickers(db_pool).await.map_err(internal_error)?;
+    tickers.sort();
+    let mut series = Vec::new();
+    for ticker in tickers {
+        let marks = ticker_marks(ticker.clone(), start, end, db_pool).await.map_err(internal_error)?;
+        series.push((ticker, marks));
+    }
+    Ok(Json(json!(correlation::calculate(&series, window))))
+}
+
+pub fn build_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/risk", get(get_portfolio_risk))
+        .route("/risk/correlation", get(get_correlation))
+        .route("/risk/:ticker", get(get_ticker_risk))
+}
\ No newline at end of file
@@ -1,6 +1,7 @@
 use crate::{
     models::stocks::{StockModel, is_valid_ticker, valid_ticker},
     schema::stocks::{StockJson, ErrorJson, ErrorType},
+    handlers::{internal_error, bad_request},
     AppState,
 };
 use std::{sync::Arc, string};
@@ -14,7 +15,7 @@ pub async fn add_stock(
     State(app_state): State<Arc<AppState>>,
     Json(stock): Json<StockJson>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    valid_ticker(&stock.ticker).await.map_err(internal_error)?;
+    valid_ticker(&stock.ticker, app_state.market_data.as_ref()).await.map_err(bad_request)?;
     let stock = StockModel::new(stock.ticker.clone(), stock.amount_held);
     let result = stock.update_if_exists_or_create(&app_state.db_pool).await;
 
@@ -29,20 +30,6 @@ pub async fn add_stock(
     }
 }
 
-pub fn internal_error<E>(e: E) -> (StatusCode, Json<serde_json::Value>)
-where
-    E: std::error::Error,
-{
-    match e {
-        YahooError => {
-            (StatusCode::BAD_REQUEST, Json(json!({ "error": "Collecting from Yahoo Finance failed" })))
-        },
-        _ => {
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() })))
-        }
-    }
-}
-
 // #[post("/stocks")]
 // pub async fn add_stock(stock: web::Json<StockJson>, state: web::Data<AppState>) -> impl Responder {
 //     if !is_valid_ticker(&stock.ticker).await {
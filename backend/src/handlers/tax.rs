@@ -0,0 +1,142 @@
+use crate::{
+    AppState,
+    analytics::fx::FxTable,
+    analytics::tax::{self, au, ca, uk, us},
+    models::dividends::DividendModel,
+    models::fx::{Currency, FxRateModel},
+    models::trades::TradeModel,
+    schema::tax::{CgtQuery, ExportFormat, TaxReportQuery, TaxYearQuery},
+    handlers::{internal_error, bad_request},
+};
+use std::sync::Arc;
+use axum::Router;
+use axum::{routing::get, response::{IntoResponse, Response}, http::{header, StatusCode}};
+use axum::Json;
+use axum::extract::{Query, State};
+use chrono::{Datelike, NaiveDate};
+use serde_json::json;
+
+pub fn csv_response(filename: String, body: String) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        body,
+    ).into_response()
+}
+
+/// The year a tax year ends in for a tax year that starts on `month`/`day`
+fn current_tax_year(today: NaiveDate, month: u32, day: u32) -> i32 {
+    let start = NaiveDate::from_ymd_opt(today.year(), month, day).unwrap();
+    let next_start = match today >= start {
+        true => NaiveDate::from_ymd_opt(today.year() + 1, month, day).unwrap(),
+        false => start,
+    };
+    next_start.pred_opt().unwrap().year()
+}
+
+/// The requested tax year, or the current one when none is given
+fn tax_year(year: Option<i32>, month: u32, day: u32) -> Result<i32, (StatusCode, Json<serde_json::Value>)> {
+    match year {
+        Some(year) => tax::validate_year(year).map_err(bad_request),
+        None => Ok(current_tax_year(chrono::Utc::now().date_naive(), month, day)),
+    }
+}
+
+pub async fn get_au_cgt(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<CgtQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let year = tax_year(query.year, 7, 1)?;
+    let trades = TradeModel::get_all(&app_state.db_pool).await.map_err(internal_error)?;
+    let fx = FxTable::from_rates(FxRateModel::get_by_currency(Currency::AUD, &app_state.db_pool).await.map_err(internal_error)?);
+    let report = au::calculate(&trades, year, query.method, query.carried_forward_loss, &fx).map_err(bad_request)?;
+    match query.format {
+        ExportFormat::Json => Ok(Json(json!(report)).into_response()),
+        ExportFormat::Csv => Ok(csv_response(format!("cgt-au-{}.csv", report.financial_year.label), report.to_csv())),
+    }
+}
+
+pub async fn get_au_income(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<TaxReportQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let year = tax_year(query.year, 7, 1)?;
+    let dividends = DividendModel::get_all(&app_state.db_pool).await.map_err(internal_error)?;
+    let trades = TradeModel::get_all(&app_state.db_pool).await.map_err(internal_error)?;
+    let report = au::income(&dividends, &trades, year);
+    match query.format {
+        ExportFormat::Json => Ok(Json(json!(report)).into_response()),
+        ExportFormat::Csv => Ok(csv_response(format!("income-au-{}.csv", report.financial_year.label), report.to_csv())),
+    }
+}
+
+pub async fn get_uk_gains(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<TaxYearQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let year = tax_year(query.year, 4, 6)?;
+    let trades = TradeModel::get_all(&app_state.db_pool).await.map_err(internal_error)?;
+    Ok(Json(json!(uk::calculate(&trades, year))))
+}
+
+pub async fn get_ca_acb(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<TaxYearQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let year = tax_year(query.year, 1, 1)?;
+    let trades = TradeModel::get_all(&app_state.db_pool).await.map_err(internal_error)?;
+    Ok(Json(json!(ca::calculate(&trades, year))))
+}
+
+pub async fn get_us_tax_lots(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<TaxYearQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let year = tax_year(query.year, 1, 1)?;
+    let trades = TradeModel::get_all(&app_state.db_pool).await.map_err(internal_error)?;
+    Ok(Json(json!(us::calculate(&trades, year))))
+}
+
+pub fn build_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/tax/au/cgt", get(get_au_cgt))
+        .route("/tax/au/income", get(get_au_income))
+        .route("/tax/uk/gains", get(get_uk_gains))
+        .route("/tax/ca/acb", get(get_ca_acb))
+        .route("/tax/us/lots", get(get_us_tax_lots))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn au_tax_year_ends_in_june() {
+        assert_eq!(current_tax_year(date("2024-06-30"), 7, 1), 2024);
+        assert_eq!(current_tax_year(date("2024-07-01"), 7, 1), 2025);
+    }
+
+    #[test]
+    fn uk_tax_year_ends_in_april() {
+        assert_eq!(current_tax_year(date("2024-04-05"), 4, 6), 2024);
+        assert_eq!(current_tax_year(date("2024-04-06"), 4, 6), 2025);
+    }
+
+    #[test]
+    fn ca_tax_year_is_the_calendar_year() {
+        assert_eq!(current_tax_year(date("2024-01-01"), 1, 1), 2024);
+        assert_eq!(current_tax_year(date("2024-12-31"), 1, 1), 2024);
+    }
+
+    #[test]
+    fn us_tax_year_is_the_calendar_year() {
+        assert_eq!(current_tax_year(date("2023-12-31"), 1, 1), 2023);
+        assert_eq!(current_tax_year(date("2024-06-15"), 1, 1), 2024);
+    }
+}
@@ -9,11 +9,11 @@ use actix_web::{delete, get, patch, post, web, HttpResponse, Responder};
 
 #[post("/trades")]
 pub async fn add_trade(state: web::Data<AppState>, trade: web::Json<TradeJson>) -> impl Responder {
-    if !(is_valid_ticker(&trade.ticker).await) {
+    if !(is_valid_ticker(&trade.ticker, state.market_data.as_ref()).await) {
         return HttpResponse::BadRequest().body("Invalid ticker");
     }
     let result: TradeModel = trade.into_inner().into();
-    let result = result.insert(&state.db_pool).await;
+    let result = result.insert(state.market_data.clone(), &state.db_pool).await;
     match result {
         Ok(_) => HttpResponse::Ok().json(TradeJson::from(result.unwrap())),
         Err(_) => HttpResponse::InternalServerError().body("Failed to add trade")
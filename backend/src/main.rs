@@ -13,10 +13,15 @@ use serde_json::json;
 mod schema;
 mod handlers;
 mod models;
+mod market_data;
+mod analytics;
+mod scheduler;
 use models::quotes::QuoteModel;
+use market_data::MarketDataProvider;
 #[derive(Clone)]
 pub struct AppState {
     db_pool: sqlx::postgres::PgPool,
+    market_data: Arc<dyn MarketDataProvider>,
 }
 
 async fn index() -> &'static str {
@@ -73,7 +78,9 @@ async fn main() {
 
     let state = Arc::new(AppState {
         db_pool: db_pool.clone(),
+        market_data: market_data::from_env(),
     });
+    scheduler::start(db_pool.clone(), state.market_data.clone());
 
     // Build our application with a single route.
     let app = axum::Router::new()
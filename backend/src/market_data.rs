@@ -0,0 +1,67 @@
+pub mod yahoo;
+pub mod fixture;
+
+use std::fmt;
+use std::sync::Arc;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use crate::models::fx::Currency;
+
+/// A single daily OHLCV bar as returned by a market data provider.
+#[derive(Debug, Clone)]
+pub struct Bar {
+    pub date: NaiveDate,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: i64,
+}
+
+#[derive(Debug)]
+pub enum MarketDataError {
+    UnknownSymbol(String),
+    FetchFailed(String),
+}
+
+impl fmt::Display for MarketDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarketDataError::UnknownSymbol(ticker) => write!(f, "Ticker {} could not be found", ticker),
+            MarketDataError::FetchFailed(message) => write!(f, "Fetching market data failed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for MarketDataError {}
+
+/// Source of prices for the backend. Everything that needs market data goes through
+/// the provider held in `AppState` rather than talking to an API directly.
+#[async_trait]
+pub trait MarketDataProvider: Send + Sync {
+    /// Daily bars for `ticker` between `start` and `end` inclusive, oldest first.
+    async fn history(&self, ticker: &str, start: NaiveDate, end: NaiveDate) -> Result<Vec<Bar>, MarketDataError>;
+    /// The most recent bar available for `ticker`.
+    async fn latest_quote(&self, ticker: &str) -> Result<Bar, MarketDataError>;
+    async fn validate_symbol(&self, ticker: &str) -> Result<(), MarketDataError> {
+        self.latest_quote(ticker).await.map(|_| ())
+    }
+    /// Daily bars for one unit of `from` priced in `to`, using Yahoo style `AUDUSD=X` symbols
+    async fn fx_history(&self, from: Currency, to: Currency, start: NaiveDate, end: NaiveDate) -> Result<Vec<Bar>, MarketDataError> {
+        self.history(&format!("{}{}=X", from, to), start, end).await
+    }
+}
+
+/// Builds the provider selected by `MARKET_DATA_PROVIDER` (`yahoo` or `fixture`, defaults to `yahoo`).
+/// The fixture provider reads CSV files from `MARKET_DATA_FIXTURES` (defaults to `fixtures`).
+pub fn from_env() -> Arc<dyn MarketDataProvider> {
+    let provider = std::env::var("MARKET_DATA_PROVIDER").unwrap_or("yahoo".to_string());
+    match provider.to_lowercase().as_str() {
+        "fixture" | "offline" => {
+            let path = std::env::var("MARKET_DATA_FIXTURES").unwrap_or("fixtures".to_string());
+            println!("📂 Using fixture market data from {}", path);
+            Arc::new(fixture::FixtureProvider::new(path))
+        },
+        _ => Arc::new(yahoo::YahooProvider::new()),
+    }
+}
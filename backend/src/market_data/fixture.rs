@@ -0,0 +1,66 @@
+use std::path::PathBuf;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use super::{Bar, MarketDataError, MarketDataProvider};
+
+/// Offline provider backed by one CSV file per ticker, e.g. `fixtures/CBA.AX.csv`:
+/// ```text
+/// date,open,high,low,close,volume
+/// 2023-08-01,101.50,102.10,100.90,101.80,1520300
+/// ```
+pub struct FixtureProvider {
+    directory: PathBuf,
+}
+
+impl FixtureProvider {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+    fn load(&self, ticker: &str) -> Result<Vec<Bar>, MarketDataError> {
+        let path = self.directory.join(format!("{}.csv", ticker));
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|_| MarketDataError::UnknownSymbol(ticker.to_string()))?;
+        let mut bars = Vec::new();
+        for (line_number, line) in contents.lines().enumerate().skip(1) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let bar = parse_line(line).ok_or(MarketDataError::FetchFailed(
+                format!("{}:{} is not a valid bar", path.display(), line_number + 1)
+            ))?;
+            bars.push(bar);
+        }
+        bars.sort_by_key(|bar| bar.date);
+        Ok(bars)
+    }
+}
+
+fn parse_line(line: &str) -> Option<Bar> {
+    let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+    if fields.len() < 6 {
+        return None;
+    }
+    Some(Bar {
+        date: NaiveDate::parse_from_str(fields[0], "%Y-%m-%d").ok()?,
+        open: fields[1].parse().ok()?,
+        high: fields[2].parse().ok()?,
+        low: fields[3].parse().ok()?,
+        close: fields[4].parse().ok()?,
+        volume: fields[5].parse().ok()?,
+    })
+}
+
+#[async_trait]
+impl MarketDataProvider for FixtureProvider {
+    async fn history(&self, ticker: &str, start: NaiveDate, end: NaiveDate) -> Result<Vec<Bar>, MarketDataError> {
+        let bars = self.load(ticker)?;
+        Ok(bars.into_iter().filter(|bar| bar.date >= start && bar.date <= end).collect())
+    }
+    async fn latest_quote(&self, ticker: &str) -> Result<Bar, MarketDataError> {
+        self.load(ticker)?
+            .pop()
+            .ok_or(MarketDataError::UnknownSymbol(ticker.to_string()))
+    }
+}
@@ -0,0 +1,57 @@
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime};
+use yahoo_finance_api as yahoo;
+use yahoo::time::OffsetDateTime;
+use super::{Bar, MarketDataError, MarketDataProvider};
+
+pub struct YahooProvider {
+    connector: yahoo::YahooConnector,
+}
+
+impl YahooProvider {
+    pub fn new() -> Self {
+        Self {
+            connector: yahoo::YahooConnector::new(),
+        }
+    }
+}
+
+fn to_offset_datetime(date: NaiveDate) -> OffsetDateTime {
+    let timestamp = date.and_hms_opt(0, 0, 0).expect("Failed to convert datetime").timestamp();
+    OffsetDateTime::from_unix_timestamp(timestamp).unwrap_or(OffsetDateTime::UNIX_EPOCH)
+}
+
+fn to_bar(quote: &yahoo::Quote) -> Option<Bar> {
+    let date = NaiveDateTime::from_timestamp_opt(quote.timestamp as i64, 0)?;
+    Some(Bar {
+        date: date.date(),
+        open: quote.open,
+        high: quote.high,
+        low: quote.low,
+        close: quote.close,
+        volume: quote.volume as i64,
+    })
+}
+
+#[async_trait]
+impl MarketDataProvider for YahooProvider {
+    async fn history(&self, ticker: &str, start: NaiveDate, end: NaiveDate) -> Result<Vec<Bar>, MarketDataError> {
+        // Yahoo treats the end as exclusive, so ask for the day after to include `end`
+        let end = end + chrono::Duration::days(1);
+        let resp = self.connector.get_quote_history(ticker, to_offset_datetime(start), to_offset_datetime(end))
+            .await
+            .map_err(|e| MarketDataError::FetchFailed(format!("{:?}", e)))?;
+        let quotes = resp.quotes().unwrap_or_default();
+        Ok(quotes.iter().filter_map(to_bar).collect())
+    }
+    async fn latest_quote(&self, ticker: &str) -> Result<Bar, MarketDataError> {
+        let resp = self.connector.get_latest_quotes(ticker, "1d")
+            .await
+            .map_err(|_| MarketDataError::UnknownSymbol(ticker.to_string()))?;
+        resp.quotes()
+            .unwrap_or_default()
+            .last()
+            .and_then(to_bar)
+            .ok_or(MarketDataError::UnknownSymbol(ticker.to_string()))
+    }
+}
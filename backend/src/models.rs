@@ -1,6 +1,13 @@
 pub mod stocks;
 pub mod quotes;
 pub mod trades;
+pub mod fx;
+pub mod dividends;
+pub mod corporate_actions;
+pub mod benchmarks;
+pub mod allocations;
+pub mod markov;
+pub mod scenarios;
 
 use sqlx::postgres::PgPool;
 
@@ -8,5 +15,12 @@ pub async fn nuke_database(db_pool: &PgPool) -> Result<(), sqlx::Error> {
     stocks::StockModel::delete_all(db_pool).await?;
     quotes::QuoteModel::delete_all(db_pool).await?;
     trades::TradeModel::delete_all(db_pool).await?;
+    fx::FxRateModel::delete_all(db_pool).await?;
+    dividends::DividendModel::delete_all(db_pool).await?;
+    corporate_actions::CorporateActionModel::delete_all(db_pool).await?;
+    benchmarks::BenchmarkModel::delete_all(db_pool).await?;
+    allocations::TargetAllocationModel::delete_all(db_pool).await?;
+    markov::MarkovFitModel::delete_all(db_pool).await?;
+    scenarios::ScenarioModel::delete_all(db_pool).await?;
     Ok(())
 }
\ No newline at end of file
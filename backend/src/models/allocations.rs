@@ -0,0 +1,53 @@
+use sqlx;
+use sqlx::postgres::PgQueryResult;
+use sqlx::types::BigDecimal;
+
+/// Share of a portfolio's value that should be held in `ticker`, as a fraction
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct TargetAllocationModel {
+    pub id: i32,
+    pub portfolio: String,
+    pub ticker: String,
+    pub weight: BigDecimal,
+}
+
+impl TargetAllocationModel {
+    pub async fn get_by_portfolio(portfolio: String, db_pool: &sqlx::PgPool) -> Result<Vec<TargetAllocationModel>, sqlx::Error> {
+        sqlx::query_as!(
+            TargetAllocationModel,
+            r#"SELECT * FROM target_allocations WHERE portfolio = $1 ORDER BY ticker ASC"#,
+            portfolio
+        ).fetch_all(db_pool).await
+    }
+    /// Replaces every target of `portfolio` with `targets` in one transaction
+    pub async fn replace(portfolio: String, targets: &[TargetAllocationModel], db_pool: &sqlx::PgPool) -> Result<Vec<TargetAllocationModel>, sqlx::Error> {
+        let mut tx = db_pool.begin().await?;
+        sqlx::query!(
+            r#"DELETE FROM target_allocations WHERE portfolio = $1"#,
+            portfolio
+        ).execute(&mut *tx).await?;
+        let mut inserted = Vec::new();
+        for target in targets {
+            inserted.push(sqlx::query_as!(
+                TargetAllocationModel,
+                r#"INSERT INTO target_allocations (portfolio, ticker, weight) VALUES ($1, $2, $3) RETURNING *"#,
+                portfolio,
+                target.ticker,
+                target.weight
+            ).fetch_one(&mut *tx).await?);
+        }
+        tx.commit().await?;
+        Ok(inserted)
+    }
+    pub async fn delete_by_portfolio(portfolio: String, db_pool: &sqlx::PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            r#"DELETE FROM target_allocations WHERE portfolio = $1"#,
+            portfolio
+        ).execute(db_pool).await
+    }
+    pub async fn delete_all(db_pool: &sqlx::PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            r#"DELETE FROM target_allocations"#,
+        ).execute(db_pool).await
+    }
+}
\ No newline at end of file
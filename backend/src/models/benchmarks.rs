@@ -0,0 +1,54 @@
+use sqlx;
+use sqlx::postgres::PgQueryResult;
+use crate::market_data::MarketDataProvider;
+use crate::models::quotes::QuoteModel;
+
+/// A ticker the portfolio is compared against, kept populated whether or not it is held
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct BenchmarkModel {
+    pub id: i32,
+    pub ticker: String,
+    pub name: Option<String>,
+}
+
+impl BenchmarkModel {
+    pub async fn insert(&self, db_pool: &sqlx::PgPool) -> Result<BenchmarkModel, sqlx::Error> {
+        sqlx::query_as!(
+            BenchmarkModel,
+            r#"INSERT INTO benchmarks (ticker, name) VALUES ($1, $2)
+            ON CONFLICT (ticker) DO UPDATE SET name = EXCLUDED.name RETURNING *"#,
+            self.ticker,
+            self.name
+        ).fetch_one(db_pool).await
+    }
+    pub async fn delete_by_id(id: i32, db_pool: &sqlx::PgPool) -> Result<BenchmarkModel, sqlx::Error> {
+        sqlx::query_as!(
+            BenchmarkModel,
+            r#"DELETE FROM benchmarks WHERE id = $1 RETURNING *"#,
+            id
+        ).fetch_one(db_pool).await
+    }
+    pub async fn get_all(db_pool: &sqlx::PgPool) -> Result<Vec<BenchmarkModel>, sqlx::Error> {
+        sqlx::query_as!(
+            BenchmarkModel,
+            r#"SELECT * FROM benchmarks ORDER BY ticker ASC"#
+        ).fetch_all(db_pool).await
+    }
+    pub async fn delete_all(db_pool: &sqlx::PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            r#"DELETE FROM benchmarks"#,
+        ).execute(db_pool).await
+    }
+    pub async fn update_benchmarks(market_data: &dyn MarketDataProvider, db_pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        for benchmark in BenchmarkModel::get_all(db_pool).await? {
+            let result = QuoteModel::populate_ticker(benchmark.ticker.clone(), market_data, db_pool).await;
+            match result {
+                Ok(_) => {},
+                Err(err) => {
+                    println!("Error populating benchmark {}: {:?}", benchmark.ticker, err);
+                }
+            }
+        }
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,566 @@
+use std::fmt;
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx;
+use sqlx::types::BigDecimal;
+use bigdecimal::Zero;
+use strum_macros::{EnumString, Display};
+use serde::{Deserialize, Serialize};
+use crate::models::quotes::QuoteModel;
+use crate::models::trades::TradeType;
+
+#[derive(Serialize, Deserialize, Debug, EnumString, Display, Clone, Copy, PartialEq)]
+pub enum CorporateActionType {
+    Split,
+    ReverseSplit,
+    Consolidation,
+    TickerChange,
+    Merger,
+}
+
+impl std::convert::From<std::string::String> for CorporateActionType {
+    fn from(s: std::string::String) -> Self {
+        match s.to_lowercase().as_str() {
+            "reversesplit" => CorporateActionType::ReverseSplit,
+            "consolidation" => CorporateActionType::Consolidation,
+            "tickerchange" => CorporateActionType::TickerChange,
+            "merger" => CorporateActionType::Merger,
+            _ => CorporateActionType::Split,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CorporateActionError {
+    Invalid(String),
+    AlreadyApplied(i32),
+    NotApplied(i32),
+    /// A later action on the same ticker has to be undone first
+    LaterActionApplied(i32),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for CorporateActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorporateActionError::Invalid(message) => write!(f, "Invalid corporate action: {}", message),
+            CorporateActionError::AlreadyApplied(id) => write!(f, "Corporate action {} has already been applied", id),
+            CorporateActionError::NotApplied(id) => write!(f, "Corporate action {} has not been applied", id),
+            CorporateActionError::LaterActionApplied(id) => write!(f, "Corporate action {} was applied later and must be undone first", id),
+            CorporateActionError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CorporateActionError {}
+
+impl From<sqlx::Error> for CorporateActionError {
+    fn from(e: sqlx::Error) -> Self {
+        CorporateActionError::Database(e)
+    }
+}
+
+/// Number of rows an apply or undo changed in each table
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Adjustments {
+    pub quotes: u64,
+    pub trades: u64,
+    pub dividends: u64,
+    pub stocks: u64,
+}
+
+/// Holders of `ratio_from` shares of `ticker` end up with `ratio_to` shares of `new_ticker`
+/// (or `ticker` when it doesn't change) and `cash_per_share` for each share they held
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct CorporateActionModel {
+    pub id: i32,
+    pub ticker: String,
+    pub action_type: CorporateActionType,
+    pub effective_date: NaiveDate,
+    pub ratio_from: i32,
+    pub ratio_to: i32,
+    pub new_ticker: Option<String>,
+    pub cash_per_share: BigDecimal,
+    pub applied_at: Option<NaiveDateTime>,
+    /// Fraction of a new share the trades before the effective date came to, paid out as cash in lieu
+    pub fractional_shares: BigDecimal,
+    /// Value of the fractional share at the last close before the effective date, `None` without a close
+    pub cash_in_lieu: Option<BigDecimal>,
+}
+
+/// A trade dated before an action takes effect
+#[derive(Debug, sqlx::FromRow, Clone, PartialEq)]
+pub struct HeldTrade {
+    pub id: i32,
+    pub trade_type: TradeType,
+    pub amount: i32,
+    pub price: BigDecimal,
+}
+
+impl CorporateActionModel {
+    pub async fn insert(&self, db_pool: &sqlx::PgPool) -> Result<CorporateActionModel, sqlx::Error> {
+        sqlx::query_as!(
+            CorporateActionModel,
+            r#"INSERT INTO corporate_actions (ticker, action_type, effective_date, ratio_from, ratio_to, new_ticker, cash_per_share) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *"#,
+            self.ticker,
+            self.action_type.to_string(),
+            self.effective_date,
+            self.ratio_from,
+            self.ratio_to,
+            self.new_ticker,
+            self.cash_per_share
+        ).fetch_one(db_pool).await
+    }
+    pub async fn delete_by_id(id: i32, db_pool: &sqlx::PgPool) -> Result<CorporateActionModel, sqlx::Error> {
+        sqlx::query_as!(
+            CorporateActionModel,
+            r#"DELETE FROM corporate_actions WHERE id = $1 RETURNING *"#,
+            id
+        ).fetch_one(db_pool).await
+    }
+    pub async fn get_all(db_pool: &sqlx::PgPool) -> Result<Vec<CorporateActionModel>, sqlx::Error> {
+        sqlx::query_as!(
+            CorporateActionModel,
+            r#"SELECT * FROM corporate_actions ORDER BY effective_date DESC, id DESC"#
+        ).fetch_all(db_pool).await
+    }
+    pub async fn get_by_id(id: i32, db_pool: &sqlx::PgPool) -> Result<CorporateActionModel, sqlx::Error> {
+        sqlx::query_as!(
+            CorporateActionModel,
+            r#"SELECT * FROM corporate_actions WHERE id = $1"#,
+            id
+        ).fetch_one(db_pool).await
+    }
+    /// Applied actions on `ticker`, or that turned another ticker into it, oldest effective date first
+    pub async fn get_applied_by_ticker(ticker: String, db_pool: &sqlx::PgPool) -> Result<Vec<CorporateActionModel>, sqlx::Error> {
+        sqlx::query_as!(
+            CorporateActionModel,
+            r#"SELECT * FROM corporate_actions WHERE (ticker = $1 OR new_ticker = $1) AND applied_at IS NOT NULL ORDER BY effective_date ASC, id ASC"#,
+            ticker
+        ).fetch_all(db_pool).await
+    }
+    pub async fn delete_all(db_pool: &sqlx::PgPool) -> Result<sqlx::postgres::PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            r#"DELETE FROM corporate_actions"#,
+        ).execute(db_pool).await
+    }
+
+    /// Ticker the shares are held under once the action takes effect
+    pub fn resulting_ticker(&self) -> String {
+        self.new_ticker.clone().unwrap_or(self.ticker.clone())
+    }
+    /// New shares per old share
+    pub fn share_factor(&self) -> f64 {
+        self.ratio_to as f64 / self.ratio_from as f64
+    }
+    pub fn validate(&self) -> Result<(), CorporateActionError> {
+        if self.ratio_from <= 0 || self.ratio_to <= 0 {
+            return Err(CorporateActionError::Invalid("ratios must be positive".to_string()));
+        }
+        if self.cash_per_share < BigDecimal::zero() {
+            return Err(CorporateActionError::Invalid("cash per share can't be negative".to_string()));
+        }
+        let changes_ticker = self.new_ticker.as_ref().map(|new_ticker| *new_ticker != self.ticker).unwrap_or(false);
+        match self.action_type {
+            CorporateActionType::Split if self.ratio_to <= self.ratio_from => {
+                Err(CorporateActionError::Invalid("a split must increase the number of shares".to_string()))
+            },
+            CorporateActionType::ReverseSplit | CorporateActionType::Consolidation if self.ratio_to >= self.ratio_from => {
+                Err(CorporateActionError::Invalid("a reverse split or consolidation must reduce the number of shares".to_string()))
+            },
+            CorporateActionType::Split | CorporateActionType::ReverseSplit | CorporateActionType::Consolidation if changes_ticker || !self.cash_per_share.is_zero() => {
+                Err(CorporateActionError::Invalid("only ticker changes and mergers can change the ticker or pay cash".to_string()))
+            },
+            CorporateActionType::TickerChange | CorporateActionType::Merger if !changes_ticker => {
+                Err(CorporateActionError::Invalid("a new ticker is required".to_string()))
+            },
+            CorporateActionType::TickerChange if !self.cash_per_share.is_zero() => {
+                Err(CorporateActionError::Invalid("a ticker change can't pay cash".to_string()))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// New shares for `held` old shares, rounded down
+    fn new_shares(&self, held: i64) -> i64 {
+        (held * self.ratio_to as i64).div_euclid(self.ratio_from as i64)
+    }
+
+    /// Rescales `trades`, oldest first, into new shares. Every trade gets the shares the running
+    /// position gains or loses when it is rounded down, so the trades always add up to the rounded
+    /// position, the same as `stocks`. A buy or sell that rounds to nothing passes its cost or
+    /// proceeds on to the next one that doesn't. Returns the rescaled trades and the fraction of
+    /// a new share left over at the end.
+    pub fn rescale_trades(&self, trades: &[HeldTrade]) -> (Vec<HeldTrade>, BigDecimal) {
+        let mut position: i64 = 0;
+        let mut cost = BigDecimal::zero();
+        let mut proceeds = BigDecimal::zero();
+        let mut rescaled = Vec::new();
+        for trade in trades {
+            let before = self.new_shares(position);
+            let (pending, price) = match trade.trade_type {
+                TradeType::Buy => {
+                    position += trade.amount as i64;
+                    let price = match trade.price > self.cash_per_share {
+                        true => &trade.price - &self.cash_per_share,
+                        false => BigDecimal::zero(),
+                    };
+                    (&mut cost, price)
+                },
+                TradeType::Sell => {
+                    position -= trade.amount as i64;
+                    (&mut proceeds, trade.price.clone())
+                },
+            };
+            let amount = (self.new_shares(position) - before).abs();
+            *pending += &price * BigDecimal::from(trade.amount);
+            let price = match amount {
+                0 => price * BigDecimal::from(self.ratio_from) / BigDecimal::from(self.ratio_to),
+                _ => std::mem::take(pending) / BigDecimal::from(amount),
+            };
+            rescaled.push(HeldTrade {
+                id: trade.id,
+                trade_type: trade.trade_type,
+                amount: amount as i32,
+                price: price.round(6),
+            });
+        }
+        let fraction = BigDecimal::from((position * self.ratio_to as i64).rem_euclid(self.ratio_from as i64)) / BigDecimal::from(self.ratio_from);
+        (rescaled, fraction)
+    }
+
+    /// Dividend adjustments depend on both the closes and the dividends an action rescales
+    async fn refresh_adjusted_closes(&self, db_pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        QuoteModel::refresh_adjusted_close(self.ticker.clone(), db_pool).await?;
+        if self.resulting_ticker() != self.ticker {
+            QuoteModel::refresh_adjusted_close(self.resulting_ticker(), db_pool).await?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites everything dated before the effective date into post-action terms in one transaction,
+    /// keeping a copy of each row it changes so the action can be undone.
+    ///
+    /// Splits and consolidations rescale quotes, trades and dividends per share. Ticker changes and
+    /// mergers move trades, dividends and `stocks` onto the new ticker while quotes stay under the old
+    /// one, since the new ticker's history comes from the market data provider. Cash paid in a merger
+    /// reduces the cost of the buys, on the basis that the shares held on the effective date came from them.
+    /// Shares are rounded down as in `rescale_trades`, and the fraction left over is recorded with the
+    /// cash paid in lieu of it. `stocks` is left alone until the effective date has passed.
+    pub async fn apply(&self, db_pool: &sqlx::PgPool) -> Result<(CorporateActionModel, Adjustments), CorporateActionError> {
+        self.validate()?;
+        if self.applied_at.is_some() {
+            return Err(CorporateActionError::AlreadyApplied(self.id));
+        }
+        let new_ticker = self.resulting_ticker();
+        let mut adjustments = Adjustments::default();
+        let mut tx = db_pool.begin().await?;
+        let last_close = sqlx::query!(
+            r#"SELECT close FROM quotes WHERE ticker = $1 AND date < $2 ORDER BY date DESC LIMIT 1"#,
+            self.ticker,
+            self.effective_date
+        ).fetch_optional(&mut *tx).await?.map(|quote| quote.close);
+
+        if new_ticker == self.ticker {
+            sqlx::query!(
+                r#"INSERT INTO corporate_action_quotes (action_id, ticker, date, open, high, low, close, volume)
+                SELECT $1, ticker, date, open, high, low, close, volume FROM quotes WHERE ticker = $2 AND date < $3"#,
+                self.id,
+                self.ticker,
+                self.effective_date
+            ).execute(&mut *tx).await?;
+            adjustments.quotes = sqlx::query!(
+                r#"UPDATE quotes SET open = open * $3::INT / $4::INT, high = high * $3::INT / $4::INT, low = low * $3::INT / $4::INT,
+                close = close * $3::INT / $4::INT, volume = volume * $4::INT / $3::INT
+                WHERE ticker = $1 AND date < $2"#,
+                self.ticker,
+                self.effective_date,
+                self.ratio_from,
+                self.ratio_to
+            ).execute(&mut *tx).await?.rows_affected();
+        }
+
+        sqlx::query!(
+            r#"INSERT INTO corporate_action_trades (action_id, trade_id, ticker, amount, price)
+            SELECT $1, id, ticker, amount, price FROM trades_history WHERE ticker = $2 AND date < $3"#,
+            self.id,
+            self.ticker,
+            self.effective_date
+        ).execute(&mut *tx).await?;
+        let held = sqlx::query_as!(
+            HeldTrade,
+            r#"SELECT id, trade_type, amount, price FROM trades_history WHERE ticker = $1 AND date < $2 ORDER BY date ASC, id ASC"#,
+            self.ticker,
+            self.effective_date
+        ).fetch_all(&mut *tx).await?;
+        let (rescaled, fractional_shares) = self.rescale_trades(&held);
+        for trade in &rescaled {
+            adjustments.trades += sqlx::query!(
+                r#"UPDATE trades_history SET ticker = $1, amount = $2, price = $3 WHERE id = $4"#,
+                new_ticker,
+                trade.amount,
+                trade.price,
+                trade.id
+            ).execute(&mut *tx).await?.rows_affected();
+        }
+        let cash_in_lieu = match fractional_shares.is_zero() {
+            true => Some(BigDecimal::zero()),
+            false => last_close.map(|close| (&fractional_shares * close * BigDecimal::from(self.ratio_from) / BigDecimal::from(self.ratio_to)).round(6)),
+        };
+
+        // A dividend's entitlement counts the shares held before its ex-date, so it is in the
+        // rescaled units whenever the ex-date is on or before the effective date
+        sqlx::query!(
+            r#"INSERT INTO corporate_action_dividends (action_id, dividend_id, ticker, amount_per_share)
+            SELECT $1, id, ticker, amount_per_share FROM dividends WHERE ticker = $2 AND ex_date <= $3"#,
+            self.id,
+            self.ticker,
+            self.effective_date
+        ).execute(&mut *tx).await?;
+        adjustments.dividends = sqlx::query!(
+            r#"UPDATE dividends SET ticker = $3, amount_per_share = amount_per_share * $4::INT / $5::INT WHERE ticker = $1 AND ex_date <= $2"#,
+            self.ticker,
+            self.effective_date,
+            new_ticker,
+            self.ratio_from,
+            self.ratio_to
+        ).execute(&mut *tx).await?.rows_affected();
+
+        // `stocks` holds what is held today, so it waits for an action that hasn't taken effect yet
+        let today = chrono::Utc::now().naive_utc().date();
+        let stock = match self.effective_date <= today {
+            true => {
+                sqlx::query!(
+                    r#"INSERT INTO corporate_action_stocks (action_id, stock_id, ticker, amount_held, last_updated)
+                    SELECT $1, id, ticker, amount_held, last_updated FROM stocks WHERE ticker = $2 OR ticker = $3"#,
+                    self.id,
+                    self.ticker,
+                    new_ticker
+                ).execute(&mut *tx).await?;
+                sqlx::query!(
+                    r#"SELECT id, amount_held FROM stocks WHERE ticker = $1"#,
+                    self.ticker
+                ).fetch_optional(&mut *tx).await?
+            },
+            false => None,
+        };
+        if let Some(stock) = stock {
+            let amount_held = self.new_shares(stock.amount_held as i64) as i32;
+            let existing = match new_ticker == self.ticker {
+                true => None,
+                false => sqlx::query!(r#"SELECT id FROM stocks WHERE ticker = $1"#, new_ticker).fetch_optional(&mut *tx).await?,
+            };
+            match existing {
+                Some(existing) => {
+                    sqlx::query!(
+                        r#"UPDATE stocks SET amount_held = amount_held + $1, last_updated = $2 WHERE id = $3"#,
+                        amount_held,
+                        today,
+                        existing.id
+                    ).execute(&mut *tx).await?;
+                    sqlx::query!(r#"DELETE FROM stocks WHERE id = $1"#, stock.id).execute(&mut *tx).await?;
+                    adjustments.stocks = 2;
+                },
+                None => {
+                    sqlx::query!(
+                        r#"UPDATE stocks SET ticker = $1, amount_held = $2, last_updated = $3 WHERE id = $4"#,
+                        new_ticker,
+                        amount_held,
+                        today,
+                        stock.id
+                    ).execute(&mut *tx).await?;
+                    adjustments.stocks = 1;
+                }
+            }
+        }
+
+        let action = sqlx::query_as!(
+            CorporateActionModel,
+            r#"UPDATE corporate_actions SET applied_at = NOW(), fractional_shares = $2, cash_in_lieu = $3 WHERE id = $1 RETURNING *"#,
+            self.id,
+            fractional_shares,
+            cash_in_lieu
+        ).fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        self.refresh_adjusted_closes(db_pool).await?;
+        Ok((action, adjustments))
+    }
+
+    /// Puts back every row the action changed. Only the most recently applied action touching
+    /// either ticker can be undone, otherwise later adjustments would be lost.
+    pub async fn undo(&self, db_pool: &sqlx::PgPool) -> Result<(CorporateActionModel, Adjustments), CorporateActionError> {
+        let applied_at = self.applied_at.ok_or(CorporateActionError::NotApplied(self.id))?;
+        let new_ticker = self.resulting_ticker();
+        let later = sqlx::query!(
+            r#"SELECT id FROM corporate_actions WHERE id != $1 AND applied_at > $2
+            AND (ticker = $3 OR ticker = $4 OR new_ticker = $3 OR new_ticker = $4) ORDER BY applied_at DESC LIMIT 1"#,
+            self.id,
+            applied_at,
+            self.ticker,
+            new_ticker
+        ).fetch_optional(db_pool).await?;
+        if let Some(later) = later {
+            return Err(CorporateActionError::LaterActionApplied(later.id));
+        }
+        let mut adjustments = Adjustments::default();
+        let mut tx = db_pool.begin().await?;
+
+        adjustments.quotes = sqlx::query!(
+            r#"UPDATE quotes SET open = s.open, high = s.high, low = s.low, close = s.close, volume = s.volume
+            FROM corporate_action_quotes s WHERE s.action_id = $1 AND quotes.ticker = s.ticker AND quotes.date = s.date"#,
+            self.id
+        ).execute(&mut *tx).await?.rows_affected();
+        adjustments.trades = sqlx::query!(
+            r#"UPDATE trades_history SET ticker = s.ticker, amount = s.amount, price = s.price
+            FROM corporate_action_trades s WHERE s.action_id = $1 AND trades_history.id = s.trade_id"#,
+            self.id
+        ).execute(&mut *tx).await?.rows_affected();
+        adjustments.dividends = sqlx::query!(
+            r#"UPDATE dividends SET ticker = s.ticker, amount_per_share = s.amount_per_share
+            FROM corporate_action_dividends s WHERE s.action_id = $1 AND dividends.id = s.dividend_id"#,
+            self.id
+        ).execute(&mut *tx).await?.rows_affected();
+        sqlx::query!(
+            r#"DELETE FROM stocks WHERE id IN (SELECT stock_id FROM corporate_action_stocks WHERE action_id = $1)"#,
+            self.id
+        ).execute(&mut *tx).await?;
+        adjustments.stocks = sqlx::query!(
+            r#"INSERT INTO stocks (id, ticker, amount_held, last_updated)
+            SELECT stock_id, ticker, amount_held, last_updated FROM corporate_action_stocks WHERE action_id = $1"#,
+            self.id
+        ).execute(&mut *tx).await?.rows_affected();
+
+        sqlx::query!(r#"DELETE FROM corporate_action_quotes WHERE action_id = $1"#, self.id).execute(&mut *tx).await?;
+        sqlx::query!(r#"DELETE FROM corporate_action_trades WHERE action_id = $1"#, self.id).execute(&mut *tx).await?;
+        sqlx::query!(r#"DELETE FROM corporate_action_dividends WHERE action_id = $1"#, self.id).execute(&mut *tx).await?;
+        sqlx::query!(r#"DELETE FROM corporate_action_stocks WHERE action_id = $1"#, self.id).execute(&mut *tx).await?;
+        let action = sqlx::query_as!(
+            CorporateActionModel,
+            r#"UPDATE corporate_actions SET applied_at = NULL, fractional_shares = 0, cash_in_lieu = NULL WHERE id = $1 RETURNING *"#,
+            self.id
+        ).fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        self.refresh_adjusted_closes(db_pool).await?;
+        Ok((action, adjustments))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn decimal(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn action(action_type: CorporateActionType, ratio_from: i32, ratio_to: i32, cash_per_share: &str) -> CorporateActionModel {
+        CorporateActionModel {
+            id: -1,
+            ticker: "ZZCA".to_string(),
+            action_type,
+            effective_date: NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
+            ratio_from,
+            ratio_to,
+            new_ticker: None,
+            cash_per_share: decimal(cash_per_share),
+            applied_at: None,
+            fractional_shares: BigDecimal::zero(),
+            cash_in_lieu: None,
+        }
+    }
+
+    fn held(id: i32, trade_type: TradeType, amount: i32, price: &str) -> HeldTrade {
+        HeldTrade {
+            id,
+            trade_type,
+            amount,
+            price: decimal(price),
+        }
+    }
+
+    fn amounts_and_prices(trades: &[HeldTrade]) -> Vec<(i32, BigDecimal)> {
+        trades.iter().map(|trade| (trade.amount, trade.price.clone())).collect()
+    }
+
+    #[test]
+    fn consolidation_rounds_the_running_position_down() {
+        let trades = [held(1, TradeType::Buy, 5, "10"), held(2, TradeType::Buy, 5, "10"), held(3, TradeType::Buy, 5, "10")];
+        let (rescaled, fraction) = action(CorporateActionType::Consolidation, 10, 1, "0").rescale_trades(&trades);
+        // The first buy's cost moves onto the second, which completes a new share
+        assert_eq!(amounts_and_prices(&rescaled), vec![(0, decimal("100")), (1, decimal("100")), (0, decimal("100"))]);
+        assert_eq!(fraction, decimal("0.5"));
+    }
+
+    #[test]
+    fn split_keeps_the_cost_of_each_trade() {
+        let trades = [held(1, TradeType::Buy, 100, "10.01")];
+        let (rescaled, fraction) = action(CorporateActionType::Split, 1, 3, "0").rescale_trades(&trades);
+        assert_eq!(amounts_and_prices(&rescaled), vec![(300, decimal("3.336667"))]);
+        assert!(fraction.is_zero());
+    }
+
+    #[test]
+    fn sells_take_the_shares_the_position_loses() {
+        let trades = [held(1, TradeType::Buy, 15, "10"), held(2, TradeType::Sell, 10, "12")];
+        let (rescaled, fraction) = action(CorporateActionType::Consolidation, 10, 1, "0").rescale_trades(&trades);
+        // 1.5 new shares rounds down to 1, and selling 1 leaves the half share
+        assert_eq!(amounts_and_prices(&rescaled), vec![(1, decimal("150")), (1, decimal("120"))]);
+        assert_eq!(fraction, decimal("0.5"));
+    }
+
+    #[test]
+    fn merger_cash_comes_off_the_buys() {
+        let mut merger = action(CorporateActionType::Merger, 1, 2, "3");
+        merger.new_ticker = Some("ZZCB".to_string());
+        let trades = [held(1, TradeType::Buy, 10, "10"), held(2, TradeType::Buy, 10, "2"), held(3, TradeType::Sell, 5, "12")];
+        let (rescaled, _) = merger.rescale_trades(&trades);
+        assert_eq!(amounts_and_prices(&rescaled), vec![(20, decimal("3.5")), (20, decimal("0")), (10, decimal("6"))]);
+    }
+
+    /// Needs the database in DATABASE_URL, run with `cargo test -- --ignored`
+    #[tokio::test]
+    #[ignore]
+    async fn apply_and_undo_restore_every_row() {
+        let db_pool = sqlx::PgPool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
+        let clean_up = || async {
+            sqlx::query("DELETE FROM corporate_actions WHERE ticker = 'ZZCA'").execute(&db_pool).await.unwrap();
+            sqlx::query("DELETE FROM trades_history WHERE ticker = 'ZZCA'").execute(&db_pool).await.unwrap();
+            sqlx::query("DELETE FROM stocks WHERE ticker = 'ZZCA'").execute(&db_pool).await.unwrap();
+            sqlx::query("DELETE FROM quotes WHERE ticker = 'ZZCA'").execute(&db_pool).await.unwrap();
+        };
+        clean_up().await;
+        for date in ["2023-01-03", "2023-02-01", "2023-03-01"] {
+            sqlx::query("INSERT INTO trades_history (ticker, amount, date, country, price, trade_type, currency) VALUES ('ZZCA', 5, $1::TEXT::DATE, 'AU', 10, 'Buy', 'AUD')")
+                .bind(date).execute(&db_pool).await.unwrap();
+        }
+        sqlx::query("INSERT INTO stocks (ticker, amount_held, last_updated) VALUES ('ZZCA', 15, '2023-03-01')").execute(&db_pool).await.unwrap();
+        sqlx::query("INSERT INTO quotes (ticker, date, open, high, low, close, volume, currency, adjusted_close) VALUES ('ZZCA', '2023-05-31', 12, 12, 12, 12, 1000, 'AUD', 12)")
+            .execute(&db_pool).await.unwrap();
+        let action = action(CorporateActionType::Consolidation, 10, 1, "0").insert(&db_pool).await.unwrap();
+        let trades = || async {
+            sqlx::query_as::<_, (i32, BigDecimal)>("SELECT amount, price FROM trades_history WHERE ticker = 'ZZCA' ORDER BY date")
+                .fetch_all(&db_pool).await.unwrap()
+        };
+        let stock = || async {
+            sqlx::query_scalar::<_, i32>("SELECT amount_held FROM stocks WHERE ticker = 'ZZCA'").fetch_one(&db_pool).await.unwrap()
+        };
+        let before = trades().await;
+
+        let (applied, adjustments) = action.apply(&db_pool).await.unwrap();
+        assert_eq!(adjustments.trades, 3);
+        assert_eq!(trades().await.iter().map(|(amount, _)| *amount).sum::<i32>(), 1);
+        assert_eq!(stock().await, 1);
+        assert_eq!(applied.fractional_shares, decimal("0.5"));
+        // Half a new share is worth five old shares at 12
+        assert_eq!(applied.cash_in_lieu, Some(decimal("60")));
+        let close = sqlx::query_scalar::<_, BigDecimal>("SELECT close FROM quotes WHERE ticker = 'ZZCA'").fetch_one(&db_pool).await.unwrap();
+        assert_eq!(close, decimal("120"));
+
+        let (undone, _) = applied.undo(&db_pool).await.unwrap();
+        assert_eq!(trades().await, before);
+        assert_eq!(stock().await, 15);
+        assert!(undone.applied_at.is_none());
+        assert!(undone.cash_in_lieu.is_none());
+        clean_up().await;
+    }
+}
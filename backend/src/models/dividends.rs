@@ -0,0 +1,107 @@
+use chrono::NaiveDate;
+use sqlx;
+use sqlx::postgres::PgQueryResult;
+use sqlx::types::BigDecimal;
+use std::fmt;
+use crate::models::fx::Currency;
+
+#[derive(Debug)]
+pub enum DividendError {
+    Invalid(String),
+}
+
+impl fmt::Display for DividendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DividendError::Invalid(message) => write!(f, "Invalid dividend: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for DividendError {}
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct DividendModel {
+    pub id: i32,
+    pub ticker: String,
+    pub ex_date: NaiveDate,
+    pub pay_date: NaiveDate,
+    pub amount_per_share: BigDecimal,
+    pub withholding_rate: BigDecimal,
+    /// Percentage of the dividend that is franked, 0 to 100
+    pub franking_percent: BigDecimal,
+    pub company_tax_rate: BigDecimal,
+    pub currency: Currency,
+}
+
+impl DividendModel {
+    pub async fn insert(&self, db_pool: &sqlx::PgPool) -> Result<DividendModel, sqlx::Error> {
+        sqlx::query_as!(
+            DividendModel,
+            r#"INSERT INTO dividends (ticker, ex_date, pay_date, amount_per_share, withholding_rate, currency, franking_percent, company_tax_rate) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id, ticker, ex_date, pay_date, amount_per_share, withholding_rate, franking_percent, company_tax_rate, currency AS "currency: Currency""#,
+            self.ticker,
+            self.ex_date,
+            self.pay_date,
+            self.amount_per_share,
+            self.withholding_rate,
+            self.currency.to_string(),
+            self.franking_percent,
+            self.company_tax_rate
+        ).fetch_one(db_pool).await
+    }
+    pub async fn update(&self, db_pool: &sqlx::PgPool) -> Result<DividendModel, sqlx::Error> {
+        sqlx::query_as!(
+            DividendModel,
+            r#"UPDATE dividends SET ticker = $1, ex_date = $2, pay_date = $3, amount_per_share = $4, withholding_rate = $5, currency = $6, franking_percent = $7, company_tax_rate = $8 WHERE id = $9 RETURNING id, ticker, ex_date, pay_date, amount_per_share, withholding_rate, franking_percent, company_tax_rate, currency AS "currency: Currency""#,
+            self.ticker,
+            self.ex_date,
+            self.pay_date,
+            self.amount_per_share,
+            self.withholding_rate,
+            self.currency.to_string(),
+            self.franking_percent,
+            self.company_tax_rate,
+            self.id
+        ).fetch_one(db_pool).await
+    }
+    pub async fn delete_by_id(id: i32, db_pool: &sqlx::PgPool) -> Result<DividendModel, sqlx::Error> {
+        sqlx::query_as!(
+            DividendModel,
+            r#"DELETE FROM dividends WHERE id = $1 RETURNING id, ticker, ex_date, pay_date, amount_per_share, withholding_rate, franking_percent, company_tax_rate, currency AS "currency: Currency""#,
+            id
+        ).fetch_one(db_pool).await
+    }
+    pub async fn get_all(db_pool: &sqlx::PgPool) -> Result<Vec<DividendModel>, sqlx::Error> {
+        sqlx::query_as!(
+            DividendModel,
+            r#"SELECT id, ticker, ex_date, pay_date, amount_per_share, withholding_rate, franking_percent, company_tax_rate, currency AS "currency: Currency" FROM dividends ORDER BY ex_date DESC"#
+        ).fetch_all(db_pool).await
+    }
+    pub async fn get_by_id(id: i32, db_pool: &sqlx::PgPool) -> Result<DividendModel, sqlx::Error> {
+        sqlx::query_as!(
+            DividendModel,
+            r#"SELECT id, ticker, ex_date, pay_date, amount_per_share, withholding_rate, franking_percent, company_tax_rate, currency AS "currency: Currency" FROM dividends WHERE id = $1"#,
+            id
+        ).fetch_one(db_pool).await
+    }
+    pub async fn get_by_ticker(ticker: String, db_pool: &sqlx::PgPool) -> Result<Vec<DividendModel>, sqlx::Error> {
+        sqlx::query_as!(
+            DividendModel,
+            r#"SELECT id, ticker, ex_date, pay_date, amount_per_share, withholding_rate, franking_percent, company_tax_rate, currency AS "currency: Currency" FROM dividends WHERE ticker = $1 ORDER BY ex_date ASC"#,
+            ticker
+        ).fetch_all(db_pool).await
+    }
+    pub async fn get_pay_date_range(start: NaiveDate, end: NaiveDate, db_pool: &sqlx::PgPool) -> Result<Vec<DividendModel>, sqlx::Error> {
+        sqlx::query_as!(
+            DividendModel,
+            r#"SELECT id, ticker, ex_date, pay_date, amount_per_share, withholding_rate, franking_percent, company_tax_rate, currency AS "currency: Currency" FROM dividends WHERE pay_date >= $1 AND pay_date <= $2 ORDER BY pay_date ASC"#,
+            start,
+            end
+        ).fetch_all(db_pool).await
+    }
+    pub async fn delete_all(db_pool: &sqlx::PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            r#"DELETE FROM dividends"#,
+        ).execute(db_pool).await
+    }
+}
@@ -0,0 +1,150 @@
+use chrono::{NaiveDate, Utc};
+use sqlx;
+use sqlx::postgres::PgQueryResult;
+use sqlx::types::BigDecimal;
+use bigdecimal::FromPrimitive;
+use strum_macros::{EnumString, Display, EnumIter};
+use strum::IntoEnumIterator;
+use serde::{Deserialize, Serialize};
+use crate::market_data::MarketDataProvider;
+
+#[derive(Serialize, Deserialize, Debug, EnumString, Display, EnumIter, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Currency {
+    #[default]
+    AUD,
+    USD,
+    CAD,
+    GBP,
+}
+
+/// Currencies are stored as their code; an unknown code fails to decode rather than being guessed
+impl sqlx::Type<sqlx::Postgres> for Currency {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <String as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+    fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+        <String as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, sqlx::Postgres> for Currency {
+    fn decode(value: sqlx::postgres::PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let code = <&str as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
+        Ok(code.to_uppercase().parse()?)
+    }
+}
+
+impl Currency {
+    /// Currency a ticker is quoted in, worked out from its exchange suffix
+    pub fn from_ticker(ticker: &str) -> Currency {
+        match ticker.rsplit_once('.').map(|(_, suffix)| suffix.to_uppercase()) {
+            Some(suffix) if suffix == "AX" => Currency::AUD,
+            Some(suffix) if suffix == "TO" || suffix == "V" || suffix == "NE" => Currency::CAD,
+            Some(suffix) if suffix == "L" => Currency::GBP,
+            _ => Currency::USD,
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct FxRateModel {
+    pub from_currency: Currency,
+    pub to_currency: Currency,
+    pub date: NaiveDate,
+    pub rate: BigDecimal,
+}
+
+impl FxRateModel {
+    pub async fn insert(&self, db_pool: &sqlx::PgPool) -> Result<FxRateModel, sqlx::Error> {
+        sqlx::query_as!(
+            FxRateModel,
+            r#"INSERT INTO fx_rates ( from_currency, to_currency, date, rate ) VALUES ( $1, $2, $3, $4 )
+            ON CONFLICT ( from_currency, to_currency, date ) DO UPDATE SET rate = EXCLUDED.rate RETURNING from_currency AS "from_currency: Currency", to_currency AS "to_currency: Currency", date, rate"#,
+            self.from_currency.to_string(),
+            self.to_currency.to_string(),
+            self.date,
+            self.rate,
+        ).fetch_one(db_pool).await
+    }
+    pub async fn get_closest_date(from: Currency, to: Currency, date: NaiveDate, db_pool: &sqlx::PgPool) -> Result<FxRateModel, sqlx::Error> {
+        sqlx::query_as!(
+            FxRateModel,
+            r#"SELECT from_currency AS "from_currency: Currency", to_currency AS "to_currency: Currency", date, rate FROM fx_rates WHERE from_currency = $1 AND to_currency = $2 AND date <= $3 ORDER BY date DESC LIMIT 1"#,
+            from.to_string(),
+            to.to_string(),
+            date
+        ).fetch_one(db_pool).await
+    }
+    /// Every stored rate into or out of `currency`
+    pub async fn get_by_currency(currency: Currency, db_pool: &sqlx::PgPool) -> Result<Vec<FxRateModel>, sqlx::Error> {
+        sqlx::query_as!(
+            FxRateModel,
+            r#"SELECT from_currency AS "from_currency: Currency", to_currency AS "to_currency: Currency", date, rate FROM fx_rates WHERE from_currency = $1 OR to_currency = $1 ORDER BY date ASC"#,
+            currency.to_string()
+        ).fetch_all(db_pool).await
+    }
+    pub async fn delete_all(db_pool: &sqlx::PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            r#"DELETE FROM fx_rates"#,
+        ).execute(db_pool).await
+    }
+    pub async fn populate_pair(from: Currency, to: Currency, market_data: &dyn MarketDataProvider, db_pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        let end = Utc::now().date_naive();
+        let mut start = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        let latest = FxRateModel::get_closest_date(from, to, end, db_pool).await;
+        match latest {
+            Ok(latest) => {
+                start = latest.date + chrono::Duration::days(1);
+            },
+            Err(_) => {}
+        }
+        if start >= end {
+            println!("FX rate: {}{} is up to date", from, to);
+            return Ok(());
+        }
+        let result = market_data.fx_history(from, to, start, end).await;
+        match result {
+            Ok(bars) => {
+                let mut added = 0;
+                let mut errors = 0;
+                for bar in bars.iter() {
+                    let rate = FxRateModel {
+                        from_currency: from,
+                        to_currency: to,
+                        date: bar.date,
+                        rate: BigDecimal::from_f64(bar.close).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
+                    };
+                    match rate.insert(db_pool).await {
+                        Ok(_) => {
+                            added += 1;
+                        },
+                        Err(_) => {
+                            errors += 1;
+                        }
+                    }
+                }
+                println!("FX Rates Added: {}, Errors: {}", added, errors);
+                Ok(())
+            },
+            Err(err) => {
+                println!("Error fetching FX rates: {:?}", err);
+                Err(sqlx::Error::RowNotFound)
+            }
+        }
+    }
+    /// Keeps a rate from every currency into every other supported currency up to date
+    pub async fn update_rates(market_data: &dyn MarketDataProvider, db_pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        for from in Currency::iter() {
+            for to in Currency::iter().filter(|to| *to != from) {
+                let result = FxRateModel::populate_pair(from, to, market_data, db_pool).await;
+                match result {
+                    Ok(_) => {},
+                    Err(err) => {
+                        println!("Error populating {}{}: {:?}", from, to, err);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx;
+use sqlx::postgres::PgQueryResult;
+
+/// A fitted Markov chain of daily return buckets, see `analytics::markov::MarkovFit`
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct MarkovFitModel {
+    pub id: i32,
+    pub tickers: Vec<String>,
+    pub buckets: i32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    /// Tickers x buckets x buckets, row major
+    pub transitions: Vec<f64>,
+    /// Tickers x buckets, row major
+    pub bucket_returns: Vec<f64>,
+    pub initial_states: Vec<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+impl MarkovFitModel {
+    pub async fn insert(&self, db_pool: &sqlx::PgPool) -> Result<MarkovFitModel, sqlx::Error> {
+        sqlx::query_as!(
+            MarkovFitModel,
+            r#"INSERT INTO markov_models (tickers, buckets, start_date, end_date, transitions, bucket_returns, initial_states)
+            VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *"#,
+            &self.tickers,
+            self.buckets,
+            self.start_date,
+            self.end_date,
+            &self.transitions,
+            &self.bucket_returns,
+            &self.initial_states
+        ).fetch_one(db_pool).await
+    }
+    pub async fn delete_by_id(id: i32, db_pool: &sqlx::PgPool) -> Result<MarkovFitModel, sqlx::Error> {
+        sqlx::query_as!(
+            MarkovFitModel,
+            r#"DELETE FROM markov_models WHERE id = $1 RETURNING *"#,
+            id
+        ).fetch_one(db_pool).await
+    }
+    pub async fn get_all(db_pool: &sqlx::PgPool) -> Result<Vec<MarkovFitModel>, sqlx::Error> {
+        sqlx::query_as!(
+            MarkovFitModel,
+            r#"SELECT * FROM markov_models ORDER BY created_at DESC"#
+        ).fetch_all(db_pool).await
+    }
+    pub async fn get_by_id(id: i32, db_pool: &sqlx::PgPool) -> Result<MarkovFitModel, sqlx::Error> {
+        sqlx::query_as!(
+            MarkovFitModel,
+            r#"SELECT * FROM markov_models WHERE id = $1"#,
+            id
+        ).fetch_one(db_pool).await
+    }
+    pub async fn delete_all(db_pool: &sqlx::PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            r#"DELETE FROM markov_models"#,
+        ).execute(db_pool).await
+    }
+}
\ No newline at end of file
@@ -1,10 +1,13 @@
-use chrono::{NaiveDate, NaiveDateTime, Utc};
+use chrono::{NaiveDate, Utc};
 use sqlx;
 use sqlx::postgres::PgQueryResult;
 use sqlx::types::BigDecimal;
-use yahoo_finance_api::time::OffsetDateTime;
-use yahoo_finance_api::time::macros::datetime;
 use bigdecimal::FromPrimitive;
+use crate::analytics::prices::{self, PriceAdjustment};
+use crate::market_data::{Bar, MarketDataProvider};
+use crate::models::corporate_actions::CorporateActionModel;
+use crate::models::dividends::DividendModel;
+use crate::models::fx::Currency;
 use crate::schema::Pagination;
 #[derive(Debug, sqlx::FromRow, Clone)]
 pub struct QuoteModel {
@@ -15,6 +18,9 @@ pub struct QuoteModel {
     pub low: BigDecimal,
     pub close: BigDecimal,
     pub volume: i64,
+    pub currency: Currency,
+    /// Close adjusted for dividends paid since, see `refresh_adjusted_close`
+    pub adjusted_close: BigDecimal,
 }
 
 impl QuoteModel {
@@ -28,7 +34,7 @@ impl QuoteModel {
         }
         sqlx::query_as!(
             QuoteModel,
-            r#"INSERT INTO quotes ( ticker, date, open, high, low, close, volume ) VALUES ( $1, $2, $3, $4, $5, $6, $7 ) RETURNING *"#,
+            r#"INSERT INTO quotes ( ticker, date, open, high, low, close, volume, currency, adjusted_close ) VALUES ( $1, $2, $3, $4, $5, $6, $7, $8, $9 ) RETURNING ticker, date, open, high, low, close, volume, currency AS "currency: Currency", adjusted_close"#,
             self.ticker,
             self.date,
             self.open,
@@ -36,51 +42,52 @@ impl QuoteModel {
             self.low,
             self.close,
             self.volume,
+            self.currency.to_string(),
+            self.adjusted_close,
         ).fetch_one(db_pool).await
     }
     pub async fn delete(&self, db_pool: &sqlx::PgPool) -> Result<QuoteModel, sqlx::Error> {
         sqlx::query_as!(
             QuoteModel,
-            r#"DELETE FROM quotes WHERE ( ticker = $1 AND date = $2 ) RETURNING *"#,
+            r#"DELETE FROM quotes WHERE ( ticker = $1 AND date = $2 ) RETURNING ticker, date, open, high, low, close, volume, currency AS "currency: Currency", adjusted_close"#,
             self.ticker,
             self.date
         ).fetch_one(db_pool).await
     }
-    pub async fn populate_ticker(ticker: String, db_pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
-        let end = OffsetDateTime::now_utc();
-        let mut start = datetime!(1970-01-01 0:00 UTC);
-        let latest = QuoteModel::get_closest_date(ticker.clone(), Utc::now().date_naive(), db_pool).await;
+    pub fn from_bar(ticker: String, bar: &Bar) -> QuoteModel {
+        QuoteModel {
+            currency: Currency::from_ticker(&ticker),
+            ticker,
+            date: bar.date,
+            open: BigDecimal::from_f64(bar.open).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
+            high: BigDecimal::from_f64(bar.high).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
+            low: BigDecimal::from_f64(bar.low).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
+            close: BigDecimal::from_f64(bar.close).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
+            adjusted_close: BigDecimal::from_f64(bar.close).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
+            volume: bar.volume,
+        }
+    }
+    pub async fn populate_ticker(ticker: String, market_data: &dyn MarketDataProvider, db_pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        let end = Utc::now().date_naive();
+        let mut start = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+        let latest = QuoteModel::get_closest_date(ticker.clone(), end, db_pool).await;
         match latest {
             Ok(latest) => {
-                let new_start = latest.date + chrono::Duration::days(1);
-                start = OffsetDateTime::from_unix_timestamp(new_start.and_hms_opt(0, 0, 0).expect("Failed to convert datetime").timestamp() as i64).unwrap_or(start);
+                start = latest.date + chrono::Duration::days(1);
             },
             Err(_) => {}
         }
-        if start.date() >= end.date() {
+        if start >= end {
             println!("Ticker: {} is up to date", ticker);
             return Ok(());
         }
-        let result = yahoo_finance_api::YahooConnector::new().get_quote_history(&ticker, start, end).await;
+        let result = market_data.history(&ticker, start, end).await;
         match result {
-            Ok(result) => {
-                let quotes = result.quotes();
+            Ok(bars) => {
                 let mut added = 0;
                 let mut errors = 0;
-                for quote in quotes.unwrap_or_default() {
-                    let date = NaiveDateTime::from_timestamp_opt(quote.timestamp as i64, 0);
-                    if date.is_none() {
-                        continue;
-                    }
-                    let quote = QuoteModel {
-                        ticker: ticker.clone(),
-                        date: date.unwrap().date(),
-                        open: BigDecimal::from_f64(quote.open).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
-                        high: BigDecimal::from_f64(quote.high).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
-                        low: BigDecimal::from_f64(quote.low).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
-                        close: BigDecimal::from_f64(quote.close).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
-                        volume: quote.volume as i64,
-                    };
+                for bar in bars.iter() {
+                    let quote = QuoteModel::from_bar(ticker.clone(), bar);
                     let result = quote.insert(db_pool).await;
                     match result {
                         Ok(_) => {
@@ -92,10 +99,13 @@ impl QuoteModel {
                     }
                 }
                 println!("Quotes Added: {}, Errors: {}", added, errors);
+                if added > 0 {
+                    QuoteModel::refresh_adjusted_close(ticker, db_pool).await?;
+                }
                 return Ok(());
             },
             Err(err) => {
-                println!("Error with Yahoo: {:?}", err);
+                println!("Error fetching market data: {:?}", err);
                 return Err(sqlx::Error::RowNotFound);
             }
         }
@@ -103,7 +113,7 @@ impl QuoteModel {
     pub async fn update(&self, db_pool: &sqlx::PgPool) -> Result<QuoteModel, sqlx::Error> {
         sqlx::query_as!(
             QuoteModel,
-            r#"UPDATE quotes SET open = $3, high = $4, low = $5, close = $6, volume = $7 WHERE ( ticker = $1 AND date = $2 ) RETURNING *"#,
+            r#"UPDATE quotes SET open = $3, high = $4, low = $5, close = $6, volume = $7, currency = $8, adjusted_close = $9 WHERE ( ticker = $1 AND date = $2 ) RETURNING ticker, date, open, high, low, close, volume, currency AS "currency: Currency", adjusted_close"#,
             self.ticker,
             self.date,
             self.open,
@@ -111,6 +121,8 @@ impl QuoteModel {
             self.low,
             self.close,
             self.volume,
+            self.currency.to_string(),
+            self.adjusted_close,
         ).fetch_one(db_pool).await
     }
     pub async fn get_tickers(db_pool: &sqlx::PgPool) -> Result<Vec<String>, sqlx::Error> {
@@ -127,7 +139,7 @@ impl QuoteModel {
         println!("Page: {:?}", page);
         sqlx::query_as!(
             QuoteModel,
-            r#"SELECT * FROM quotes ORDER BY date DESC LIMIT $1 OFFSET $2"#,
+            r#"SELECT ticker, date, open, high, low, close, volume, currency AS "currency: Currency", adjusted_close FROM quotes ORDER BY date DESC LIMIT $1 OFFSET $2"#,
             page.page_size,
             page.page_size * page.page
         ).fetch_all(db_pool).await
@@ -135,24 +147,52 @@ impl QuoteModel {
     pub async fn get_by_ticker_date(ticker: String, date: NaiveDate, db_pool: &sqlx::PgPool) -> Result<QuoteModel, sqlx::Error> {
         sqlx::query_as!(
             QuoteModel,
-            r#"SELECT * FROM quotes WHERE ticker = $1 AND date = $2"#,
+            r#"SELECT ticker, date, open, high, low, close, volume, currency AS "currency: Currency", adjusted_close FROM quotes WHERE ticker = $1 AND date = $2"#,
             ticker,
             date
         ).fetch_one(db_pool).await
     }
-    pub async fn get_date_range(ticker: String, start: NaiveDate, end: NaiveDate, db_pool: &sqlx::PgPool) -> Result<Vec<QuoteModel>, sqlx::Error> {
-        sqlx::query_as!(
+    /// Quotes for `ticker` between `start` and `end` inclusive, oldest first, adjusted as requested
+    pub async fn get_date_range(ticker: String, start: NaiveDate, end: NaiveDate, adjustment: PriceAdjustment, db_pool: &sqlx::PgPool) -> Result<Vec<QuoteModel>, sqlx::Error> {
+        let quotes = sqlx::query_as!(
             QuoteModel,
-            r#"SELECT * FROM quotes WHERE ticker = $1 AND date >= $2 AND date <= $3"#,
+            r#"SELECT ticker, date, open, high, low, close, volume, currency AS "currency: Currency", adjusted_close FROM quotes WHERE ticker = $1 AND date >= $2 AND date <= $3 ORDER BY date ASC"#,
             ticker,
             start,
             end
-        ).fetch_all(db_pool).await
+        ).fetch_all(db_pool).await?;
+        let actions = match adjustment {
+            PriceAdjustment::Raw => CorporateActionModel::get_applied_by_ticker(ticker, db_pool).await?,
+            _ => Vec::new(),
+        };
+        Ok(prices::adjust(quotes, adjustment, &actions))
+    }
+    /// Recalculates the dividend adjusted close for every stored quote of `ticker`
+    pub async fn refresh_adjusted_close(ticker: String, db_pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        let quotes = sqlx::query_as!(
+            QuoteModel,
+            r#"SELECT ticker, date, open, high, low, close, volume, currency AS "currency: Currency", adjusted_close FROM quotes WHERE ticker = $1 ORDER BY date ASC"#,
+            ticker
+        ).fetch_all(db_pool).await?;
+        let dividends = DividendModel::get_by_ticker(ticker.clone(), db_pool).await?;
+        let dates: Vec<NaiveDate> = quotes.iter().map(|quote| quote.date).collect();
+        let adjusted_closes: Vec<BigDecimal> = prices::dividend_adjusted_closes(&quotes, &dividends)
+            .into_iter()
+            .map(|close| BigDecimal::from_f64(close).unwrap_or(BigDecimal::from_f64(0.0).unwrap()))
+            .collect();
+        sqlx::query!(
+            r#"UPDATE quotes SET adjusted_close = data.adjusted_close FROM UNNEST($2::DATE[], $3::NUMERIC[]) AS data(date, adjusted_close)
+            WHERE quotes.ticker = $1 AND quotes.date = data.date"#,
+            ticker,
+            &dates,
+            &adjusted_closes
+        ).execute(db_pool).await?;
+        Ok(())
     }
     pub async fn get_all_date_range(start: NaiveDate, end: NaiveDate, db_pool: &sqlx::PgPool) -> Result<Vec<QuoteModel>, sqlx::Error> {
         sqlx::query_as!(
             QuoteModel,
-            r#"SELECT * FROM quotes WHERE date >= $1 AND date <= $2"#,
+            r#"SELECT ticker, date, open, high, low, close, volume, currency AS "currency: Currency", adjusted_close FROM quotes WHERE date >= $1 AND date <= $2"#,
             start,
             end
         ).fetch_all(db_pool).await
@@ -160,7 +200,7 @@ impl QuoteModel {
     pub async fn get_closest_date(ticker: String, date: NaiveDate, db_pool: &sqlx::PgPool) -> Result<QuoteModel, sqlx::Error> {
         sqlx::query_as!(
             QuoteModel,
-            r#"SELECT * FROM quotes WHERE ticker = $1 AND date <= $2 ORDER BY date DESC LIMIT 1"#,
+            r#"SELECT ticker, date, open, high, low, close, volume, currency AS "currency: Currency", adjusted_close FROM quotes WHERE ticker = $1 AND date <= $2 ORDER BY date DESC LIMIT 1"#,
             ticker,
             date
         ).fetch_one(db_pool).await
@@ -170,12 +210,12 @@ impl QuoteModel {
             r#"DELETE FROM quotes"#,
         ).execute(db_pool).await
     }
-    pub async fn update_quotes(db_pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+    pub async fn update_quotes(market_data: &dyn MarketDataProvider, db_pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
         let tickers = QuoteModel::get_tickers(db_pool).await;
         match tickers {
             Ok(tickers) => {
                 for ticker in tickers {
-                    let result = QuoteModel::populate_ticker(ticker.clone(), db_pool).await;
+                    let result = QuoteModel::populate_ticker(ticker.clone(), market_data, db_pool).await;
                     match result {
                         Ok(_) => {},
                         Err(err) => {
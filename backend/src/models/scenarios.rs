@@ -0,0 +1,109 @@
+use chrono::NaiveDateTime;
+use sqlx;
+use sqlx::postgres::PgQueryResult;
+
+/// A saved modelling scenario, `config` is the JSON of `schema::modelling::ScenarioConfig`
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct ScenarioModel {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    pub config: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// One run of a scenario, `result` is the JSON the run returned
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct ScenarioResultModel {
+    pub id: i32,
+    pub scenario_id: i32,
+    pub result: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl ScenarioModel {
+    pub async fn insert(&self, db_pool: &sqlx::PgPool) -> Result<ScenarioModel, sqlx::Error> {
+        sqlx::query_as!(
+            ScenarioModel,
+            r#"INSERT INTO model_scenarios (name, description, config) VALUES ($1, $2, $3::TEXT::JSONB)
+            RETURNING id, name, description, config::TEXT AS "config!", created_at"#,
+            self.name,
+            self.description,
+            self.config
+        ).fetch_one(db_pool).await
+    }
+    /// Replaces the scenario and deletes its results in one transaction, since they were run from
+    /// the config being replaced
+    pub async fn update(&self, db_pool: &sqlx::PgPool) -> Result<ScenarioModel, sqlx::Error> {
+        let mut tx = db_pool.begin().await?;
+        let scenario = sqlx::query_as!(
+            ScenarioModel,
+            r#"UPDATE model_scenarios SET name = $1, description = $2, config = $3::TEXT::JSONB WHERE id = $4
+            RETURNING id, name, description, config::TEXT AS "config!", created_at"#,
+            self.name,
+            self.description,
+            self.config,
+            self.id
+        ).fetch_one(&mut *tx).await?;
+        sqlx::query!(
+            r#"DELETE FROM model_results WHERE scenario_id = $1"#,
+            self.id
+        ).execute(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(scenario)
+    }
+    pub async fn delete_by_id(id: i32, db_pool: &sqlx::PgPool) -> Result<ScenarioModel, sqlx::Error> {
+        sqlx::query_as!(
+            ScenarioModel,
+            r#"DELETE FROM model_scenarios WHERE id = $1
+            RETURNING id, name, description, config::TEXT AS "config!", created_at"#,
+            id
+        ).fetch_one(db_pool).await
+    }
+    pub async fn get_all(db_pool: &sqlx::PgPool) -> Result<Vec<ScenarioModel>, sqlx::Error> {
+        sqlx::query_as!(
+            ScenarioModel,
+            r#"SELECT id, name, description, config::TEXT AS "config!", created_at FROM model_scenarios ORDER BY created_at DESC"#
+        ).fetch_all(db_pool).await
+    }
+    pub async fn get_by_id(id: i32, db_pool: &sqlx::PgPool) -> Result<ScenarioModel, sqlx::Error> {
+        sqlx::query_as!(
+            ScenarioModel,
+            r#"SELECT id, name, description, config::TEXT AS "config!", created_at FROM model_scenarios WHERE id = $1"#,
+            id
+        ).fetch_one(db_pool).await
+    }
+    /// Deletes every scenario along with its results
+    pub async fn delete_all(db_pool: &sqlx::PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            r#"DELETE FROM model_scenarios"#,
+        ).execute(db_pool).await
+    }
+}
+
+impl ScenarioResultModel {
+    pub async fn insert(scenario_id: i32, result: String, db_pool: &sqlx::PgPool) -> Result<ScenarioResultModel, sqlx::Error> {
+        sqlx::query_as!(
+            ScenarioResultModel,
+            r#"INSERT INTO model_results (scenario_id, result) VALUES ($1, $2::TEXT::JSONB)
+            RETURNING id, scenario_id, result::TEXT AS "result!", created_at"#,
+            scenario_id,
+            result
+        ).fetch_one(db_pool).await
+    }
+    pub async fn get_by_scenario(scenario_id: i32, db_pool: &sqlx::PgPool) -> Result<Vec<ScenarioResultModel>, sqlx::Error> {
+        sqlx::query_as!(
+            ScenarioResultModel,
+            r#"SELECT id, scenario_id, result::TEXT AS "result!", created_at FROM model_results WHERE scenario_id = $1 ORDER BY created_at DESC, id DESC"#,
+            scenario_id
+        ).fetch_all(db_pool).await
+    }
+    /// Most recent run of the scenario
+    pub async fn get_latest(scenario_id: i32, db_pool: &sqlx::PgPool) -> Result<ScenarioResultModel, sqlx::Error> {
+        sqlx::query_as!(
+            ScenarioResultModel,
+            r#"SELECT id, scenario_id, result::TEXT AS "result!", created_at FROM model_results WHERE scenario_id = $1 ORDER BY created_at DESC, id DESC LIMIT 1"#,
+            scenario_id
+        ).fetch_one(db_pool).await
+    }
+}
\ No newline at end of file
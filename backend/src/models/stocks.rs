@@ -2,9 +2,8 @@ use serde::{Deserialize, Serialize};
 use chrono::NaiveDate;
 use sqlx;
 use sqlx::postgres::PgQueryResult;
-use yahoo::YahooError;
+use crate::market_data::{MarketDataError, MarketDataProvider};
 use crate::schema::stocks::StockJson;
-use yahoo_finance_api as yahoo;
 #[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
 pub struct StockModel {
     pub id: i32,
@@ -114,23 +113,10 @@ impl StockModel {
     }
 }
 
-pub async fn is_valid_ticker(ticker: &str) -> bool {
-    let provider = yahoo::YahooConnector::new();
-    let resp = provider.get_latest_quotes(ticker, "1d").await;
-    match resp {
-        Ok(resp) => resp.quotes().unwrap_or_default().len() > 0,
-        Err(_) => false
-    }
+pub async fn is_valid_ticker(ticker: &str, market_data: &dyn MarketDataProvider) -> bool {
+    market_data.validate_symbol(ticker).await.is_ok()
 }
 
-pub async fn valid_ticker(ticker: &str) -> Result<(), YahooError> {
-    let provider = yahoo::YahooConnector::new();
-    let resp = provider.get_latest_quotes(ticker, "1d")
-        .await
-        .map_err(|_| YahooError::FetchFailed("Ticker could not be found on yahoo finance".to_string()))?;
-    if resp.quotes().unwrap_or_default().len() > 0 {
-        Ok(())
-    } else {
-        Err(YahooError::FetchFailed("Ticker could not be found on yahoo finance".to_string()))
-    }
+pub async fn valid_ticker(ticker: &str, market_data: &dyn MarketDataProvider) -> Result<(), MarketDataError> {
+    market_data.validate_symbol(ticker).await
 }
\ No newline at end of file
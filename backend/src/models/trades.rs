@@ -6,6 +6,9 @@ use strum_macros::{EnumString, Display};
 use serde::{Deserialize, Serialize};
 use crate::models::quotes::QuoteModel;
 use crate::models::stocks::StockModel;
+use crate::models::fx::Currency;
+use crate::market_data::MarketDataProvider;
+use std::sync::Arc;
 use tokio::spawn;
 #[derive(Debug, sqlx::FromRow, Clone)]
 pub struct TradeModel {
@@ -16,13 +19,14 @@ pub struct TradeModel {
     pub country: Country,
     pub price: BigDecimal,
     pub trade_type: TradeType,
+    pub currency: Currency,
 }
-#[derive(Serialize, Deserialize, Debug, EnumString, Display, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, EnumString, Display, Clone, Copy, PartialEq)]
 pub enum TradeType {
     Buy,
     Sell,
 }
-#[derive(Serialize, Deserialize, Debug, EnumString, Display, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, EnumString, Display, Clone, Copy, PartialEq)]
 pub enum Country {
     US,
     CA,
@@ -41,6 +45,17 @@ impl std::convert::From<std::string::String> for Country {
         }
     }
 }
+impl Country {
+    /// Currency trades in this market settle in when none is given
+    pub fn currency(&self) -> Currency {
+        match self {
+            Country::US => Currency::USD,
+            Country::CA => Currency::CAD,
+            Country::UK => Currency::GBP,
+            Country::AU => Currency::AUD,
+        }
+    }
+}
 impl std::convert::From<std::string::String> for TradeType {
     fn from(s: std::string::String) -> Self {
         match s.to_lowercase().as_str() {
@@ -52,16 +67,17 @@ impl std::convert::From<std::string::String> for TradeType {
 }
 
 impl TradeModel {
-    pub async fn insert(&self, db_pool: &sqlx::PgPool) -> Result<TradeModel, sqlx::Error> {
+    pub async fn insert(&self, market_data: Arc<dyn MarketDataProvider>, db_pool: &sqlx::PgPool) -> Result<TradeModel, sqlx::Error> {
         let result = sqlx::query_as!(
             TradeModel,
-            r#"INSERT INTO trades_history (ticker, amount, date, country, price, trade_type) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *"#,
+            r#"INSERT INTO trades_history (ticker, amount, date, country, price, trade_type, currency) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id, ticker, amount, date, country, price, trade_type, currency AS "currency: Currency""#,
             self.ticker,
             self.amount,
             self.date,
             self.country.to_string(),
             self.price,
-            self.trade_type.to_string()
+            self.trade_type.to_string(),
+            self.currency.to_string()
         ).fetch_one(db_pool).await;
         match result {
             Ok(result) => {
@@ -78,7 +94,7 @@ impl TradeModel {
                 let ticker = self.ticker.clone();
                 spawn(async move {
                     let _ = stock.update_if_exists_or_create(&db_clone).await;
-                    let _ = QuoteModel::populate_ticker(ticker, &db_clone).await;
+                    let _ = QuoteModel::populate_ticker(ticker, market_data.as_ref(), &db_clone).await;
                 });
                 return Ok(result);
             },
@@ -90,37 +106,61 @@ impl TradeModel {
     pub async fn update(&self, db_pool: &sqlx::PgPool) -> Result<TradeModel, sqlx::Error> {
         sqlx::query_as!(
             TradeModel,
-            r#"UPDATE trades_history SET ticker = $1, amount = $2, date = $3, country = $4, price = $5, trade_type = $6 WHERE id = $7 RETURNING *"#,
+            r#"UPDATE trades_history SET ticker = $1, amount = $2, date = $3, country = $4, price = $5, trade_type = $6, currency = $7 WHERE id = $8 RETURNING id, ticker, amount, date, country, price, trade_type, currency AS "currency: Currency""#,
             self.ticker,
             self.amount,
             self.date,
             self.country.to_string(),
             self.price,
             self.trade_type.to_string(),
+            self.currency.to_string(),
             self.id
         ).fetch_one(db_pool).await
     }
     pub async fn delete(&self, db_pool: &sqlx::PgPool) -> Result<TradeModel, sqlx::Error> {
         sqlx::query_as!(
             TradeModel,
-            r#"DELETE FROM trades_history WHERE id = $1 RETURNING *"#,
+            r#"DELETE FROM trades_history WHERE id = $1 RETURNING id, ticker, amount, date, country, price, trade_type, currency AS "currency: Currency""#,
             self.id
         ).fetch_one(db_pool).await
     }
     pub async fn get_all(db_pool: &sqlx::PgPool) -> Result<Vec<TradeModel>, sqlx::Error> {
         sqlx::query_as!(
             TradeModel,
-            r#"SELECT * FROM trades_history ORDER BY date DESC"#
+            r#"SELECT id, ticker, amount, date, country, price, trade_type, currency AS "currency: Currency" FROM trades_history ORDER BY date DESC"#
+        ).fetch_all(db_pool).await
+    }
+    pub async fn get_by_ticker(ticker: String, db_pool: &sqlx::PgPool) -> Result<Vec<TradeModel>, sqlx::Error> {
+        sqlx::query_as!(
+            TradeModel,
+            r#"SELECT id, ticker, amount, date, country, price, trade_type, currency AS "currency: Currency" FROM trades_history WHERE ticker = $1 ORDER BY date ASC, id ASC"#,
+            ticker
         ).fetch_all(db_pool).await
     }
     pub async fn get_all_date_range(start: NaiveDate, end: NaiveDate, db_pool: &sqlx::PgPool) -> Result<Vec<TradeModel>, sqlx::Error> {
         sqlx::query_as!(
             TradeModel,
-            r#"SELECT * FROM trades_history WHERE date >= $1 AND date <= $2"#,
+            r#"SELECT id, ticker, amount, date, country, price, trade_type, currency AS "currency: Currency" FROM trades_history WHERE date >= $1 AND date <= $2"#,
             start,
             end
         ).fetch_all(db_pool).await
     }
+    /// Replays trades up to and including `date` to get the positions held at the end of that day
+    pub async fn get_holdings_at(date: NaiveDate, db_pool: &sqlx::PgPool) -> Result<Vec<StockModel>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"SELECT ticker, SUM(CASE WHEN trade_type = 'Sell' THEN -amount ELSE amount END) AS "amount_held!" FROM trades_history WHERE date <= $1 GROUP BY ticker"#,
+            date
+        ).fetch_all(db_pool).await?;
+        Ok(rows.into_iter()
+            .filter(|row| row.amount_held > 0)
+            .map(|row| StockModel {
+                id: -1,
+                ticker: row.ticker,
+                amount_held: row.amount_held as i32,
+                last_updated: date,
+            })
+            .collect())
+    }
     pub async fn delete_all(db_pool: &sqlx::PgPool) -> Result<PgQueryResult, sqlx::Error> {
         sqlx::query!(
             r#"DELETE FROM trades_history"#,
@@ -1,22 +1,58 @@
-use actix_rt::{spawn, time::interval};
+use tokio::{spawn, time::interval};
+use std::sync::Arc;
 use std::time::Duration;
+use crate::market_data::MarketDataProvider;
 use crate::models::quotes::QuoteModel;
+use crate::models::fx::FxRateModel;
+use crate::models::benchmarks::BenchmarkModel;
 use sqlx::postgres::PgPool;
-pub fn start(db_pool: PgPool) {
-    start_quote_updater(db_pool.clone());
+pub fn start(db_pool: PgPool, market_data: Arc<dyn MarketDataProvider>) {
+    start_quote_updater(db_pool.clone(), market_data.clone());
+    start_fx_updater(db_pool.clone(), market_data.clone());
+    start_benchmark_updater(db_pool.clone(), market_data.clone());
 }
 
-fn start_quote_updater(db_pool: PgPool) {
+fn start_quote_updater(db_pool: PgPool, market_data: Arc<dyn MarketDataProvider>) {
     spawn(async move {
         let mut interval = interval(Duration::from_secs(60*60*24));
         loop {
             interval.tick().await;
             println!("🚀 Updating quotes...");
-            let result = QuoteModel::update_quotes(&db_pool).await;
+            let result = QuoteModel::update_quotes(market_data.as_ref(), &db_pool).await;
             match result {
                 Ok(_) => println!("✅ Quotes updated successfully!"),
                 Err(err) => println!("🔥 Failed to update quotes: {:?}", err)
             }
         }
     });
+}
+
+fn start_fx_updater(db_pool: PgPool, market_data: Arc<dyn MarketDataProvider>) {
+    spawn(async move {
+        let mut interval = interval(Duration::from_secs(60*60*24));
+        loop {
+            interval.tick().await;
+            println!("🚀 Updating FX rates...");
+            let result = FxRateModel::update_rates(market_data.as_ref(), &db_pool).await;
+            match result {
+                Ok(_) => println!("✅ FX rates updated successfully!"),
+                Err(err) => println!("🔥 Failed to update FX rates: {:?}", err)
+            }
+        }
+    });
+}
+
+fn start_benchmark_updater(db_pool: PgPool, market_data: Arc<dyn MarketDataProvider>) {
+    spawn(async move {
+        let mut interval = interval(Duration::from_secs(60*60*24));
+        loop {
+            interval.tick().await;
+            println!("🚀 Updating benchmarks...");
+            let result = BenchmarkModel::update_benchmarks(market_data.as_ref(), &db_pool).await;
+            match result {
+                Ok(_) => println!("✅ Benchmarks updated successfully!"),
+                Err(err) => println!("🔥 Failed to update benchmarks: {:?}", err)
+            }
+        }
+    });
 }
\ No newline at end of file
@@ -1,7 +1,19 @@
 pub mod stocks;
 pub mod quotes;
 pub mod trades;
+pub mod lots;
+pub mod pnl;
+pub mod tax;
+pub mod dividends;
+pub mod corporate_actions;
+pub mod performance;
+pub mod benchmarks;
+pub mod risk;
+pub mod allocations;
+pub mod modelling;
 use serde::{Deserialize, Serialize};
+use chrono::NaiveDate;
+use crate::models::fx::Currency;
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
 pub struct Pagination {
     #[serde(default="default_page")]
@@ -14,4 +26,10 @@ fn default_page_size() -> i64 {
 }
 fn default_page() -> i64 {
     0
+}
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct AsOfQuery {
+    pub as_of: Option<NaiveDate>,
+    #[serde(default)]
+    pub base: Currency,
 }
\ No newline at end of file
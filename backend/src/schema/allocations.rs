@@ -0,0 +1,70 @@
+use std::convert::{From, Into};
+use bigdecimal::{FromPrimitive, ToPrimitive};
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
+use crate::models::allocations::TargetAllocationModel;
+use crate::models::fx::Currency;
+
+#[derive(Deserialize, Serialize)]
+pub struct TargetAllocationJson {
+    pub ticker: String,
+    /// Fraction of the portfolio's value, 0 to 1
+    pub weight: f64,
+}
+
+impl From<TargetAllocationModel> for TargetAllocationJson {
+    fn from(model: TargetAllocationModel) -> Self {
+        Self {
+            ticker: model.ticker,
+            weight: model.weight.to_f64().unwrap_or(0.0),
+        }
+    }
+}
+
+impl Into<TargetAllocationModel> for TargetAllocationJson {
+    fn into(self) -> TargetAllocationModel {
+        TargetAllocationModel {
+            id: -1,
+            portfolio: String::new(),
+            ticker: self.ticker,
+            weight: BigDecimal::from_f64(self.weight).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AllocationQuery {
+    #[serde(default = "default_portfolio")]
+    pub portfolio: String,
+    #[serde(default)]
+    pub base: Currency,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DriftQuery {
+    #[serde(default = "default_portfolio")]
+    pub portfolio: String,
+    #[serde(default)]
+    pub base: Currency,
+    /// Cash held alongside the holdings, in the base currency
+    #[serde(default)]
+    pub cash: f64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RebalanceQuery {
+    #[serde(default = "default_portfolio")]
+    pub portfolio: String,
+    #[serde(default)]
+    pub base: Currency,
+    /// New cash to invest, in the base currency
+    #[serde(default)]
+    pub cash: f64,
+    /// Only propose buys, leaving overweight holdings alone
+    #[serde(default)]
+    pub buy_only: bool,
+}
+
+fn default_portfolio() -> String {
+    "default".to_string()
+}
\ No newline at end of file
@@ -0,0 +1,30 @@
+use std::convert::{From, Into};
+
+use crate::models::benchmarks::BenchmarkModel;
+use serde::{Deserialize, Serialize};
+#[derive(Deserialize, Serialize)]
+pub struct BenchmarkJson {
+    pub id: Option<i32>,
+    pub ticker: String,
+    pub name: Option<String>,
+}
+
+impl From<BenchmarkModel> for BenchmarkJson {
+    fn from(model: BenchmarkModel) -> Self {
+        Self {
+            id: Some(model.id),
+            ticker: model.ticker,
+            name: model.name,
+        }
+    }
+}
+
+impl Into<BenchmarkModel> for BenchmarkJson {
+    fn into(self) -> BenchmarkModel {
+        BenchmarkModel {
+            id: self.id.unwrap_or(-1),
+            ticker: self.ticker,
+            name: self.name,
+        }
+    }
+}
\ No newline at end of file
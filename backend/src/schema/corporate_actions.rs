@@ -0,0 +1,76 @@
+use std::convert::{From, Into};
+
+use bigdecimal::ToPrimitive;
+use chrono::{NaiveDate, NaiveDateTime};
+use crate::models::corporate_actions::{Adjustments, CorporateActionModel, CorporateActionType};
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
+use bigdecimal::FromPrimitive;
+#[derive(Deserialize, Serialize)]
+pub struct CorporateActionJson {
+    pub id: Option<i32>,
+    pub ticker: String,
+    pub action_type: CorporateActionType,
+    pub effective_date: NaiveDate,
+    /// A 2-for-1 split is `ratio_from` 1 and `ratio_to` 2
+    #[serde(default = "default_ratio")]
+    pub ratio_from: i32,
+    #[serde(default = "default_ratio")]
+    pub ratio_to: i32,
+    pub new_ticker: Option<String>,
+    #[serde(default)]
+    pub cash_per_share: f64,
+    #[serde(default)]
+    pub applied_at: Option<NaiveDateTime>,
+    /// Fraction of a new share paid out in cash when the action was applied
+    #[serde(default)]
+    pub fractional_shares: f64,
+    #[serde(default)]
+    pub cash_in_lieu: Option<f64>,
+}
+
+fn default_ratio() -> i32 {
+    1
+}
+
+impl From<CorporateActionModel> for CorporateActionJson {
+    fn from(model: CorporateActionModel) -> Self {
+        Self {
+            id: Some(model.id),
+            ticker: model.ticker,
+            action_type: model.action_type,
+            effective_date: model.effective_date,
+            ratio_from: model.ratio_from,
+            ratio_to: model.ratio_to,
+            new_ticker: model.new_ticker,
+            cash_per_share: model.cash_per_share.to_f64().unwrap_or(0.0),
+            applied_at: model.applied_at,
+            fractional_shares: model.fractional_shares.to_f64().unwrap_or(0.0),
+            cash_in_lieu: model.cash_in_lieu.and_then(|cash| cash.to_f64()),
+        }
+    }
+}
+
+impl Into<CorporateActionModel> for CorporateActionJson {
+    fn into(self) -> CorporateActionModel {
+        CorporateActionModel {
+            id: self.id.unwrap_or(-1),
+            ticker: self.ticker,
+            action_type: self.action_type,
+            effective_date: self.effective_date,
+            ratio_from: self.ratio_from,
+            ratio_to: self.ratio_to,
+            new_ticker: self.new_ticker,
+            cash_per_share: BigDecimal::from_f64(self.cash_per_share).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
+            applied_at: None,
+            fractional_shares: BigDecimal::from_f64(0.0).unwrap(),
+            cash_in_lieu: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CorporateActionResultJson {
+    pub action: CorporateActionJson,
+    pub adjustments: Adjustments,
+}
\ No newline at end of file
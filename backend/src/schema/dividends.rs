@@ -0,0 +1,95 @@
+use std::convert::{From, Into};
+
+use bigdecimal::ToPrimitive;
+use chrono::NaiveDate;
+use crate::analytics::tax::au::COMPANY_TAX_RATE;
+use crate::models::dividends::{DividendError, DividendModel};
+use crate::models::fx::Currency;
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
+use bigdecimal::FromPrimitive;
+#[derive(Deserialize, Serialize)]
+pub struct DividendJson {
+    pub id: Option<i32>,
+    pub ticker: String,
+    pub ex_date: NaiveDate,
+    pub pay_date: NaiveDate,
+    pub amount_per_share: f64,
+    /// Share of the dividend withheld at source, e.g. 0.15 for 15%
+    #[serde(default)]
+    pub withholding_rate: f64,
+    /// Percentage of an AU dividend that is franked, 0 to 100
+    #[serde(default)]
+    pub franking_percent: f64,
+    /// Rate the company paid tax at, defaults to the 30% corporate rate
+    #[serde(default = "default_company_tax_rate")]
+    pub company_tax_rate: f64,
+    /// Defaults to the currency the ticker is quoted in
+    pub currency: Option<Currency>,
+}
+
+fn default_company_tax_rate() -> f64 {
+    COMPANY_TAX_RATE
+}
+
+/// Largest amount per share that fits the NUMERIC(12,6) column
+const MAX_AMOUNT_PER_SHARE: f64 = 1_000_000.0;
+
+impl DividendJson {
+    /// Rejects values the dividends table can't store or that make no sense for a dividend
+    pub fn validate(&self) -> Result<(), DividendError> {
+        if !(0.0..MAX_AMOUNT_PER_SHARE).contains(&self.amount_per_share) {
+            return Err(DividendError::Invalid(format!("amount per share must be at least 0 and less than {}", MAX_AMOUNT_PER_SHARE)));
+        }
+        if !(0.0..=1.0).contains(&self.withholding_rate) {
+            return Err(DividendError::Invalid("withholding rate must be between 0 and 1".to_string()));
+        }
+        if !(0.0..1.0).contains(&self.company_tax_rate) {
+            return Err(DividendError::Invalid("company tax rate must be at least 0 and less than 1".to_string()));
+        }
+        if self.pay_date < self.ex_date {
+            return Err(DividendError::Invalid("pay date can't be before the ex-date".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl From<DividendModel> for DividendJson {
+    fn from(model: DividendModel) -> Self {
+        Self {
+            id: Some(model.id),
+            ticker: model.ticker,
+            ex_date: model.ex_date,
+            pay_date: model.pay_date,
+            amount_per_share: model.amount_per_share.to_f64().unwrap_or(0.0),
+            withholding_rate: model.withholding_rate.to_f64().unwrap_or(0.0),
+            franking_percent: model.franking_percent.to_f64().unwrap_or(0.0),
+            company_tax_rate: model.company_tax_rate.to_f64().unwrap_or(COMPANY_TAX_RATE),
+            currency: Some(model.currency),
+        }
+    }
+}
+
+impl Into<DividendModel> for DividendJson {
+    fn into(self) -> DividendModel {
+        DividendModel {
+            id: self.id.unwrap_or(-1),
+            currency: self.currency.unwrap_or(Currency::from_ticker(&self.ticker)),
+            ticker: self.ticker,
+            ex_date: self.ex_date,
+            pay_date: self.pay_date,
+            amount_per_share: BigDecimal::from_f64(self.amount_per_share).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
+            withholding_rate: BigDecimal::from_f64(self.withholding_rate).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
+            franking_percent: BigDecimal::from_f64(self.franking_percent.clamp(0.0, 100.0)).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
+            company_tax_rate: BigDecimal::from_f64(self.company_tax_rate).unwrap_or(BigDecimal::from_f64(COMPANY_TAX_RATE).unwrap()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct IncomeQuery {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+    #[serde(default)]
+    pub base: Currency,
+}
\ No newline at end of file
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+use crate::analytics::lots::{CostBasisMethod, Lot, LotEngine, Disposal};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct LotQuery {
+    #[serde(default)]
+    pub method: CostBasisMethod,
+    pub ticker: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct HoldingLotsJson {
+    pub ticker: String,
+    pub method: CostBasisMethod,
+    pub quantity: f64,
+    pub cost_basis: f64,
+    pub average_cost: Option<f64>,
+    pub lots: Vec<Lot>,
+}
+
+impl HoldingLotsJson {
+    pub fn from_engine(engine: &LotEngine, ticker: &str) -> Self {
+        Self {
+            ticker: ticker.to_string(),
+            method: engine.method,
+            quantity: engine.quantity(ticker),
+            cost_basis: engine.cost_basis(ticker),
+            average_cost: engine.average_cost(ticker),
+            lots: engine.open_lots(ticker).to_vec(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DisposalsJson {
+    pub method: CostBasisMethod,
+    pub realised_gain: f64,
+    pub disposals: Vec<Disposal>,
+}
\ No newline at end of file
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use chrono::{Duration, Months, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use crate::analytics::markov::DEFAULT_BUCKETS;
+use crate::models::fx::Currency;
+use crate::models::markov::MarkovFitModel;
+use crate::models::scenarios::{ScenarioModel, ScenarioResultModel};
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MarkovFitRequest {
+    /// Defaults to every active ticker
+    pub tickers: Option<Vec<String>>,
+    /// Defaults to five years before `end`
+    pub start: Option<NaiveDate>,
+    /// Defaults to today
+    pub end: Option<NaiveDate>,
+    #[serde(default = "default_buckets")]
+    pub buckets: usize,
+}
+
+fn default_buckets() -> usize {
+    DEFAULT_BUCKETS
+}
+
+/// Price model for a simulation run. Everything but fixed returns is estimated from the daily
+/// total returns of the tickers involved.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PriceModelConfig {
+    /// A stored Markov model, or one fitted for the run with `buckets` return buckets
+    Markov {
+        markov_model_id: Option<i32>,
+        #[serde(default = "default_buckets")]
+        buckets: usize,
+    },
+    /// Geometric Brownian motion with each ticker's historical drift and volatility
+    Gbm,
+    /// Resampled blocks of `block_days` consecutive historical days
+    Bootstrap {
+        #[serde(default = "default_block_days")]
+        block_days: usize,
+    },
+    /// The same annual return every year, 0.07 for 7%
+    Fixed {
+        annual_return: f64,
+    },
+}
+
+impl Default for PriceModelConfig {
+    fn default() -> Self {
+        PriceModelConfig::Markov {
+            markov_model_id: None,
+            buckets: DEFAULT_BUCKETS,
+        }
+    }
+}
+
+fn default_block_days() -> usize {
+    20
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct MarkovFitJson {
+    pub id: i32,
+    pub tickers: Vec<String>,
+    pub buckets: i32,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// Per ticker, the probability of moving from the row's bucket to the column's
+    pub transitions: Vec<Vec<Vec<f64>>>,
+    /// Per ticker, the daily return each bucket steps prices by
+    pub bucket_returns: Vec<Vec<f64>>,
+    pub initial_states: Vec<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<MarkovFitModel> for MarkovFitJson {
+    fn from(model: MarkovFitModel) -> Self {
+        let buckets = model.buckets.max(1) as usize;
+        Self {
+            id: model.id,
+            transitions: model.transitions
+                .chunks(buckets * buckets)
+                .map(|matrix| matrix.chunks(buckets).map(|row| row.to_vec()).collect())
+                .collect(),
+            bucket_returns: model.bucket_returns.chunks(buckets).map(|row| row.to_vec()).collect(),
+            tickers: model.tickers,
+            buckets: model.buckets,
+            start: model.start_date,
+            end: model.end_date,
+            initial_states: model.initial_states,
+            created_at: model.created_at,
+        }
+    }
+}
+
+/// How far the simulated clock moves each step
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StepSize {
+    Day,
+    Week,
+    #[default]
+    Month,
+}
+impl StepSize {
+    /// Date `steps` steps after `start`. Months are counted from the start so a run starting on
+    /// the 31st keeps landing on the last day of each month rather than drifting to the 28th.
+    pub fn advance(&self, start: NaiveDate, steps: i32) -> NaiveDate {
+        match self {
+            StepSize::Day => start + Duration::days(steps as i64),
+            StepSize::Week => start + Duration::weeks(steps as i64),
+            StepSize::Month => start + Months::new(steps as u32),
+        }
+    }
+    /// Most steps there can be in one calendar year
+    pub fn max_steps_per_year(&self) -> u64 {
+        match self {
+            StepSize::Day => 366,
+            StepSize::Week => 53,
+            StepSize::Month => 12,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FireRequest {
+    /// A year's spending in the base currency
+    pub expenses: f64,
+    /// Share of the portfolio that can be spent each year, 4% by default
+    #[serde(default = "default_fire_rate")]
+    pub fire_rate: f64,
+    /// Annual growth of expenses
+    #[serde(default)]
+    pub inflation: f64,
+    #[serde(default)]
+    pub monthly_investment: f64,
+    /// Weights new money is invested with, defaults to the current holdings' weights
+    pub allocation: Option<HashMap<String, f64>>,
+    #[serde(default = "default_paths")]
+    pub paths: usize,
+    #[serde(default = "default_years")]
+    pub years: u32,
+    #[serde(default)]
+    pub step: StepSize,
+    #[serde(default)]
+    pub seed: u64,
+    /// How prices move from one step to the next
+    #[serde(default)]
+    pub model: PriceModelConfig,
+    #[serde(default)]
+    pub base: Currency,
+}
+
+fn default_fire_rate() -> f64 {
+    0.04
+}
+
+fn default_paths() -> usize {
+    1000
+}
+
+fn default_years() -> u32 {
+    30
+}
+
+/// What ends a scenario's paths, or records when they succeed
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CriteriaConfig {
+    /// `fire_rate` of the portfolio covers a year's `expenses`, growing with `inflation`
+    BasicFire {
+        expenses: f64,
+        #[serde(default = "default_fire_rate")]
+        fire_rate: f64,
+        #[serde(default)]
+        inflation: f64,
+    },
+}
+
+/// Things that change holdings each step besides prices
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FactorConfig {
+    /// Invests `monthly_investment` across `allocation`, or the starting weights, every month
+    MonthlyInvestment {
+        monthly_investment: f64,
+        allocation: Option<HashMap<String, f64>>,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AssetConfig {
+    pub ticker: String,
+    pub amount_held: i64,
+}
+
+/// Everything needed to run a model. Runs start on the day they are made.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ScenarioConfig {
+    /// Defaults to the current holdings
+    pub assets: Option<Vec<AssetConfig>>,
+    #[serde(default = "default_years")]
+    pub years: u32,
+    #[serde(default)]
+    pub step: StepSize,
+    /// Stop a path as soon as the criteria are met rather than running every year. Values for
+    /// later years are then taken over the paths still running.
+    #[serde(default)]
+    pub stop_when_complete: bool,
+    #[serde(default)]
+    pub model: PriceModelConfig,
+    pub criteria: CriteriaConfig,
+    #[serde(default)]
+    pub factors: Vec<FactorConfig>,
+    #[serde(default = "default_paths")]
+    pub paths: usize,
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub base: Currency,
+}
+
+impl From<FireRequest> for ScenarioConfig {
+    fn from(request: FireRequest) -> Self {
+        Self {
+            assets: None,
+            years: request.years,
+            step: request.step,
+            stop_when_complete: false,
+            model: request.model,
+            criteria: CriteriaConfig::BasicFire {
+                expenses: request.expenses,
+                fire_rate: request.fire_rate,
+                inflation: request.inflation,
+            },
+            factors: vec![FactorConfig::MonthlyInvestment {
+                monthly_investment: request.monthly_investment,
+                allocation: request.allocation,
+            }],
+            paths: request.paths,
+            seed: request.seed,
+            base: request.base,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ScenarioJson {
+    pub id: Option<i32>,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub config: ScenarioConfig,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl TryFrom<ScenarioModel> for ScenarioJson {
+    type Error = serde_json::Error;
+    fn try_from(model: ScenarioModel) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: Some(model.id),
+            name: model.name,
+            description: model.description,
+            config: serde_json::from_str(&model.config)?,
+            created_at: Some(model.created_at),
+        })
+    }
+}
+
+impl TryInto<ScenarioModel> for ScenarioJson {
+    type Error = serde_json::Error;
+    fn try_into(self) -> Result<ScenarioModel, Self::Error> {
+        Ok(ScenarioModel {
+            id: self.id.unwrap_or(-1),
+            name: self.name,
+            description: self.description,
+            config: serde_json::to_string(&self.config)?,
+            created_at: self.created_at.unwrap_or(chrono::Utc::now().naive_utc()),
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ScenarioResultJson {
+    pub id: i32,
+    pub scenario_id: i32,
+    pub result: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
+
+impl TryFrom<ScenarioResultModel> for ScenarioResultJson {
+    type Error = serde_json::Error;
+    fn try_from(model: ScenarioResultModel) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: model.id,
+            scenario_id: model.scenario_id,
+            result: serde_json::from_str(&model.result)?,
+            created_at: model.created_at,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CompareQuery {
+    pub left: i32,
+    pub right: i32,
+}
\ No newline at end of file
@@ -0,0 +1,31 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::analytics::performance::{Interval, Period};
+use crate::models::fx::Currency;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct PerformanceQuery {
+    /// Takes precedence over `start` when given
+    pub period: Option<Period>,
+    /// Defaults to the first trade
+    pub start: Option<NaiveDate>,
+    /// Defaults to today
+    pub end: Option<NaiveDate>,
+    #[serde(default)]
+    pub base: Currency,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct HistoryQuery {
+    /// Defaults to the first trade
+    pub start: Option<NaiveDate>,
+    /// Defaults to today
+    pub end: Option<NaiveDate>,
+    #[serde(default)]
+    pub base: Currency,
+    #[serde(default)]
+    pub interval: Interval,
+    /// Include each ticker's value in every point
+    #[serde(default)]
+    pub breakdown: bool,
+}
\ No newline at end of file
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+use chrono::NaiveDate;
+use crate::analytics::lots::CostBasisMethod;
+use crate::models::fx::Currency;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct PnlQuery {
+    #[serde(default)]
+    pub method: CostBasisMethod,
+    pub as_of: Option<NaiveDate>,
+    #[serde(default)]
+    pub base: Currency,
+}
\ No newline at end of file
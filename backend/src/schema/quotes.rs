@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 use chrono::NaiveDate;
+use crate::analytics::indicators::IndicatorKind;
+use crate::analytics::prices::PriceAdjustment;
 use crate::models::quotes::QuoteModel;
+use crate::models::fx::Currency;
 use std::convert::{From, Into};
 
 use bigdecimal::{ToPrimitive, FromPrimitive};
@@ -13,6 +16,9 @@ pub struct QuoteJson {
     pub low: f64,
     pub close: f64,
     pub volume: i64,
+    pub currency: Option<Currency>,
+    /// Defaults to the close, dividend adjustments are worked out by the backend
+    pub adjusted_close: Option<f64>,
 }
 
 impl From<QuoteModel> for QuoteJson {
@@ -25,19 +31,62 @@ impl From<QuoteModel> for QuoteJson {
             low: model.low.to_f64().unwrap_or(0.0),
             close: model.close.to_f64().unwrap_or(0.0),
             volume: model.volume.to_i64().unwrap_or(0),
+            currency: Some(model.currency),
+            adjusted_close: model.adjusted_close.to_f64(),
         }
     }
 }
 impl Into<QuoteModel> for QuoteJson {
     fn into(self) -> QuoteModel {
         QuoteModel {
+            currency: self.currency.unwrap_or(Currency::from_ticker(&self.ticker)),
             ticker: self.ticker,
             date: self.date,
             open: FromPrimitive::from_f64(self.open).unwrap_or(FromPrimitive::from_f64(0.0).unwrap()),
             high: FromPrimitive::from_f64(self.high).unwrap_or(FromPrimitive::from_f64(0.0).unwrap()),
             low: FromPrimitive::from_f64(self.low).unwrap_or(FromPrimitive::from_f64(0.0).unwrap()),
             close: FromPrimitive::from_f64(self.close).unwrap_or(FromPrimitive::from_f64(0.0).unwrap()),
+            adjusted_close: FromPrimitive::from_f64(self.adjusted_close.unwrap_or(self.close)).unwrap_or(FromPrimitive::from_f64(0.0).unwrap()),
             volume: FromPrimitive::from_i64(self.volume).unwrap_or(FromPrimitive::from_i64(0).unwrap()),
         }
     }
 }
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct QuoteRangeQuery {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+    #[serde(default)]
+    pub adjustment: PriceAdjustment,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct IndicatorQuery {
+    pub kind: IndicatorKind,
+    /// Days in the indicator's window, or the signal period for MACD. Defaults per kind.
+    pub window: Option<usize>,
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+    #[serde(default)]
+    pub adjustment: PriceAdjustment,
+    /// MACD fast and slow periods
+    #[serde(default = "default_fast")]
+    pub fast: usize,
+    #[serde(default = "default_slow")]
+    pub slow: usize,
+    /// Width of the Bollinger Bands in standard deviations
+    #[serde(default = "default_deviations")]
+    pub deviations: f64,
+}
+
+fn default_fast() -> usize {
+    12
+}
+
+fn default_slow() -> usize {
+    26
+}
+
+fn default_deviations() -> f64 {
+    2.0
+}
\ No newline at end of file
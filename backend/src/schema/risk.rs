@@ -0,0 +1,39 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::analytics::performance::Period;
+use crate::models::fx::Currency;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RiskQuery {
+    /// Takes precedence over `start` when given
+    pub period: Option<Period>,
+    /// Defaults to the first trade, or a year before `end` for a single ticker
+    pub start: Option<NaiveDate>,
+    /// Defaults to today
+    pub end: Option<NaiveDate>,
+    #[serde(default)]
+    pub base: Currency,
+    /// Annual rate, e.g. 0.04 for 4%
+    #[serde(default)]
+    pub risk_free_rate: f64,
+    /// Confidence level for value at risk
+    #[serde(default = "default_confidence")]
+    pub confidence: f64,
+}
+
+fn default_confidence() -> f64 {
+    0.95
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CorrelationQuery {
+    /// Number of daily returns to use
+    #[serde(default = "default_window")]
+    pub window: usize,
+    /// Defaults to today
+    pub end: Option<NaiveDate>,
+}
+
+fn default_window() -> usize {
+    252
+}
\ No newline at end of file
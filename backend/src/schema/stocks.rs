@@ -1,16 +1,26 @@
 use serde::{Deserialize, Serialize};
 use chrono::NaiveDate;
 use crate::models::stocks::StockModel;
-use yahoo_finance_api as yahoo;
+use crate::models::quotes::QuoteModel;
+use crate::models::fx::Currency;
+use bigdecimal::ToPrimitive;
 use strum_macros::{EnumString, Display};
 
+/// A holding priced from a quote more than this many days older than the valuation date is stale
+pub const STALE_AFTER_DAYS: i64 = 5;
+
 #[derive(Deserialize, Serialize)]
 pub struct StockJson {
     pub id: Option<i32>,
     pub ticker: String,
     pub amount_held: i32,
     pub last_updated: Option<NaiveDate>,
-    pub value: Option<f64>
+    pub value: Option<f64>,
+    pub as_of: Option<NaiveDate>,
+    pub stale: Option<bool>,
+    pub currency: Option<Currency>,
+    /// Rate used to convert `value` into the portfolio's base currency
+    pub fx_rate: Option<f64>,
 }
 impl StockJson {
     pub fn from_model(model: StockModel) -> Self {
@@ -19,18 +29,27 @@ impl StockJson {
             ticker: model.ticker,
             amount_held: model.amount_held,
             last_updated: Some(model.last_updated),
-            value: None
+            value: None,
+            as_of: None,
+            stale: None,
+            currency: None,
+            fx_rate: None,
         }
     }
-    pub async fn calculate_value(&mut self) {
-        let provider = yahoo::YahooConnector::new();
-        let resp = provider.get_latest_quotes(&self.ticker, "1d").await;
-        match resp {
-            Ok(resp) => {
-                self.value = Some(resp.quotes().unwrap_or_default()[0].open);
+    /// Prices the holding at the latest close on or before `as_of` from the quotes table
+    pub async fn value_from_quotes(&mut self, as_of: NaiveDate, db_pool: &sqlx::PgPool) {
+        let quote = QuoteModel::get_closest_date(self.ticker.clone(), as_of, db_pool).await;
+        match quote {
+            Ok(quote) => {
+                self.value = Some(quote.close.to_f64().unwrap_or(0.0));
+                self.as_of = Some(quote.date);
+                self.stale = Some((as_of - quote.date).num_days() > STALE_AFTER_DAYS);
+                self.currency = Some(quote.currency);
             },
             Err(_) => {
-                self.value = Some(0.0);
+                self.value = None;
+                self.as_of = None;
+                self.stale = Some(true);
             }
         }
     }
@@ -43,7 +62,11 @@ impl From<StockModel> for StockJson {
             ticker: model.ticker,
             amount_held: model.amount_held,
             last_updated: Some(model.last_updated),
-            value: None
+            value: None,
+            as_of: None,
+            stale: None,
+            currency: None,
+            fx_rate: None,
         }
     }
 }
@@ -52,6 +75,9 @@ impl From<StockModel> for StockJson {
 pub struct PortfolioJson {
     pub stocks: Vec<StockJson>,
     pub total: f64,
+    pub as_of: NaiveDate,
+    pub stale: bool,
+    pub base: Currency,
 }
 
 #[derive(Deserialize, Serialize)]
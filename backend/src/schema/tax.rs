@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use crate::analytics::lots::CostBasisMethod;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct CgtQuery {
+    /// Year the financial year ends in, defaults to the financial year containing today
+    pub year: Option<i32>,
+    #[serde(default)]
+    pub method: CostBasisMethod,
+    #[serde(default)]
+    pub format: ExportFormat,
+    #[serde(default)]
+    pub carried_forward_loss: f64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct TaxYearQuery {
+    /// Year the tax year ends in, defaults to the tax year containing today
+    pub year: Option<i32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct TaxReportQuery {
+    /// Year the tax year ends in, defaults to the tax year containing today
+    pub year: Option<i32>,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
\ No newline at end of file
@@ -3,6 +3,7 @@ use std::convert::{From, Into};
 use bigdecimal::ToPrimitive;
 use chrono::NaiveDate;
 use crate::models::trades::{Country, TradeType, TradeModel};
+use crate::models::fx::Currency;
 use serde::{Deserialize, Serialize};
 use sqlx::types::BigDecimal;
 use bigdecimal::FromPrimitive;
@@ -15,6 +16,8 @@ pub struct TradeJson {
     pub country: Country,
     pub price: f64,
     pub trade_type: TradeType,
+    /// Defaults to the currency of the trade's country
+    pub currency: Option<Currency>,
 }
 
 impl From<TradeModel> for TradeJson {
@@ -27,6 +30,7 @@ impl From<TradeModel> for TradeJson {
             country: model.country,
             price: model.price.to_f64().unwrap_or(0.0),
             trade_type: model.trade_type,
+            currency: Some(model.currency),
         }
     }
 }
@@ -41,6 +45,7 @@ impl Into<TradeModel> for TradeJson {
             country: self.country,
             price: BigDecimal::from_f64(self.price).unwrap_or(BigDecimal::from_f64(0.0).unwrap()),
             trade_type: self.trade_type,
+            currency: self.currency.unwrap_or(self.country.currency()),
         }
     }
 }
\ No newline at end of file